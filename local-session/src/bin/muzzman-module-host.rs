@@ -0,0 +1,29 @@
+//! Loads a dynamic module and runs it for a session, used by ModuleSource::Process
+//! The session talks with the host through a unix socket given as stdin
+
+#[cfg(unix)]
+fn main() {
+    use std::os::fd::AsFd;
+
+    let mut args = std::env::args_os().skip(1);
+    let Some(module) = args.next() else {
+        eprintln!("Usage: muzzman-module-host <module>");
+        std::process::exit(2);
+    };
+    let stream = std::io::stdin()
+        .as_fd()
+        .try_clone_to_owned()
+        .map(std::os::unix::net::UnixStream::from);
+    let res =
+        stream.and_then(|stream| muzzman_local_session::serve_module(stream, module.as_ref()));
+    if let Err(error) = res {
+        eprintln!("Module host error: {error}");
+        std::process::exit(1);
+    }
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("The module host is only supported on unix");
+    std::process::exit(1);
+}
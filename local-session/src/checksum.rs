@@ -0,0 +1,109 @@
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use muzzman_lib::prelude::*;
+use sha2::Digest;
+
+use crate::{partial_file, settings::element_setting, ElementWraper, TLocalSession};
+
+/// Hashes the completed element with Session.ChecksumAlgorithm and stores the digest in the element data
+/// If Session.Checksum is set they are compared, the settings can be on the element or on its locations
+/// When they don't match the file is renamed to `path.corrupt`, with Session.PartialFile only `path.part` was written
+pub(crate) fn verify(session: &dyn TLocalSession, element: &ElementWraper) -> SessionResult<()> {
+    let Some(algorithm) = element_setting(session, element, session_settings::CHECKSUM_ALGORITHM)
+        .and_then(|atom| atom.as_str().map(str::to_string))
+        .filter(|algorithm| !algorithm.is_empty())
+    else {
+        return Ok(());
+    };
+    let expected = element_setting(session, element, session_settings::CHECKSUM)
+        .and_then(|atom| atom.as_str().map(|expected| expected.trim().to_string()))
+        .filter(|expected| !expected.is_empty());
+
+    let source = {
+        let mut element = element.element.write().unwrap();
+        element.stream.flush()?;
+        if let Stream::Memory(cursor) = &element.stream {
+            Source::Memory(cursor.get_ref().clone())
+        } else if element.data.contains_key(element_data::PARTIAL_LENGTH) {
+            Source::File(partial_file::part_path(&element.path), true)
+        } else {
+            Source::File(element.path.clone(), false)
+        }
+    };
+
+    // the element can be used while a big file is hashed
+    let digest = match &source {
+        Source::Memory(bytes) => hash(&algorithm, bytes.as_slice())?,
+        Source::File(path, _) => hash(&algorithm, File::open(path)?)?,
+    };
+
+    let mut element = element.element.write().unwrap();
+    element
+        .data
+        .insert(element_data::CHECKSUM.into(), Atom::S(digest.clone()));
+    let Some(expected) = expected else {
+        return Ok(());
+    };
+    if !digest.eq_ignore_ascii_case(&expected) {
+        if let Source::File(path, false) = source {
+            element.stream = Stream::None;
+            std::fs::rename(&path, corrupt_path(&path))?;
+        }
+        return Err(SessionError::ChecksumMismatch(expected, digest));
+    }
+    Ok(())
+}
+
+enum Source {
+    Memory(Vec<u8>),
+    /// The path and if it is the `.part` file
+    File(PathBuf, bool),
+}
+
+fn corrupt_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".corrupt");
+    path.with_file_name(name)
+}
+
+fn hash(algorithm: &str, reader: impl Read) -> SessionResult<String> {
+    let digest = match algorithm {
+        "SHA-256" => hash_with::<sha2::Sha256>(reader)?,
+        "SHA-1" => hash_with::<sha1::Sha1>(reader)?,
+        "MD5" => hash_with::<md5::Md5>(reader)?,
+        "BLAKE3" => {
+            let mut hasher = blake3::Hasher::new();
+            copy(reader, |buffer| {
+                hasher.update(buffer);
+            })?;
+            hasher.finalize().as_bytes().to_vec()
+        }
+        _ => {
+            return Err(SessionError::InvalidSettings(vec![
+                session_settings::CHECKSUM_ALGORITHM.into(),
+            ]))
+        }
+    };
+    Ok(digest.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+fn hash_with<D: Digest>(reader: impl Read) -> SessionResult<Vec<u8>> {
+    let mut hasher = D::new();
+    copy(reader, |buffer| hasher.update(buffer))?;
+    Ok(hasher.finalize().to_vec())
+}
+
+fn copy(mut reader: impl Read, mut f: impl FnMut(&[u8])) -> std::io::Result<()> {
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let len = reader.read(&mut buffer)?;
+        if len == 0 {
+            return Ok(());
+        }
+        f(&buffer[..len]);
+    }
+}
@@ -0,0 +1,79 @@
+use std::path::{Path, PathBuf};
+
+use muzzman_lib::prelude::*;
+
+use crate::{
+    partial_file::part_path, settings::element_setting, ElementWraper, LocationWraper,
+    TLocalSession,
+};
+
+/// Applies Session.OnCollision for the element using `path`
+/// Returns the path that the element should use or None if the element should be skipped
+pub(crate) fn resolve(
+    session: &dyn TLocalSession,
+    element: &ElementWraper,
+    path: PathBuf,
+) -> SessionResult<Option<PathBuf>> {
+    let uid = element.element.read().unwrap().id.uid;
+    if path.file_name().is_none() || !is_used(session, &path, uid) {
+        return Ok(Some(path));
+    }
+
+    let policy = element_setting(session, element, session_settings::ON_COLLISION)
+        .and_then(|atom| atom.as_str().map(str::to_string))
+        .unwrap_or_else(|| "Rename".to_string());
+
+    match policy.as_str() {
+        "Overwrite" => Ok(Some(path)),
+        "Skip" => Ok(None),
+        "Rename" => {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let extension = path.extension().map(|e| e.to_string_lossy());
+            for i in 1.. {
+                let name = match &extension {
+                    Some(extension) => format!("{stem} ({i}).{extension}"),
+                    None => format!("{stem} ({i})"),
+                };
+                let new = path.with_file_name(name);
+                if !is_used(session, &new, uid) {
+                    return Ok(Some(new));
+                }
+            }
+            unreachable!()
+        }
+        "Fail" => Err(SessionError::PathCollision(path)),
+        _ => Err(SessionError::InvalidSettings(vec![
+            session_settings::ON_COLLISION.into(),
+        ])),
+    }
+}
+
+/// If there is a file or a partial file at path or other element has the same path
+fn is_used(session: &dyn TLocalSession, path: &Path, except: UID) -> bool {
+    if path.exists() || part_path(path).exists() {
+        return true;
+    }
+    let Ok(root) = session.location(0) else {
+        return false;
+    };
+    is_used_in(&root, path, except)
+}
+
+fn is_used_in(location: &LocationWraper, path: &Path, except: UID) -> bool {
+    let used = location.elements.read().unwrap().iter().any(|element| {
+        let file_claimed = element.file.read().unwrap().claimed;
+        let element = element.element.read().unwrap();
+        // only the elements that started claimed the path
+        let claimed = file_claimed
+            || element.enabled
+            || element.is_completed
+            || element.data.contains_key(element_data::PARTIAL_LENGTH);
+        claimed && element.id.uid != except && element.path == path
+    });
+    used || location
+        .locations
+        .read()
+        .unwrap()
+        .iter()
+        .any(|location| is_used_in(location, path, except))
+}
@@ -0,0 +1,195 @@
+//! Runs the module callbacks that get a ModuleContext and does the operations that they added
+//! after the locks of the element or location and of the module were released
+
+use std::sync::RwLock;
+
+use muzzman_lib::{prelude::*, Storage};
+
+use crate::{module_select, permissions, ModuleSession, ModuleWraper, TLocalSession, Wraper};
+
+/// Calls `f` as the module with `storage`, if `f` fails the operations are discarded
+pub(crate) fn call(
+    session: &dyn TLocalSession,
+    module: &ModuleWraper,
+    storage: &RwLock<Storage>,
+    f: impl FnOnce(&dyn TModule, &mut Storage, &mut ModuleContext) -> SessionResult<()>,
+) -> SessionResult<()> {
+    let mut context = ModuleContext::default();
+    {
+        let module_uid = module.uid;
+        let module = module.module.read().unwrap();
+        let mut storage = storage.write().unwrap();
+        permissions::run_as(module_uid, &module.permissions, || {
+            f(module.module.as_ref(), &mut storage, &mut context)
+        })?;
+    }
+    let ops = context.take();
+    if ops.is_empty() {
+        return Ok(());
+    }
+    // the operations are done by the module
+    let session = ModuleSession::new(session, module.uid);
+    let errors = ops
+        .into_iter()
+        .filter_map(|op| apply(session.as_ref(), op).err())
+        .collect::<Vec<SessionError>>();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(SessionError::Errors(errors))
+    }
+}
+
+fn apply(local: &dyn TLocalSession, op: ContextOp) -> SessionResult<()> {
+    let session = local.weak_clone();
+    match op {
+        ContextOp::CreateElement(location, element) => {
+            create_element(local, location_id(local, location)?, element)?;
+        }
+        ContextOp::CreateLocation(location, new) => {
+            create_location(local, location_id(local, location)?, new)?;
+        }
+        ContextOp::SetEnabled(element, enabled) => {
+            session.element_set_enabled(element_id(local, element)?, enabled)?;
+        }
+        ContextOp::SetUrl(element, url) => {
+            session.element_set_url(element_id(local, element)?, url)?;
+        }
+        ContextOp::SetModule(element, module) => {
+            let module = module.map(|uid| module_id(local, uid)).transpose()?;
+            session.element_set_module(element_id(local, element)?, module)?;
+        }
+        ContextOp::SetProxy(element, proxy) => {
+            let proxy = proxy.map(|uid| module_id(local, uid)).transpose()?;
+            session.element_set_proxy(element_id(local, element)?, proxy)?;
+        }
+        ContextOp::SetSetting(uid, name, value) => match local.get(uid)? {
+            Wraper::Element(_) => {
+                let element = element_id(local, uid)?;
+                let mut settings = session.element_get_settings(element.clone())?;
+                set_setting(&mut settings, name, value);
+                session.element_set_settings(element, settings)?;
+            }
+            Wraper::Location(_) => {
+                let location = location_id(local, uid)?;
+                let mut settings = session.location_get_settings(location.clone())?;
+                set_setting(&mut settings, name, value);
+                session.location_set_settings(location, settings)?;
+            }
+            _ => return Err(SessionError::IsNotAnElementOrLocation),
+        },
+        ContextOp::SetData(uid, name, value) => match local.get(uid)? {
+            Wraper::Element(_) => {
+                let element = element_id(local, uid)?;
+                let mut data = session.element_get_data(element.clone())?;
+                data.insert(name, value);
+                session.element_set_data(element, data)?;
+            }
+            Wraper::Location(_) => {
+                let location = location_id(local, uid)?;
+                let mut data = session.location_get_data(location.clone())?;
+                data.insert(name, value);
+                session.location_set_data(location, data)?;
+            }
+            _ => return Err(SessionError::IsNotAnElementOrLocation),
+        },
+        ContextOp::Emit(uid, event) => session.emit(uid, event)?,
+        ContextOp::Write(uid, data) => {
+            session.write(uid, &data)?;
+        }
+    }
+    Ok(())
+}
+
+fn element_id(session: &dyn TLocalSession, uid: UID) -> SessionResult<ElementId> {
+    let element = session.element(uid)?;
+    let id = element.element.read().unwrap().id.clone();
+    Ok(id)
+}
+
+fn location_id(session: &dyn TLocalSession, uid: UID) -> SessionResult<LocationId> {
+    let location = session.location(uid)?;
+    let id = location.location.read().unwrap().id.clone();
+    Ok(id)
+}
+
+fn module_id(session: &dyn TLocalSession, uid: UID) -> SessionResult<ModuleId> {
+    let module = session.module(uid)?;
+    Ok(ModuleId {
+        uid: module.uid,
+        session: Some(session.weak_host().weak_box().into()),
+    })
+}
+
+fn set_setting(settings: &mut Settings, name: String, value: Atom) {
+    match settings.get_mut(&name) {
+        Some(setting) => setting.value = value,
+        None => settings.add(name, Setting::new(value, Vec::<Atom>::new(), "")),
+    }
+}
+
+fn create_element(
+    local: &dyn TLocalSession,
+    location: LocationId,
+    new: NewElement,
+) -> SessionResult<ElementId> {
+    let module = match new.module {
+        Some(uid) => Some(local.module(uid)?),
+        None if !new.url.is_empty() => {
+            let parent = local.location(location.uid)?;
+            module_select::select(local, &parent, &new.url)
+        }
+        None => None,
+    };
+    let session = local.weak_clone();
+    let element = session.create_element(location, new.name)?;
+    if let Some(module) = module {
+        let module = ModuleId {
+            uid: module.uid,
+            session: Some(local.weak_host().weak_box().into()),
+        };
+        session.element_set_module(element.clone(), Some(module))?;
+    }
+    if !new.url.is_empty() {
+        session.element_set_url(element.clone(), new.url)?;
+    }
+    if !new.settings.is_empty() {
+        let mut settings = session.element_get_settings(element.clone())?;
+        for (name, value) in new.settings {
+            set_setting(&mut settings, name, value);
+        }
+        session.element_set_settings(element.clone(), settings)?;
+    }
+    if !new.data.is_empty() {
+        let mut data = session.element_get_data(element.clone())?;
+        data.extend(new.data);
+        session.element_set_data(element.clone(), data)?;
+    }
+    if new.enabled {
+        session.element_set_enabled(element.clone(), true)?;
+    }
+    Ok(element)
+}
+
+fn create_location(
+    local: &dyn TLocalSession,
+    parent: LocationId,
+    new: NewLocation,
+) -> SessionResult<LocationId> {
+    let session = local.weak_clone();
+    let location = session.create_location(parent, new.name)?;
+    if !new.settings.is_empty() {
+        let mut settings = session.location_get_settings(location.clone())?;
+        for (name, value) in new.settings {
+            set_setting(&mut settings, name, value);
+        }
+        session.location_set_settings(location.clone(), settings)?;
+    }
+    for element in new.elements {
+        create_element(local, location.clone(), element)?;
+    }
+    for new in new.locations {
+        create_location(local, location.clone(), new)?;
+    }
+    Ok(location)
+}
@@ -1,15 +1,30 @@
+mod checksum;
+mod collision;
+mod context;
+mod lifecycle;
 pub(crate) mod module;
+mod module_select;
+mod on_complete;
+mod partial_file;
+mod permissions;
+#[cfg(unix)]
+mod process_module;
+mod runner;
 mod session;
 mod session_common;
 mod session_element;
 mod session_location;
 mod session_module;
+mod settings;
+mod space;
+mod wasm_module;
 
 #[cfg(test)]
 mod tests;
 
 use std::{
     collections::HashSet,
+    path::PathBuf,
     sync::{Arc, RwLock},
 };
 
@@ -37,9 +52,21 @@ pub struct ElementWraper {
     pub element: Arc<RwLock<Element>>,
     pub path: Path,
     pub storage: Arc<RwLock<Storage>>,
+    /// The storage of the proxy module
+    pub proxy_storage: Arc<RwLock<Storage>>,
     pub thread: Arc<RwLock<Option<std::thread::JoinHandle<()>>>>,
     pub sender: Arc<RwLock<Option<std::sync::mpsc::Sender<Event>>>>,
     pub events: Arc<RwLock<Events>>,
+    pub file: Arc<RwLock<ElementFile>>,
+}
+
+/// The file of an element as the session knows it, the module can change Element::path without the session
+#[derive(Clone, Debug, Default)]
+pub struct ElementFile {
+    /// The last path that was set through the session
+    pub path: PathBuf,
+    /// If Session.OnCollision was applied to path, the file at path is of this element
+    pub claimed: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -54,10 +81,23 @@ pub struct LocationWraper {
     pub events: Arc<RwLock<Events>>,
 }
 
+impl LocationWraper {
+    /// The location inside at `path`, without creating the missing locations like TLocalSession::create_location
+    pub fn location_at(&self, path: &[usize]) -> Option<LocationWraper> {
+        let mut location = self.clone();
+        for index in path {
+            let next = location.locations.read().unwrap().get(*index).cloned();
+            location = next?;
+        }
+        Some(location)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ModuleWraper {
     pub module: Arc<RwLock<Module>>,
     pub path: Path,
+    pub uid: UID,
 }
 
 #[derive(Clone, Debug)]
@@ -67,4 +107,8 @@ pub enum Wraper {
     Module(ModuleWraper),
 }
 
+pub use module::read_manifest;
 pub use session::*;
+
+#[cfg(unix)]
+pub use process_module::serve_module;
@@ -0,0 +1,233 @@
+//! Calls the module lifecycle hooks as the module that the element or location uses
+
+use muzzman_lib::prelude::*;
+
+use crate::{
+    permissions, runner, ElementWraper, LocationWraper, ModuleSession, ModuleWraper, TLocalSession,
+};
+
+/// A panic in the module becomes an error, `poisoned` clears the poison of the element or location
+fn run_module<T>(
+    module: &ModuleWraper,
+    poisoned: impl FnOnce(),
+    f: impl FnOnce(&dyn TModule) -> SessionResult<T>,
+) -> SessionResult<T> {
+    let module_uid = module.uid;
+    let module = module.module.read().unwrap();
+    runner::catch_panic(
+        || {
+            permissions::run_as(module_uid, &module.permissions, || {
+                f(module.module.as_ref())
+            })
+        },
+        poisoned,
+    )
+}
+
+fn element_module(session: &dyn TLocalSession, element: &ElementWraper) -> Option<ModuleWraper> {
+    let module = element.element.read().unwrap().module.clone()?;
+    session.module(module.uid).ok()
+}
+
+fn location_module(session: &dyn TLocalSession, location: &LocationWraper) -> Option<ModuleWraper> {
+    let module = location.location.read().unwrap().module.clone()?;
+    session.module(module.uid).ok()
+}
+
+pub(crate) fn load(session: &dyn TLocalSession, module: &ModuleWraper) -> SessionResult<()> {
+    let session =
+        Session::from(Box::new(ModuleSession::new(session, module.uid)) as Box<dyn TSession>);
+    run_module(module, || {}, |module| module.on_load(session))
+}
+
+pub(crate) fn unload(module: &ModuleWraper) {
+    let _ = run_module(
+        module,
+        || {},
+        |module| {
+            module.on_unload();
+            Ok(())
+        },
+    );
+}
+
+/// The element gets the settings of the module, if the module fails the element is left without a module
+pub(crate) fn element_attached(
+    session: &dyn TLocalSession,
+    element: &ElementWraper,
+) -> SessionResult<()> {
+    let Some(module) = element_module(session, element) else {
+        return Ok(());
+    };
+    let defaults = module.module.read().unwrap().element_settings.clone();
+    element
+        .element
+        .write()
+        .unwrap()
+        .settings
+        .merge_module(&defaults);
+    let mut storage = element.storage.write().unwrap();
+    let result = run_module(
+        &module,
+        || element.element.clear_poison(),
+        |module| module.on_element_attached(element.element.clone(), &mut storage),
+    );
+    if result.is_err() {
+        element.element.write().unwrap().module = None;
+        *storage = Default::default();
+    }
+    result
+}
+
+/// The storage is cleared because the values can be from the library of the module
+pub(crate) fn element_detached(session: &dyn TLocalSession, element: &ElementWraper) {
+    proxy_detached(session, element);
+    let mut storage = element.storage.write().unwrap();
+    if let Some(module) = element_module(session, element) {
+        let _ = run_module(
+            &module,
+            || element.element.clear_poison(),
+            |module| {
+                module.on_element_detached(element.element.clone(), &mut storage);
+                Ok(())
+            },
+        );
+    }
+    *storage = Default::default();
+}
+
+pub(crate) fn element_destroyed(session: &dyn TLocalSession, element: &ElementWraper) {
+    proxy_detached(session, element);
+    let mut storage = element.storage.write().unwrap();
+    if let Some(module) = element_module(session, element) {
+        let _ = run_module(
+            &module,
+            || element.element.clear_poison(),
+            |module| {
+                module.on_element_destroyed(element.element.clone(), &mut storage);
+                Ok(())
+            },
+        );
+    }
+    *storage = Default::default();
+}
+
+/// The proxy module gets the element settings that the module does not have,
+/// if it fails the element is left without a proxy
+pub(crate) fn proxy_attached(
+    session: &dyn TLocalSession,
+    element: &ElementWraper,
+) -> SessionResult<()> {
+    let proxy = element.element.read().unwrap().proxy.clone();
+    let Some(module) = proxy.and_then(|proxy| session.module(proxy.uid).ok()) else {
+        return Ok(());
+    };
+    let defaults = module.module.read().unwrap().element_settings.clone();
+    {
+        let mut element = element.element.write().unwrap();
+        reset_progress(&mut element);
+        for (name, setting) in defaults.iter() {
+            if element.settings.get(name).is_none() {
+                element.settings.add(name, setting.clone());
+            }
+        }
+    }
+    let mut storage = element.proxy_storage.write().unwrap();
+    let result = run_module(
+        &module,
+        || element.element.clear_poison(),
+        |module| module.on_element_attached(element.element.clone(), &mut storage),
+    );
+    if result.is_err() {
+        element.element.write().unwrap().proxy = None;
+        *storage = Default::default();
+    }
+    result
+}
+
+/// Ends the proxy, the module of the element polls it again
+pub(crate) fn proxy_detached(session: &dyn TLocalSession, element: &ElementWraper) {
+    let mut storage = element.proxy_storage.write().unwrap();
+    let proxy = element.element.write().unwrap().proxy.take();
+    let Some(proxy) = proxy else {
+        return;
+    };
+    if let Ok(module) = session.module(proxy.uid) {
+        let _ = run_module(
+            &module,
+            || element.element.clear_poison(),
+            |module| {
+                module.on_element_detached(element.element.clone(), &mut storage);
+                Ok(())
+            },
+        );
+    }
+    *storage = Default::default();
+    reset_progress(&mut element.element.write().unwrap());
+}
+
+/// The statuses are of the module that polls the element, the next one starts from the first status
+fn reset_progress(element: &mut Element) {
+    element.status = 0;
+    element.statuses.clear();
+    element.progress = 0.0;
+}
+
+/// The location gets the settings of the module, if the module fails the location is left without a module
+pub(crate) fn location_attached(
+    session: &dyn TLocalSession,
+    location: &LocationWraper,
+) -> SessionResult<()> {
+    let Some(module) = location_module(session, location) else {
+        return Ok(());
+    };
+    let defaults = module.module.read().unwrap().location_settings.clone();
+    location
+        .location
+        .write()
+        .unwrap()
+        .settings
+        .merge_module(&defaults);
+    let mut storage = location.storage.write().unwrap();
+    let result = run_module(
+        &module,
+        || location.location.clear_poison(),
+        |module| module.on_location_attached(location.location.clone(), &mut storage),
+    );
+    if result.is_err() {
+        location.location.write().unwrap().module = None;
+        *storage = Default::default();
+    }
+    result
+}
+
+/// The storage is cleared because the values can be from the library of the module
+pub(crate) fn location_detached(session: &dyn TLocalSession, location: &LocationWraper) {
+    let mut storage = location.storage.write().unwrap();
+    if let Some(module) = location_module(session, location) {
+        let _ = run_module(
+            &module,
+            || location.location.clear_poison(),
+            |module| {
+                module.on_location_detached(location.location.clone(), &mut storage);
+                Ok(())
+            },
+        );
+    }
+    *storage = Default::default();
+}
+
+pub(crate) fn location_destroyed(session: &dyn TLocalSession, location: &LocationWraper) {
+    let mut storage = location.storage.write().unwrap();
+    if let Some(module) = location_module(session, location) {
+        let _ = run_module(
+            &module,
+            || location.location.clear_poison(),
+            |module| {
+                module.on_location_destroyed(location.location.clone(), &mut storage);
+                Ok(())
+            },
+        );
+    }
+    *storage = Default::default();
+}
@@ -1,58 +1,140 @@
 use std::{
+    collections::BTreeSet,
     ops::DerefMut,
     path::Path,
-    sync::{Arc, RwLock},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
 };
 
-use libloading::{Library, Symbol};
+use libloading::Library;
 use muzzman_lib::{prelude::*, Storage};
 use once_cell::sync::Lazy;
 
+#[cfg(unix)]
+use crate::process_module::ProcessModule;
+use crate::wasm_module::WasmModule;
+
 #[allow(clippy::type_complexity)]
 pub struct RawModule {
-    lib: &'static Library,
-
-    fn_name: Symbol<'static, fn() -> &'static str>,
-    fn_desc: Symbol<'static, fn() -> &'static str>,
-
-    fn_id: Symbol<'static, fn() -> u64>,
-    fn_version: Symbol<'static, fn() -> u64>,
-    fn_supported_versions: Symbol<'static, fn() -> &'static [u64]>,
-
-    fn_poll_element: Symbol<
-        'static,
-        fn(&mut std::task::Context<'_>, Arc<RwLock<Element>>, &mut Storage) -> SessionResult<()>,
-    >,
-    fn_poll_location: Symbol<
-        'static,
-        fn(&mut std::task::Context<'_>, Arc<RwLock<Location>>, &mut Storage) -> SessionResult<()>,
-    >,
+    fn_name: fn() -> &'static str,
+    fn_desc: fn() -> &'static str,
+
+    fn_id: fn() -> u64,
+    fn_version: fn() -> u64,
+    fn_supported_versions: fn() -> &'static [u64],
+
+    fn_poll_element: fn(
+        &mut std::task::Context<'_>,
+        Arc<RwLock<Element>>,
+        &mut Storage,
+        &mut ModuleContext,
+    ) -> SessionResult<()>,
+    fn_poll_location: fn(
+        &mut std::task::Context<'_>,
+        Arc<RwLock<Location>>,
+        &mut Storage,
+        &mut ModuleContext,
+    ) -> SessionResult<()>,
 
     fn_element_on_event:
-        Symbol<'static, fn(Arc<RwLock<Element>>, event: Event, &mut Storage) -> SessionResult<()>>,
+        fn(Arc<RwLock<Element>>, Event, &mut Storage, &mut ModuleContext) -> SessionResult<()>,
     fn_location_on_event:
-        Symbol<'static, fn(Arc<RwLock<Location>>, event: Event, &mut Storage) -> SessionResult<()>>,
-
-    fn_default_element_settings: Symbol<'static, fn() -> Settings>,
-    fn_default_location_settings: Symbol<'static, fn() -> Settings>,
-
-    fn_supports_protocols: Symbol<'static, fn() -> &'static [&'static str]>,
-    fn_supports_extensions: Symbol<'static, fn() -> &'static [&'static str]>,
+        fn(Arc<RwLock<Location>>, Event, &mut Storage, &mut ModuleContext) -> SessionResult<()>,
+
+    fn_default_element_settings: fn() -> Settings,
+    fn_default_location_settings: fn() -> Settings,
+
+    fn_supports_protocols: fn() -> &'static [&'static str],
+    fn_supports_extensions: fn() -> &'static [&'static str],
+    /// Older modules don't have them
+    fn_permissions: Option<fn() -> Permissions>,
+    fn_element_actions: Option<fn() -> Vec<Action>>,
+    fn_location_actions: Option<fn() -> Vec<Action>>,
+    fn_element_action: Option<
+        fn(
+            Arc<RwLock<Element>>,
+            &str,
+            Vec<Atom>,
+            &mut Storage,
+            &mut ModuleContext,
+        ) -> SessionResult<()>,
+    >,
+    fn_location_action: Option<
+        fn(
+            Arc<RwLock<Location>>,
+            &str,
+            Vec<Atom>,
+            &mut Storage,
+            &mut ModuleContext,
+        ) -> SessionResult<()>,
+    >,
+    fn_on_load: Option<fn(Session) -> SessionResult<()>>,
+    fn_on_unload: Option<fn()>,
+    fn_on_element_attached: Option<fn(Arc<RwLock<Element>>, &mut Storage) -> SessionResult<()>>,
+    fn_on_element_detached: Option<fn(Arc<RwLock<Element>>, &mut Storage)>,
+    fn_on_element_destroyed: Option<fn(Arc<RwLock<Element>>, &mut Storage)>,
+    fn_on_location_attached: Option<fn(Arc<RwLock<Location>>, &mut Storage) -> SessionResult<()>>,
+    fn_on_location_detached: Option<fn(Arc<RwLock<Location>>, &mut Storage)>,
+    fn_on_location_destroyed: Option<fn(Arc<RwLock<Location>>, &mut Storage)>,
+
+    /// The functions are from the library, so it should be dropped last
+    _lib: Library,
 }
 
 impl RawModule {
+    /// Loads a C abi module if the library exports `muzzman_module`, else a rust abi module
     pub fn new_module(path: &Path) -> Result<Box<dyn TModule>, RawLibraryError> {
+        let Ok(lib) = (unsafe { Library::new(path) }) else {
+            return Err(RawLibraryError::NotFound);
+        };
+        let vtable = unsafe {
+            lib.get::<extern "C" fn() -> *const CModuleVTable>(b"muzzman_module\0")
+                .map(|func| func())
+        };
+        if let Ok(vtable) = vtable {
+            let module = unsafe { CModule::new(vtable, Some(Box::new(lib))) }?;
+            return Ok(Box::new(module));
+        }
+        drop(lib);
+
         match Self::new(path) {
             Ok(module) => Ok(Box::new(module)),
             Err(err) => Err(err),
         }
     }
     pub fn new(path: &Path) -> Result<Self, RawLibraryError> {
-        let lib = unsafe { Library::new(path) };
-        if lib.is_err() {
+        let Ok(lib) = (unsafe { Library::new(path) }) else {
             return Err(RawLibraryError::NotFound);
+        };
+
+        // Should be checked before any rust abi function is called
+        let Ok(fn_abi_version) =
+            (unsafe { lib.get::<extern "C" fn() -> u64>(b"muzzman_abi_version\0") })
+        else {
+            return Err(RawLibraryError::DontHaveSymbolAbiVersion);
+        };
+        let abi_version = fn_abi_version();
+        if abi_version != ABI_VERSION {
+            return Err(RawLibraryError::IncompatibleAbi(ABI_VERSION, abi_version));
+        }
+
+        let Ok(fn_build_id) = (unsafe {
+            lib.get::<extern "C" fn() -> *const std::ffi::c_char>(b"muzzman_build_id\0")
+        }) else {
+            return Err(RawLibraryError::DontHaveSymbolBuildId);
+        };
+        let build_id = unsafe { std::ffi::CStr::from_ptr(fn_build_id()) }
+            .to_string_lossy()
+            .to_string();
+        let host_build_id = BUILD_ID.trim_end_matches('\0');
+        if build_id != host_build_id {
+            return Err(RawLibraryError::IncompatibleBuild(
+                host_build_id.to_string(),
+                build_id,
+            ));
         }
-        let lib = Box::leak(Box::new(lib.unwrap()));
 
         let fn_name = if let Ok(func) = unsafe { lib.get(b"name\0") } {
             func
@@ -134,6 +216,42 @@ impl RawModule {
             return Err(RawLibraryError::DontHaveSymbolSupportsExtensions);
         };
 
+        let fn_permissions = unsafe { lib.get::<fn() -> Permissions>(b"permissions\0") }
+            .ok()
+            .map(|func| *func);
+        let fn_element_actions = unsafe { lib.get(b"element_actions\0") }
+            .ok()
+            .map(|func| *func);
+        let fn_location_actions = unsafe { lib.get(b"location_actions\0") }
+            .ok()
+            .map(|func| *func);
+        let fn_element_action = unsafe { lib.get(b"element_action\0") }
+            .ok()
+            .map(|func| *func);
+        let fn_location_action = unsafe { lib.get(b"location_action\0") }
+            .ok()
+            .map(|func| *func);
+        let fn_on_load = unsafe { lib.get(b"on_load\0") }.ok().map(|func| *func);
+        let fn_on_unload = unsafe { lib.get(b"on_unload\0") }.ok().map(|func| *func);
+        let fn_on_element_attached = unsafe { lib.get(b"on_element_attached\0") }
+            .ok()
+            .map(|func| *func);
+        let fn_on_element_detached = unsafe { lib.get(b"on_element_detached\0") }
+            .ok()
+            .map(|func| *func);
+        let fn_on_element_destroyed = unsafe { lib.get(b"on_element_destroyed\0") }
+            .ok()
+            .map(|func| *func);
+        let fn_on_location_attached = unsafe { lib.get(b"on_location_attached\0") }
+            .ok()
+            .map(|func| *func);
+        let fn_on_location_detached = unsafe { lib.get(b"on_location_detached\0") }
+            .ok()
+            .map(|func| *func);
+        let fn_on_location_destroyed = unsafe { lib.get(b"on_location_destroyed\0") }
+            .ok()
+            .map(|func| *func);
+
         if let Ok(logger_state) = unsafe {
             lib.get::<*mut Lazy<std::sync::Arc<std::sync::RwLock<muzzman_lib::logger::State>>>>(
                 b"LOGGER_STATE\0",
@@ -160,51 +278,63 @@ impl RawModule {
             }
         }
 
-        Ok(Self {
-            lib,
-            fn_name,
-            fn_desc,
-            fn_id,
-            fn_version,
-            fn_supported_versions,
-            fn_poll_element,
-            fn_poll_location,
-            fn_element_on_event,
-            fn_location_on_event,
-            fn_default_element_settings,
-            fn_default_location_settings,
-            fn_supports_protocols,
-            fn_supports_extensions,
-        })
-    }
-}
+        let module = Self {
+            fn_name: *fn_name,
+            fn_desc: *fn_desc,
+            fn_id: *fn_id,
+            fn_version: *fn_version,
+            fn_supported_versions: *fn_supported_versions,
+            fn_poll_element: *fn_poll_element,
+            fn_poll_location: *fn_poll_location,
+            fn_element_on_event: *fn_element_on_event,
+            fn_location_on_event: *fn_location_on_event,
+            fn_default_element_settings: *fn_default_element_settings,
+            fn_default_location_settings: *fn_default_location_settings,
+            fn_supports_protocols: *fn_supports_protocols,
+            fn_supports_extensions: *fn_supports_extensions,
+            fn_permissions,
+            fn_element_actions,
+            fn_location_actions,
+            fn_element_action,
+            fn_location_action,
+            fn_on_load,
+            fn_on_unload,
+            fn_on_element_attached,
+            fn_on_element_detached,
+            fn_on_element_destroyed,
+            fn_on_location_attached,
+            fn_on_location_detached,
+            fn_on_location_destroyed,
+            _lib: lib,
+        };
+
+        if !(module.fn_supported_versions)().contains(&ABI_VERSION) {
+            return Err(RawLibraryError::UnsupportedVersion(ABI_VERSION));
+        }
 
-impl Drop for RawModule {
-    fn drop(&mut self) {
-        let lib = unsafe { Box::from_raw((self.lib as *const _) as *mut Library) };
-        drop(lib)
+        Ok(module)
     }
 }
 
 impl TModule for RawModule {
     fn name(&self) -> &str {
-        (*self.fn_name)()
+        (self.fn_name)()
     }
 
     fn desc(&self) -> &str {
-        (*self.fn_desc)()
+        (self.fn_desc)()
     }
 
     fn id(&self) -> u64 {
-        (*self.fn_id)()
+        (self.fn_id)()
     }
 
     fn version(&self) -> u64 {
-        (*self.fn_version)()
+        (self.fn_version)()
     }
 
     fn supported_versions(&self) -> &'static [u64] {
-        (*self.fn_supported_versions)()
+        (self.fn_supported_versions)()
     }
 
     fn poll_element(
@@ -212,8 +342,9 @@ impl TModule for RawModule {
         ctx: &mut std::task::Context<'_>,
         element: Arc<RwLock<Element>>,
         storage: &mut Storage,
+        context: &mut ModuleContext,
     ) -> SessionResult<()> {
-        (*self.fn_poll_element)(ctx, element, storage)
+        (self.fn_poll_element)(ctx, element, storage, context)
     }
 
     fn poll_location(
@@ -221,8 +352,9 @@ impl TModule for RawModule {
         ctx: &mut std::task::Context<'_>,
         location: Arc<RwLock<Location>>,
         storage: &mut Storage,
+        context: &mut ModuleContext,
     ) -> SessionResult<()> {
-        (*self.fn_poll_location)(ctx, location, storage)
+        (self.fn_poll_location)(ctx, location, storage, context)
     }
 
     fn element_on_event(
@@ -230,8 +362,9 @@ impl TModule for RawModule {
         element: Arc<RwLock<Element>>,
         event: Event,
         storage: &mut Storage,
+        context: &mut ModuleContext,
     ) -> SessionResult<()> {
-        (*self.fn_element_on_event)(element, event, storage)
+        (self.fn_element_on_event)(element, event, storage, context)
     }
 
     fn location_on_event(
@@ -239,23 +372,197 @@ impl TModule for RawModule {
         location: Arc<RwLock<Location>>,
         event: Event,
         storage: &mut Storage,
+        context: &mut ModuleContext,
     ) -> SessionResult<()> {
-        (*self.fn_location_on_event)(location, event, storage)
+        (self.fn_location_on_event)(location, event, storage, context)
     }
 
     fn default_element_settings(&self) -> Settings {
-        (*self.fn_default_element_settings)()
+        (self.fn_default_element_settings)()
     }
 
     fn default_location_settings(&self) -> Settings {
-        (*self.fn_default_location_settings)()
+        (self.fn_default_location_settings)()
     }
 
     fn supports_protocols(&self) -> &[&'static str] {
-        (*self.fn_supports_protocols)()
+        (self.fn_supports_protocols)()
     }
 
     fn supports_extensions(&self) -> &[&'static str] {
-        (*self.fn_supports_extensions)()
+        (self.fn_supports_extensions)()
+    }
+
+    fn permissions(&self) -> Permissions {
+        self.fn_permissions.map(|func| func()).unwrap_or_default()
+    }
+
+    fn element_actions(&self) -> Vec<Action> {
+        self.fn_element_actions
+            .map(|func| func())
+            .unwrap_or_default()
+    }
+
+    fn location_actions(&self) -> Vec<Action> {
+        self.fn_location_actions
+            .map(|func| func())
+            .unwrap_or_default()
+    }
+
+    fn element_action(
+        &self,
+        element: Arc<RwLock<Element>>,
+        name: &str,
+        args: Vec<Atom>,
+        storage: &mut Storage,
+        context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        match self.fn_element_action {
+            Some(func) => func(element, name, args, storage, context),
+            None => Err(SessionError::NoAction(name.to_string())),
+        }
+    }
+
+    fn location_action(
+        &self,
+        location: Arc<RwLock<Location>>,
+        name: &str,
+        args: Vec<Atom>,
+        storage: &mut Storage,
+        context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        match self.fn_location_action {
+            Some(func) => func(location, name, args, storage, context),
+            None => Err(SessionError::NoAction(name.to_string())),
+        }
+    }
+
+    fn on_load(&self, session: Session) -> SessionResult<()> {
+        self.fn_on_load.map_or(Ok(()), |func| func(session))
+    }
+
+    fn on_unload(&self) {
+        if let Some(func) = self.fn_on_unload {
+            func()
+        }
+    }
+
+    fn on_element_attached(
+        &self,
+        element: Arc<RwLock<Element>>,
+        storage: &mut Storage,
+    ) -> SessionResult<()> {
+        self.fn_on_element_attached
+            .map_or(Ok(()), |func| func(element, storage))
+    }
+
+    fn on_element_detached(&self, element: Arc<RwLock<Element>>, storage: &mut Storage) {
+        if let Some(func) = self.fn_on_element_detached {
+            func(element, storage)
+        }
+    }
+
+    fn on_element_destroyed(&self, element: Arc<RwLock<Element>>, storage: &mut Storage) {
+        if let Some(func) = self.fn_on_element_destroyed {
+            func(element, storage)
+        }
+    }
+
+    fn on_location_attached(
+        &self,
+        location: Arc<RwLock<Location>>,
+        storage: &mut Storage,
+    ) -> SessionResult<()> {
+        self.fn_on_location_attached
+            .map_or(Ok(()), |func| func(location, storage))
+    }
+
+    fn on_location_detached(&self, location: Arc<RwLock<Location>>, storage: &mut Storage) {
+        if let Some(func) = self.fn_on_location_detached {
+            func(location, storage)
+        }
+    }
+
+    fn on_location_destroyed(&self, location: Arc<RwLock<Location>>, storage: &mut Storage) {
+        if let Some(func) = self.fn_on_location_destroyed {
+            func(location, storage)
+        }
+    }
+}
+
+/// Reads the manifest that `module_link` exports, no function of the library is called
+pub fn read_manifest(path: &Path) -> Result<ModuleManifest, RawLibraryError> {
+    let Ok(lib) = (unsafe { Library::new(path) }) else {
+        return Err(RawLibraryError::NotFound);
+    };
+    let Ok(manifest) = (unsafe { lib.get::<*const RawModuleManifest>(b"MUZZMAN_MANIFEST\0") })
+    else {
+        return Err(RawLibraryError::DontHaveSymbolManifest);
+    };
+    let manifest = unsafe { &**manifest };
+    // the other fields can be different in other abi versions
+    if manifest.abi_version != ABI_VERSION {
+        return Err(RawLibraryError::IncompatibleAbi(
+            ABI_VERSION,
+            manifest.abi_version,
+        ));
+    }
+    Ok(unsafe { ModuleManifest::from_raw(manifest) })
+}
+
+/// Used to give every copy of a reloaded library an unique path
+static RELOAD_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// When `copy` a dynamic library is loaded from a copy
+/// because a library with the same path will not be loaded again while the old one is loaded
+pub(crate) fn load(source: ModuleSource, copy: bool) -> SessionResult<Box<dyn TModule>> {
+    match source {
+        ModuleSource::Wasm(wasm) => Ok(Box::new(WasmModule::new(&wasm)?)),
+        ModuleSource::Dynamic(path) if copy => {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let copy_path = std::env::temp_dir().join(format!(
+                "muzzman-{}-{}-{name}",
+                std::process::id(),
+                RELOAD_COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            std::fs::copy(&path, &copy_path)?;
+            let module = RawModule::new_module(&copy_path);
+            // the library stays mapped after is removed
+            #[cfg(unix)]
+            let _ = std::fs::remove_file(&copy_path);
+            Ok(module?)
+        }
+        ModuleSource::Dynamic(path) => Ok(RawModule::new_module(&path)?),
+        #[cfg(unix)]
+        ModuleSource::Process(path) => Ok(Box::new(ProcessModule::new(&path)?)),
+        #[cfg(not(unix))]
+        ModuleSource::Process(_) => Err(SessionError::Custom(
+            "Process modules are only supported on unix".into(),
+        )),
+        ModuleSource::Box(module) => Ok(module),
+    }
+}
+
+/// TModule needs them to be static, they are leaked once for every module that uses them
+/// so loading the same module again does not leak
+pub(crate) fn intern_str(str: &str) -> &'static str {
+    static STRS: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+    let mut strs = STRS.lock().unwrap();
+    if let Some(str) = strs.get(str) {
+        return str;
+    }
+    let str: &'static str = Box::leak(str.into());
+    strs.insert(str);
+    str
+}
+
+pub(crate) fn intern_versions(versions: Vec<u64>) -> &'static [u64] {
+    static VERSIONS: Mutex<BTreeSet<&'static [u64]>> = Mutex::new(BTreeSet::new());
+    let mut interned = VERSIONS.lock().unwrap();
+    if let Some(versions) = interned.get(&versions[..]) {
+        return versions;
     }
+    let versions: &'static [u64] = Box::leak(versions.into_boxed_slice());
+    interned.insert(versions);
+    versions
 }
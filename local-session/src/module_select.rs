@@ -0,0 +1,87 @@
+use muzzman_lib::prelude::*;
+
+use crate::{settings::location_setting, LocationWraper, ModuleWraper, TLocalSession};
+
+/// Selects the module for the url from the modules that support the url scheme
+/// When more modules support the scheme the first one from this order is used:
+/// Session.PreferredModule of the location, supports the url extension,
+/// bigger version, first added
+pub(crate) fn select(
+    session: &dyn TLocalSession,
+    location: &LocationWraper,
+    url: &str,
+) -> Option<ModuleWraper> {
+    let scheme = scheme(url)?;
+    let extension = extension(url);
+    let preferred = location_setting(session, location, session_settings::PREFERRED_MODULE)
+        .and_then(|atom| atom.as_u64());
+
+    session
+        .modules()
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, wraper)| {
+            let rank = {
+                let module = wraper.module.read().unwrap();
+                let module = &module.module;
+                let supports_scheme = module
+                    .supports_protocols()
+                    .iter()
+                    .any(|protocol| protocol.trim_end_matches(':').eq_ignore_ascii_case(&scheme));
+                if !supports_scheme {
+                    return None;
+                }
+                let supports_extension = extension.as_ref().is_some_and(|extension| {
+                    module
+                        .supports_extensions()
+                        .iter()
+                        .any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(extension))
+                });
+                (
+                    preferred == Some(module.id()),
+                    supports_extension,
+                    module.version(),
+                    std::cmp::Reverse(index),
+                )
+            };
+            Some((rank, wraper))
+        })
+        .max_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, wraper)| wraper)
+}
+
+/// The element name for the url, the last segment of the path or the host
+/// The name is joined to the location path, so "." and ".." or a name with a separator is "Element"
+pub(crate) fn name(url: &str) -> String {
+    let path = url_path(url);
+    path.rsplit('/')
+        .find(|segment| !segment.is_empty())
+        .or_else(|| host(url))
+        .filter(|name| !matches!(*name, "." | "..") && !name.contains(['/', '\\', '\0']))
+        .unwrap_or("Element")
+        .to_string()
+}
+
+fn scheme(url: &str) -> Option<String> {
+    let (scheme, _) = url.split_once("://")?;
+    Some(scheme.to_lowercase())
+}
+
+fn host(url: &str) -> Option<&str> {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.split(['/', '?', '#'])
+        .next()
+        .filter(|host| !host.is_empty())
+}
+
+fn url_path(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let rest = rest.split(['?', '#']).next().unwrap_or_default();
+    rest.split_once('/').map_or("", |(_, path)| path)
+}
+
+fn extension(url: &str) -> Option<String> {
+    let name = url_path(url).rsplit('/').next()?;
+    let (_, extension) = name.rsplit_once('.')?;
+    Some(extension.to_lowercase())
+}
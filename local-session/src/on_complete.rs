@@ -0,0 +1,230 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use muzzman_lib::prelude::*;
+
+use crate::{collision, permissions, ElementWraper, LocationWraper, TLocalSession};
+
+/// Runs the Session.OnComplete actions of the element and completes the parent locations
+pub(crate) fn element_completed(session: &dyn TLocalSession, element: &ElementWraper) {
+    let (uid, module, settings, path, parent) = {
+        let element = element.element.read().unwrap();
+        (
+            element.id.uid,
+            element.module.clone(),
+            element.settings.clone(),
+            element.path.clone(),
+            element.parent.clone(),
+        )
+    };
+
+    let res = run_actions(session, uid, module.as_ref(), &settings, &path, |to| {
+        permissions::check_file(session, element, &path)?;
+        let to = collision::resolve(session, element, to)?
+            .ok_or_else(|| SessionError::PathCollision(path.clone()))?;
+        std::fs::rename(&path, &to)?;
+        element.element.write().unwrap().path = to.clone();
+        element.file.write().unwrap().path = to.clone();
+        Ok(to)
+    });
+    if let Err(error) = res {
+        let mut element = element.element.write().unwrap();
+        element.is_error = true;
+        element.status = element.statuses.len();
+        element
+            .statuses
+            .push(format!("OnComplete failed: {error:?}"));
+        drop(element);
+        let _ = session.weak_clone().emit(uid, Event::Error(uid));
+    }
+
+    if let Ok(parent) = session.location(parent.uid) {
+        location_check_completed(session, &parent);
+    }
+}
+
+/// When all the elements and locations inside are completed marks the location as completed
+/// and runs the Session.OnComplete actions of the location
+fn location_check_completed(session: &dyn TLocalSession, location: &LocationWraper) {
+    let completed = location.elements.read().unwrap().iter().all(|element| {
+        let element = element.element.read().unwrap();
+        element.is_completed && !element.is_error
+    }) && location.locations.read().unwrap().iter().all(|location| {
+        let location = location.location.read().unwrap();
+        location.is_completed && !location.is_error
+    });
+
+    let (uid, module, settings, path, parent) = {
+        let mut l = location.location.write().unwrap();
+        if !completed || l.is_completed {
+            return;
+        }
+        l.is_completed = true;
+        (
+            l.id.uid,
+            l.module.clone(),
+            l.settings.clone(),
+            l.path.clone(),
+            l.parent.clone(),
+        )
+    };
+
+    let session_box = session.weak_clone();
+    let _ = session_box.emit(uid, Event::Completed(uid));
+
+    let res = run_actions(session, uid, module.as_ref(), &settings, &path, |to| {
+        if to.exists() {
+            return Err(SessionError::PathCollision(to));
+        }
+        std::fs::rename(&path, &to)?;
+        move_paths(location, &path, &to);
+        Ok(to)
+    });
+    if let Err(error) = res {
+        let mut location = location.location.write().unwrap();
+        location.is_error = true;
+        location.status = location.statuses.len();
+        location
+            .statuses
+            .push(format!("OnComplete failed: {error:?}"));
+        drop(location);
+        let _ = session_box.emit(uid, Event::Error(uid));
+    }
+
+    if let Some(parent) = parent.and_then(|parent| session.location(parent.uid).ok()) {
+        location_check_completed(session, &parent);
+    }
+}
+
+/// Move, Unpack, Command then Event
+/// move_to should move path to the new path and return it
+/// Unpack and Command run only if `module` can spawn processes
+fn run_actions(
+    session: &dyn TLocalSession,
+    uid: UID,
+    module: Option<&ModuleId>,
+    settings: &Settings,
+    path: &Path,
+    move_to: impl FnOnce(PathBuf) -> SessionResult<PathBuf>,
+) -> SessionResult<()> {
+    let get = |name: &str| settings.get(name).map(|setting| setting.value.clone());
+    let mut path = path.to_path_buf();
+
+    if let Some(to) = get(session_settings::ON_COMPLETE_MOVE)
+        .and_then(|atom| atom.as_str().map(str::to_string))
+        .filter(|to| !to.is_empty())
+    {
+        let to = parse_location_path(&to).ok_or_else(|| {
+            SessionError::InvalidSettings(vec![session_settings::ON_COMPLETE_MOVE.into()])
+        })?;
+        // a missing location is not created, it would have no path
+        let location = session.location(0)?.location_at(&to).ok_or_else(|| {
+            SessionError::InvalidSettings(vec![session_settings::ON_COMPLETE_MOVE.into()])
+        })?;
+        let dir = location.location.read().unwrap().path.clone();
+        let name = path.file_name().unwrap_or_default().to_os_string();
+        path = move_to(dir.join(name))?;
+    }
+
+    if get(session_settings::ON_COMPLETE_UNPACK)
+        .and_then(|atom| atom.as_bool())
+        .unwrap_or(false)
+    {
+        permissions::check_spawn(session, module)?;
+        unpack(&path)?;
+    }
+
+    if let Some(command) = get(session_settings::ON_COMPLETE_COMMAND)
+        .and_then(|atom| atom.as_str().map(str::to_string))
+        .filter(|command| !command.trim().is_empty())
+    {
+        permissions::check_spawn(session, module)?;
+        // the arguments are split on whitespace, quoting is not supported
+        if command.contains(['"', '\'', '\\']) {
+            return Err(SessionError::InvalidSettings(vec![
+                session_settings::ON_COMPLETE_COMMAND.into(),
+            ]));
+        }
+        let mut args = command.split_whitespace();
+        let program = args.next().unwrap_or_default();
+        run(Command::new(program).args(args).arg(&path))?;
+    }
+
+    if let Some(event) = get(session_settings::ON_COMPLETE_EVENT)
+        .and_then(|atom| atom.as_str().map(str::to_string))
+        .filter(|event| !event.is_empty())
+    {
+        session.weak_clone().emit(uid, Event::Custom(event))?;
+    }
+
+    Ok(())
+}
+
+/// "/" is the default location, "0/1" is the second location from the first location
+fn parse_location_path(path: &str) -> Option<Vec<usize>> {
+    path.split('/')
+        .filter(|index| !index.is_empty())
+        .map(|index| index.trim().parse().ok())
+        .collect()
+}
+
+fn unpack(path: &Path) -> SessionResult<()> {
+    let name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_lowercase();
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    if name.ends_with(".zip") {
+        run(Command::new("unzip").arg("-o").arg(path).arg("-d").arg(dir))
+    } else if [".tar", ".tar.gz", ".tgz", ".tar.xz", ".tar.bz2", ".tar.zst"]
+        .iter()
+        .any(|extension| name.ends_with(extension))
+    {
+        run(Command::new("tar").arg("-xf").arg(path).arg("-C").arg(dir))
+    } else {
+        Err(SessionError::Custom(format!(
+            "Cannot unpack {}, unknown archive type",
+            path.display()
+        )))
+    }
+}
+
+fn run(command: &mut Command) -> SessionResult<()> {
+    let status = command.status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(SessionError::Custom(format!(
+            "{command:?} exited with {status}"
+        )))
+    }
+}
+
+/// Replaces the from prefix with to on every element and location path inside location
+fn move_paths(location: &LocationWraper, from: &Path, to: &Path) {
+    {
+        let mut location = location.location.write().unwrap();
+        if let Ok(rest) = location.path.strip_prefix(from) {
+            location.path = to.join(rest);
+        }
+    }
+    for element in location.elements.read().unwrap().iter() {
+        let mut file = element.file.write().unwrap();
+        if let Ok(rest) = file.path.strip_prefix(from) {
+            file.path = to.join(rest);
+        }
+        let mut element = element.element.write().unwrap();
+        if let Ok(rest) = element.path.strip_prefix(from) {
+            element.path = to.join(rest);
+        }
+    }
+    for location in location.locations.read().unwrap().iter() {
+        move_paths(location, from, to);
+    }
+}
@@ -0,0 +1,157 @@
+use std::{
+    fs::OpenOptions,
+    io::{Seek, Write},
+    path::{Path, PathBuf},
+};
+
+use fs2::FileExt;
+use muzzman_lib::prelude::*;
+
+use crate::{permissions, settings::element_setting, ElementWraper, TLocalSession};
+
+pub(crate) fn part_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+/// Opens the element stream, `path.part` if Session.PartialFile is enabled or else `path`
+/// When the element was paused before the `.part` file will continue from where it was
+/// Elements without a directory, like the ones in the default location, don't get a file
+pub(crate) fn open(session: &dyn TLocalSession, element: &ElementWraper) -> SessionResult<()> {
+    let enabled = element_setting(session, element, session_settings::PARTIAL_FILE)
+        .and_then(|atom| atom.as_bool())
+        .unwrap_or(false);
+
+    let path = element.element.read().unwrap().path.clone();
+    if !matches!(path.parent(), Some(parent) if !parent.as_os_str().is_empty()) {
+        return Ok(());
+    }
+    permissions::check_file(session, element, &path)?;
+
+    let mut element = element.element.write().unwrap();
+    if !matches!(element.stream, Stream::None) || element.path.file_name().is_none() {
+        return Ok(());
+    }
+
+    if !enabled {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&element.path)?;
+        element.stream = Stream::from_file(file)?;
+        return Ok(());
+    }
+
+    let part = part_path(&element.path);
+    let resume = element
+        .data
+        .get(element_data::PARTIAL_LENGTH)
+        .and_then(Atom::as_u64)
+        .filter(|_| part.exists());
+
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(resume.is_none())
+        .open(&part)?;
+    if resume.is_none() {
+        if let Some(size) = element.data.get(element_data::SIZE).and_then(Atom::as_u64) {
+            file.allocate(size)?;
+        }
+    }
+
+    // every write is tracked, so the file can be cut after the last written byte
+    let position = resume.unwrap_or(0);
+    let mut ranges = Ranges::default();
+    if position > 0 {
+        ranges.insert(0..position);
+    }
+    element.stream = Stream::Segmented(SegmentedFile::with_ranges(file, ranges), position);
+    element
+        .data
+        .insert(element_data::PARTIAL_LENGTH.into(), Atom::U(position));
+    Ok(())
+}
+
+/// Closes the `.part` file and remembers how much was written from the start
+pub(crate) fn pause(element: &ElementWraper) -> SessionResult<()> {
+    let mut element = element.element.write().unwrap();
+    if !element.data.contains_key(element_data::PARTIAL_LENGTH) {
+        return Ok(());
+    }
+
+    let mut stream = std::mem::replace(&mut element.stream, Stream::None);
+    stream.flush()?;
+    let position = match stream.written_ranges() {
+        Some(ranges) => ranges
+            .iter()
+            .next()
+            .filter(|range| range.start == 0)
+            .map(|range| range.end)
+            .unwrap_or(0),
+        None => stream.stream_position()?,
+    };
+    element
+        .data
+        .insert(element_data::PARTIAL_LENGTH.into(), Atom::U(position));
+    Ok(())
+}
+
+/// Cuts the `.part` file after the last written byte, syncs it to the disk and renames it to the element path
+/// Without Session.PartialFile the element file is only synced
+pub(crate) fn complete(session: &dyn TLocalSession, element: &ElementWraper) -> SessionResult<()> {
+    let path = element.element.read().unwrap().path.clone();
+    permissions::check_file(session, element, &path)?;
+
+    let mut element = element.element.write().unwrap();
+    let mut stream = std::mem::replace(&mut element.stream, Stream::None);
+    stream.flush()?;
+    if !element.data.contains_key(element_data::PARTIAL_LENGTH) {
+        if let Stream::File(file, _, _) = &stream {
+            file.sync_all()?;
+        }
+        return Ok(());
+    }
+
+    // the file was allocated with the expected size, after what was written are only zeros
+    let length = match stream.written_ranges() {
+        Some(ranges) => ranges.iter().map(|range| range.end).max().unwrap_or(0),
+        None => match element.data.get(element_data::SIZE).and_then(Atom::as_u64) {
+            Some(size) => size,
+            None => stream.stream_position()?,
+        },
+    };
+    drop(stream);
+
+    let part = part_path(&element.path);
+    let file = OpenOptions::new().write(true).open(&part)?;
+    file.set_len(length)?;
+    file.sync_all()?;
+    drop(file);
+
+    std::fs::rename(&part, &element.path)?;
+    sync_dir(&element.path)?;
+    element.data.remove(element_data::PARTIAL_LENGTH);
+    Ok(())
+}
+
+/// Syncs the directory of `path` so the rename is on the disk
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> std::io::Result<()> {
+    match path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        Some(parent) => std::fs::File::open(parent)?.sync_all(),
+        None => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_: &Path) -> std::io::Result<()> {
+    Ok(())
+}
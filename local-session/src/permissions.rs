@@ -0,0 +1,210 @@
+//! The checks for the permissions of the module that calls the session
+//! A module is known from the session handle that it got, TModule::on_load gets a ModuleSession and the
+//! ModuleContext operations are done with one, so the module is known also when it calls from its own thread
+//! While the session runs a callback of a module the calls from that thread are checked as the module too,
+//! this covers the sessions of the ids that the callback gets
+//! The session APIs called by the user are not checked
+
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+};
+
+use muzzman_lib::prelude::*;
+
+use crate::{ElementWraper, TLocalSession, Wraper};
+
+thread_local! {
+    static CURRENT: RefCell<Option<(UID, Permissions)>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` as the module `uid`, the session APIs called by `f` are checked with `permissions`
+pub(crate) fn run_as<T>(uid: UID, permissions: &Permissions, f: impl FnOnce() -> T) -> T {
+    /// Restores the last module also if `f` panics
+    struct Restore(Option<(UID, Permissions)>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+        }
+    }
+
+    let _restore =
+        Restore(CURRENT.with(|current| current.replace(Some((uid, permissions.clone())))));
+    f()
+}
+
+/// The module that calls with `session` and its permissions, a module that was removed has none
+fn current(session: &dyn TLocalSession) -> Option<(UID, Permissions)> {
+    let Some(uid) = session.caller() else {
+        return CURRENT.with(|current| current.borrow().clone());
+    };
+    let permissions = session
+        .module(uid)
+        .map(|module| module.module.read().unwrap().permissions.clone())
+        .unwrap_or_default();
+    Some((uid, permissions))
+}
+
+pub(crate) fn check_not_module(session: &dyn TLocalSession) -> SessionResult<()> {
+    match current(session) {
+        Some(_) => Err(SessionError::NoPermission),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn check_create_elements(session: &dyn TLocalSession) -> SessionResult<()> {
+    match current(session) {
+        Some((_, permissions)) if !permissions.create_elements => Err(SessionError::NoPermission),
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn check_write(session: &dyn TLocalSession, path: &Path) -> SessionResult<()> {
+    match current(session) {
+        Some((_, permissions)) => can_write(&permissions, path),
+        None => Ok(()),
+    }
+}
+
+fn can_write(permissions: &Permissions, path: &Path) -> SessionResult<()> {
+    // a relative path can go anywhere with ".."
+    let path = normalize(path).ok_or(SessionError::NoPermission)?;
+    if permissions.can_write(&path) {
+        Ok(())
+    } else {
+        Err(SessionError::NoPermission)
+    }
+}
+
+/// A module can write Element::path without the session, so before the session creates, renames or moves
+/// the file of the element at `path` the module of the element should be allowed to write there,
+/// unless `path` is the one that was set through the session
+pub(crate) fn check_file(
+    session: &dyn TLocalSession,
+    element: &ElementWraper,
+    path: &Path,
+) -> SessionResult<()> {
+    if element.file.read().unwrap().path == path {
+        return Ok(());
+    }
+    let Some(module) = element.element.read().unwrap().module.clone() else {
+        return Ok(());
+    };
+    let permissions = session
+        .module(module.uid)
+        .map(|module| module.module.read().unwrap().permissions.clone())
+        .unwrap_or_default();
+    can_write(&permissions, path)
+}
+
+/// A module can change only its own elements, and the elements without a module
+/// with Permissions::create_elements because it could create them
+pub(crate) fn check_module_of(
+    session: &dyn TLocalSession,
+    module: Option<&ModuleId>,
+) -> SessionResult<()> {
+    let Some((current, permissions)) = current(session) else {
+        return Ok(());
+    };
+    match module {
+        Some(module) if module.uid == current => Ok(()),
+        None if permissions.create_elements => Ok(()),
+        _ => Err(SessionError::NoPermission),
+    }
+}
+
+/// The element can be changed by the module that calls with `session`
+pub(crate) fn check_element(
+    session: &dyn TLocalSession,
+    element: &ElementWraper,
+) -> SessionResult<()> {
+    let module = element.element.read().unwrap().module.clone();
+    check_module_of(session, module.as_ref())
+}
+
+/// The settings that run processes cannot be changed without Permissions::spawn_processes
+pub(crate) fn check_settings(
+    session: &dyn TLocalSession,
+    old: &Settings,
+    new: &Settings,
+) -> SessionResult<()> {
+    match current(session) {
+        Some((_, permissions)) if !permissions.spawn_processes => {}
+        _ => return Ok(()),
+    }
+    let spawns = |settings: &Settings| {
+        let command = settings
+            .get(session_settings::ON_COMPLETE_COMMAND)
+            .and_then(|setting| setting.value.as_str().map(str::to_string))
+            .unwrap_or_default();
+        let unpack = settings
+            .get(session_settings::ON_COMPLETE_UNPACK)
+            .and_then(|setting| setting.value.as_bool())
+            .unwrap_or(false);
+        (command.trim().to_string(), unpack)
+    };
+    let (old_command, old_unpack) = spawns(old);
+    let (new_command, new_unpack) = spawns(new);
+    if (!new_command.is_empty() && new_command != old_command) || (new_unpack && !old_unpack) {
+        return Err(SessionError::NoPermission);
+    }
+    Ok(())
+}
+
+/// A module can write the settings of its elements without the session, so before a process is spawned
+/// for an element or location the module that it uses should have Permissions::spawn_processes
+pub(crate) fn check_spawn(
+    session: &dyn TLocalSession,
+    module: Option<&ModuleId>,
+) -> SessionResult<()> {
+    let Some(module) = module else {
+        return Ok(());
+    };
+    let Ok(module) = session.module(module.uid) else {
+        return Err(SessionError::NoPermission);
+    };
+    if module.module.read().unwrap().permissions.spawn_processes {
+        Ok(())
+    } else {
+        Err(SessionError::NoPermission)
+    }
+}
+
+/// The events of `uid` can be used by the current module only if `uid` uses it or with Permissions::other_events
+pub(crate) fn check_events(session: &dyn TLocalSession, uid: UID) -> SessionResult<()> {
+    let Some((module, permissions)) = current(session) else {
+        return Ok(());
+    };
+    if permissions.other_events {
+        return Ok(());
+    }
+    let uses = match session.get(uid)? {
+        Wraper::Element(element) => element.element.read().unwrap().module.clone(),
+        Wraper::Location(location) => location.location.read().unwrap().module.clone(),
+        Wraper::Module(_) => None,
+    };
+    if uses.is_some_and(|uses| uses.uid == module) {
+        Ok(())
+    } else {
+        Err(SessionError::NoPermission)
+    }
+}
+
+fn normalize(path: &Path) -> Option<PathBuf> {
+    if !path.is_absolute() {
+        return None;
+    }
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::ParentDir => {
+                if !normalized.pop() {
+                    return None;
+                }
+            }
+            std::path::Component::CurDir => {}
+            component => normalized.push(component),
+        }
+    }
+    Some(normalized)
+}
@@ -0,0 +1,819 @@
+//! Runs a dynamic module in a module host process, so a crash in the module only kills the host
+//!
+//! The session starts `muzzman-module-host <module>` with one end of a unix socket pair as its stdin,
+//! the host loads the module and answers every Request with a Response, one json per line
+//! Only the session has the other end, no path is created that other processes could connect to
+//!
+//! The host has its own copy of the element or location, the state is sent with every call
+//! and the changes are applied back, what the module writes in the element stream is sent back and written in the real stream
+//! The module storage stays in the host, if the host is restarted the storage is lost
+//! A host that does not answer in MODULE_HOST_TIMEOUT_ENV seconds is killed and started again
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Cursor, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
+
+use muzzman_lib::{prelude::*, Storage};
+use serde::{Deserialize, Serialize};
+
+use crate::module::{intern_str, intern_versions, RawModule};
+
+/// How long the session waits for an answer if MODULE_HOST_TIMEOUT_ENV is not set
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize)]
+enum Request {
+    Info,
+    PollElement(ElementState),
+    PollLocation(LocationState),
+    ElementOnEvent(ElementState, Event),
+    LocationOnEvent(LocationState, Event),
+    ElementAction(ElementState, String, Vec<Atom>),
+    LocationAction(LocationState, String, Vec<Atom>),
+    ElementHook(ElementState, Hook),
+    LocationHook(LocationState, Hook),
+    Unload,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+enum Hook {
+    Attached,
+    Detached,
+    Destroyed,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Response {
+    Info(Info),
+    /// The element after the call, the bytes written in the stream, the module result
+    /// and the operations that the module added to the context
+    Element(ElementState, Vec<u8>, Result<(), String>, Vec<ContextOp>),
+    Location(LocationState, Result<(), String>, Vec<ContextOp>),
+    Unloaded,
+    LoadError(RawLibraryError),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Info {
+    name: String,
+    desc: String,
+    id: u64,
+    version: u64,
+    supported_versions: Vec<u64>,
+    protocols: Vec<String>,
+    extensions: Vec<String>,
+    element_settings: Settings,
+    location_settings: Settings,
+    permissions: Permissions,
+    element_actions: Vec<Action>,
+    location_actions: Vec<Action>,
+}
+
+/// The part of an element that the module host can see and change
+#[derive(Clone, Serialize, Deserialize)]
+struct ElementState {
+    uid: UID,
+    parent: UID,
+    name: String,
+    desc: String,
+    data: HashMap<String, Atom>,
+    settings: Settings,
+    path: PathBuf,
+    url: String,
+    status: usize,
+    statuses: Vec<String>,
+    progress: f32,
+    download_speed_counter: usize,
+    upload_speed_counter: usize,
+    total_download: usize,
+    total_upload: usize,
+    enabled: bool,
+    is_error: bool,
+    is_completed: bool,
+}
+
+impl ElementState {
+    fn new(element: &Element) -> Self {
+        Self {
+            uid: element.id.uid,
+            parent: element.parent.uid,
+            name: element.name.clone(),
+            desc: element.desc.clone(),
+            data: element.data.clone(),
+            settings: element.settings.clone(),
+            path: element.path.clone(),
+            url: element.url.clone(),
+            status: element.status,
+            statuses: element.statuses.clone(),
+            progress: element.progress,
+            download_speed_counter: element.download_speed_counter,
+            upload_speed_counter: element.upload_speed_counter,
+            total_download: element.total_download,
+            total_upload: element.total_upload,
+            enabled: element.enabled,
+            is_error: element.is_error,
+            is_completed: element.is_completed,
+        }
+    }
+
+    /// The element used by the module in the module host, it writes in memory
+    fn to_element(&self) -> Element {
+        Element {
+            name: self.name.clone(),
+            desc: self.desc.clone(),
+            data: self.data.clone(),
+            settings: self.settings.clone(),
+            path: self.path.clone(),
+            module: None,
+            proxy: None,
+            id: ElementId {
+                uid: self.uid,
+                session: None,
+            },
+            url: self.url.clone(),
+            parent: LocationId {
+                uid: self.parent,
+                session: None,
+            },
+            stream: Stream::Memory(Cursor::new(Vec::new())),
+            buffer: Vec::new(),
+            buffer_size: 0,
+            status: self.status,
+            statuses: self.statuses.clone(),
+            progress: self.progress,
+            download_speed: 0,
+            upload_speed: 0,
+            download_speed_counter: self.download_speed_counter,
+            upload_speed_counter: self.upload_speed_counter,
+            total_download: self.total_download,
+            total_upload: self.total_upload,
+            enabled: self.enabled,
+            is_error: self.is_error,
+            is_completed: self.is_completed,
+        }
+    }
+
+    /// The counters are added, because the session can change them while the module host runs
+    fn apply(self, sent: &ElementState, element: &mut Element) {
+        element.name = self.name;
+        element.desc = self.desc;
+        element.data = self.data;
+        element.settings = self.settings;
+        element.url = self.url;
+        element.status = self.status;
+        element.statuses = self.statuses;
+        element.progress = self.progress;
+        element.download_speed_counter += self
+            .download_speed_counter
+            .saturating_sub(sent.download_speed_counter);
+        element.upload_speed_counter += self
+            .upload_speed_counter
+            .saturating_sub(sent.upload_speed_counter);
+        element.total_download += self.total_download.saturating_sub(sent.total_download);
+        element.total_upload += self.total_upload.saturating_sub(sent.total_upload);
+        element.is_error = self.is_error;
+        element.is_completed = self.is_completed;
+    }
+}
+
+/// The part of a location that the module host can see and change
+#[derive(Serialize, Deserialize)]
+struct LocationState {
+    uid: UID,
+    name: String,
+    desc: String,
+    data: HashMap<String, Atom>,
+    settings: Settings,
+    path: PathBuf,
+    status: usize,
+    statuses: Vec<String>,
+    progress: f32,
+    enabled: bool,
+    is_error: bool,
+    is_completed: bool,
+}
+
+impl LocationState {
+    fn new(location: &Location) -> Self {
+        Self {
+            uid: location.id.uid,
+            name: location.name.clone(),
+            desc: location.desc.clone(),
+            data: location.data.clone(),
+            settings: location.settings.clone(),
+            path: location.path.clone(),
+            status: location.status,
+            statuses: location.statuses.clone(),
+            progress: location.progress,
+            enabled: location.enabled,
+            is_error: location.is_error,
+            is_completed: location.is_completed,
+        }
+    }
+
+    fn to_location(&self) -> Location {
+        Location {
+            name: self.name.clone(),
+            desc: self.desc.clone(),
+            data: self.data.clone(),
+            path: self.path.clone(),
+            settings: self.settings.clone(),
+            module: None,
+            id: LocationId {
+                uid: self.uid,
+                session: None,
+            },
+            parent: None,
+            locations: Vec::new(),
+            elements: Vec::new(),
+            buffer: Vec::new(),
+            buffer_size: 0,
+            status: self.status,
+            statuses: self.statuses.clone(),
+            progress: self.progress,
+            download_speed: 0,
+            upload_speed: 0,
+            download_speed_counter: 0,
+            upload_speed_counter: 0,
+            total_download: 0,
+            total_upload: 0,
+            enabled: self.enabled,
+            is_error: self.is_error,
+            is_completed: self.is_completed,
+        }
+    }
+
+    fn apply(self, location: &mut Location) {
+        location.name = self.name;
+        location.desc = self.desc;
+        location.data = self.data;
+        location.settings = self.settings;
+        location.status = self.status;
+        location.statuses = self.statuses;
+        location.progress = self.progress;
+        location.is_error = self.is_error;
+        location.is_completed = self.is_completed;
+    }
+}
+
+struct Host {
+    child: std::process::Child,
+    writer: UnixStream,
+    reader: BufReader<UnixStream>,
+}
+
+impl Host {
+    fn spawn(module: &Path) -> Result<Self, RawLibraryError> {
+        let error = |error: std::io::Error| RawLibraryError::ModuleHost(error.to_string());
+
+        let (stream, host) = UnixStream::pair().map_err(error)?;
+        stream.set_read_timeout(Some(timeout())).map_err(error)?;
+        let child = std::process::Command::new(host_path())
+            .arg(module)
+            .stdin(std::os::fd::OwnedFd::from(host))
+            .spawn()
+            .map_err(error)?;
+
+        let reader = BufReader::new(stream.try_clone().map_err(error)?);
+        Ok(Self {
+            child,
+            writer: stream,
+            reader,
+        })
+    }
+
+    fn request(&mut self, request: &Request) -> std::io::Result<Response> {
+        let mut line = serde_json::to_vec(request)?;
+        line.push(b'\n');
+        self.writer.write_all(&line)?;
+
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+impl Drop for Host {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn timeout() -> Duration {
+    std::env::var(MODULE_HOST_TIMEOUT_ENV)
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_TIMEOUT)
+}
+
+/// The module host executable, from MUZZMAN_MODULE_HOST, next to the current executable or from PATH
+fn host_path() -> PathBuf {
+    if let Some(path) = std::env::var_os(MODULE_HOST_ENV) {
+        return path.into();
+    }
+    let name = format!("muzzman-module-host{}", std::env::consts::EXE_SUFFIX);
+    if let Ok(exe) = std::env::current_exe() {
+        // tests run from target/<profile>/deps
+        for dir in exe.ancestors().skip(1).take(2) {
+            if dir.join(&name).is_file() {
+                return dir.join(&name);
+            }
+        }
+    }
+    name.into()
+}
+
+pub struct ProcessModule {
+    path: PathBuf,
+    info: Info,
+    supported_versions: &'static [u64],
+    protocols: Vec<&'static str>,
+    extensions: Vec<&'static str>,
+    /// None after the module host crashed, it is started again on the next call
+    host: Mutex<Option<Host>>,
+}
+
+impl ProcessModule {
+    pub fn new(path: &Path) -> Result<Self, RawLibraryError> {
+        let mut host = Host::spawn(path)?;
+        let info = match host.request(&Request::Info) {
+            Ok(Response::Info(info)) => info,
+            Ok(Response::LoadError(error)) => return Err(error),
+            Ok(_) => return Err(RawLibraryError::ModuleHost("Invalid response".into())),
+            Err(error) => return Err(RawLibraryError::ModuleHost(error.to_string())),
+        };
+
+        let intern = |strings: &[String]| -> Vec<&'static str> {
+            strings.iter().map(|s| intern_str(s)).collect()
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            supported_versions: intern_versions(info.supported_versions.clone()),
+            protocols: intern(&info.protocols),
+            extensions: intern(&info.extensions),
+            info,
+            host: Mutex::new(Some(host)),
+        })
+    }
+
+    fn request(&self, request: Request) -> SessionResult<Response> {
+        let mut host = self.host.lock().unwrap();
+        if host.is_none() {
+            *host = Some(Host::spawn(&self.path)?);
+        }
+        let res = host.as_mut().unwrap().request(&request);
+        match res {
+            Ok(Response::LoadError(error)) => {
+                *host = None;
+                Err(error.into())
+            }
+            Ok(response) => Ok(response),
+            Err(error)
+                if matches!(
+                    error.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                // the old host is killed when dropped, the next call can use the new one
+                *host = Host::spawn(&self.path).ok();
+                Err(SessionError::Custom("Module host timed out".into()))
+            }
+            Err(error) => {
+                let mut dead = host.take().unwrap();
+                let status = dead.child.try_wait().ok().flatten();
+                Err(SessionError::Custom(match status {
+                    Some(status) => format!("Module host crashed: {status}"),
+                    None => format!("Module host crashed: {error}"),
+                }))
+            }
+        }
+    }
+
+    /// `sent` is the state from the request
+    fn element(
+        &self,
+        element: Arc<RwLock<Element>>,
+        sent: ElementState,
+        request: Request,
+        context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        let Response::Element(state, written, res, ops) = self.request(request)? else {
+            return Err(SessionError::Custom(
+                "Invalid response from module host".into(),
+            ));
+        };
+        let mut element = element.write().unwrap();
+        state.apply(&sent, &mut element);
+        if !written.is_empty() {
+            element.stream.write_all(&written)?;
+        }
+        context.extend(ops);
+        res.map_err(SessionError::Custom)
+    }
+
+    fn location(
+        &self,
+        location: Arc<RwLock<Location>>,
+        request: Request,
+        context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        let Response::Location(state, res, ops) = self.request(request)? else {
+            return Err(SessionError::Custom(
+                "Invalid response from module host".into(),
+            ));
+        };
+        state.apply(&mut location.write().unwrap());
+        context.extend(ops);
+        res.map_err(SessionError::Custom)
+    }
+}
+
+impl TModule for ProcessModule {
+    fn name(&self) -> &str {
+        &self.info.name
+    }
+
+    fn desc(&self) -> &str {
+        &self.info.desc
+    }
+
+    fn id(&self) -> u64 {
+        self.info.id
+    }
+
+    fn version(&self) -> u64 {
+        self.info.version
+    }
+
+    fn supported_versions(&self) -> &'static [u64] {
+        self.supported_versions
+    }
+
+    fn poll_element(
+        &self,
+        _ctx: &mut std::task::Context<'_>,
+        element: Arc<RwLock<Element>>,
+        _storage: &mut Storage,
+        context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        let state = ElementState::new(&element.read().unwrap());
+        self.element(element, state.clone(), Request::PollElement(state), context)
+    }
+
+    fn poll_location(
+        &self,
+        _ctx: &mut std::task::Context<'_>,
+        location: Arc<RwLock<Location>>,
+        _storage: &mut Storage,
+        context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        let state = LocationState::new(&location.read().unwrap());
+        self.location(location, Request::PollLocation(state), context)
+    }
+
+    fn element_on_event(
+        &self,
+        element: Arc<RwLock<Element>>,
+        event: Event,
+        _storage: &mut Storage,
+        context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        let state = ElementState::new(&element.read().unwrap());
+        self.element(
+            element,
+            state.clone(),
+            Request::ElementOnEvent(state, event),
+            context,
+        )
+    }
+
+    fn location_on_event(
+        &self,
+        location: Arc<RwLock<Location>>,
+        event: Event,
+        _storage: &mut Storage,
+        context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        let state = LocationState::new(&location.read().unwrap());
+        self.location(location, Request::LocationOnEvent(state, event), context)
+    }
+
+    fn default_element_settings(&self) -> Settings {
+        self.info.element_settings.clone()
+    }
+
+    fn default_location_settings(&self) -> Settings {
+        self.info.location_settings.clone()
+    }
+
+    fn supports_protocols(&self) -> &[&'static str] {
+        &self.protocols
+    }
+
+    fn supports_extensions(&self) -> &[&'static str] {
+        &self.extensions
+    }
+
+    fn permissions(&self) -> Permissions {
+        self.info.permissions.clone()
+    }
+
+    fn element_actions(&self) -> Vec<Action> {
+        self.info.element_actions.clone()
+    }
+
+    fn location_actions(&self) -> Vec<Action> {
+        self.info.location_actions.clone()
+    }
+
+    fn element_action(
+        &self,
+        element: Arc<RwLock<Element>>,
+        name: &str,
+        args: Vec<Atom>,
+        _storage: &mut Storage,
+        context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        let state = ElementState::new(&element.read().unwrap());
+        self.element(
+            element,
+            state.clone(),
+            Request::ElementAction(state, name.to_string(), args),
+            context,
+        )
+    }
+
+    fn location_action(
+        &self,
+        location: Arc<RwLock<Location>>,
+        name: &str,
+        args: Vec<Atom>,
+        _storage: &mut Storage,
+        context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        let state = LocationState::new(&location.read().unwrap());
+        self.location(
+            location,
+            Request::LocationAction(state, name.to_string(), args),
+            context,
+        )
+    }
+
+    // on_load is not forwarded because the module host has no session
+
+    fn on_unload(&self) {
+        let _ = self.request(Request::Unload);
+    }
+
+    fn on_element_attached(
+        &self,
+        element: Arc<RwLock<Element>>,
+        _storage: &mut Storage,
+    ) -> SessionResult<()> {
+        let state = ElementState::new(&element.read().unwrap());
+        self.element(
+            element,
+            state.clone(),
+            Request::ElementHook(state, Hook::Attached),
+            &mut ModuleContext::default(),
+        )
+    }
+
+    fn on_element_detached(&self, element: Arc<RwLock<Element>>, _storage: &mut Storage) {
+        let state = ElementState::new(&element.read().unwrap());
+        let _ = self.element(
+            element,
+            state.clone(),
+            Request::ElementHook(state, Hook::Detached),
+            &mut ModuleContext::default(),
+        );
+    }
+
+    fn on_element_destroyed(&self, element: Arc<RwLock<Element>>, _storage: &mut Storage) {
+        let state = ElementState::new(&element.read().unwrap());
+        let _ = self.element(
+            element,
+            state.clone(),
+            Request::ElementHook(state, Hook::Destroyed),
+            &mut ModuleContext::default(),
+        );
+    }
+
+    fn on_location_attached(
+        &self,
+        location: Arc<RwLock<Location>>,
+        _storage: &mut Storage,
+    ) -> SessionResult<()> {
+        let state = LocationState::new(&location.read().unwrap());
+        self.location(
+            location,
+            Request::LocationHook(state, Hook::Attached),
+            &mut ModuleContext::default(),
+        )
+    }
+
+    fn on_location_detached(&self, location: Arc<RwLock<Location>>, _storage: &mut Storage) {
+        let state = LocationState::new(&location.read().unwrap());
+        let _ = self.location(
+            location,
+            Request::LocationHook(state, Hook::Detached),
+            &mut ModuleContext::default(),
+        );
+    }
+
+    fn on_location_destroyed(&self, location: Arc<RwLock<Location>>, _storage: &mut Storage) {
+        let state = LocationState::new(&location.read().unwrap());
+        let _ = self.location(
+            location,
+            Request::LocationHook(state, Hook::Destroyed),
+            &mut ModuleContext::default(),
+        );
+    }
+}
+
+/// The module host side, loads the module and answers the session until `stream` is closed
+pub fn serve_module(stream: UnixStream, module: &Path) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let reader = BufReader::new(stream);
+
+    let module = RawModule::new_module(module);
+    let waker = std::task::Waker::noop();
+    let mut ctx = std::task::Context::from_waker(waker);
+    let mut element_storages = HashMap::<UID, Storage>::new();
+    let mut location_storages = HashMap::<UID, Storage>::new();
+
+    for line in reader.lines() {
+        let request: Request = serde_json::from_str(&line?)?;
+        let module = match &module {
+            Ok(module) => module,
+            Err(error) => {
+                send(&mut writer, &Response::LoadError(error.clone()))?;
+                continue;
+            }
+        };
+
+        // the operations are done by the session when it gets the response
+        let mut context = ModuleContext::default();
+        let response = match request {
+            Request::Info => Response::Info(Info {
+                name: module.name().to_string(),
+                desc: module.desc().to_string(),
+                id: module.id(),
+                version: module.version(),
+                supported_versions: module.supported_versions().to_vec(),
+                protocols: module
+                    .supports_protocols()
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect(),
+                extensions: module
+                    .supports_extensions()
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect(),
+                element_settings: module.default_element_settings(),
+                location_settings: module.default_location_settings(),
+                permissions: module.permissions(),
+                element_actions: module.element_actions(),
+                location_actions: module.location_actions(),
+            }),
+            Request::PollElement(state) => {
+                let storage = element_storages.entry(state.uid).or_default();
+                let element = Arc::new(RwLock::new(state.to_element()));
+                let res =
+                    catch(|| module.poll_element(&mut ctx, element.clone(), storage, &mut context));
+                element_response(element, res, &mut context)
+            }
+            Request::ElementOnEvent(state, event) => {
+                let storage = element_storages.entry(state.uid).or_default();
+                let element = Arc::new(RwLock::new(state.to_element()));
+                let res = catch(|| {
+                    module.element_on_event(element.clone(), event, storage, &mut context)
+                });
+                element_response(element, res, &mut context)
+            }
+            Request::ElementAction(state, name, args) => {
+                let storage = element_storages.entry(state.uid).or_default();
+                let element = Arc::new(RwLock::new(state.to_element()));
+                let res = catch(|| {
+                    module.element_action(element.clone(), &name, args, storage, &mut context)
+                });
+                element_response(element, res, &mut context)
+            }
+            Request::PollLocation(state) => {
+                let storage = location_storages.entry(state.uid).or_default();
+                let location = Arc::new(RwLock::new(state.to_location()));
+                let res = catch(|| {
+                    module.poll_location(&mut ctx, location.clone(), storage, &mut context)
+                });
+                let state = LocationState::new(&location.read().unwrap());
+                Response::Location(state, res, context.take())
+            }
+            Request::LocationOnEvent(state, event) => {
+                let storage = location_storages.entry(state.uid).or_default();
+                let location = Arc::new(RwLock::new(state.to_location()));
+                let res = catch(|| {
+                    module.location_on_event(location.clone(), event, storage, &mut context)
+                });
+                let state = LocationState::new(&location.read().unwrap());
+                Response::Location(state, res, context.take())
+            }
+            Request::LocationAction(state, name, args) => {
+                let storage = location_storages.entry(state.uid).or_default();
+                let location = Arc::new(RwLock::new(state.to_location()));
+                let res = catch(|| {
+                    module.location_action(location.clone(), &name, args, storage, &mut context)
+                });
+                let state = LocationState::new(&location.read().unwrap());
+                Response::Location(state, res, context.take())
+            }
+            Request::ElementHook(state, hook) => {
+                let uid = state.uid;
+                let storage = element_storages.entry(uid).or_default();
+                let element = Arc::new(RwLock::new(state.to_element()));
+                let res = catch(|| match hook {
+                    Hook::Attached => module.on_element_attached(element.clone(), storage),
+                    Hook::Detached => {
+                        module.on_element_detached(element.clone(), storage);
+                        Ok(())
+                    }
+                    Hook::Destroyed => {
+                        module.on_element_destroyed(element.clone(), storage);
+                        Ok(())
+                    }
+                });
+                // the session clears the storage also when attaching fails
+                if !matches!((hook, &res), (Hook::Attached, Ok(()))) {
+                    element_storages.remove(&uid);
+                }
+                element_response(element, res, &mut context)
+            }
+            Request::LocationHook(state, hook) => {
+                let uid = state.uid;
+                let storage = location_storages.entry(uid).or_default();
+                let location = Arc::new(RwLock::new(state.to_location()));
+                let res = catch(|| match hook {
+                    Hook::Attached => module.on_location_attached(location.clone(), storage),
+                    Hook::Detached => {
+                        module.on_location_detached(location.clone(), storage);
+                        Ok(())
+                    }
+                    Hook::Destroyed => {
+                        module.on_location_destroyed(location.clone(), storage);
+                        Ok(())
+                    }
+                });
+                if !matches!((hook, &res), (Hook::Attached, Ok(()))) {
+                    location_storages.remove(&uid);
+                }
+                let state = LocationState::new(&location.read().unwrap());
+                Response::Location(state, res, context.take())
+            }
+            Request::Unload => {
+                module.on_unload();
+                element_storages.clear();
+                location_storages.clear();
+                Response::Unloaded
+            }
+        };
+        send(&mut writer, &response)?;
+    }
+    Ok(())
+}
+
+fn send(writer: &mut UnixStream, response: &Response) -> std::io::Result<()> {
+    let mut line = serde_json::to_vec(response)?;
+    line.push(b'\n');
+    writer.write_all(&line)
+}
+
+/// Runs a module call, a panic becomes an error like in the session runner
+fn catch(call: impl FnOnce() -> SessionResult<()>) -> Result<(), String> {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(call)) {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(error)) => Err(format!("{error:?}")),
+        Err(panic) => Err(crate::runner::panic_message(panic)),
+    }
+}
+
+fn element_response(
+    element: Arc<RwLock<Element>>,
+    res: Result<(), String>,
+    context: &mut ModuleContext,
+) -> Response {
+    let mut element = element.write().unwrap();
+    let written = match std::mem::replace(&mut element.stream, Stream::None) {
+        Stream::Memory(cursor) => cursor.into_inner(),
+        _ => Vec::new(),
+    };
+    Response::Element(ElementState::new(&element), written, res, context.take())
+}
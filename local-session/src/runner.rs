@@ -0,0 +1,191 @@
+use std::{
+    panic::AssertUnwindSafe,
+    sync::Arc,
+    task::{Context, Wake, Waker},
+    time::{Duration, Instant},
+};
+
+use muzzman_lib::prelude::*;
+
+use crate::{
+    checksum, context, lifecycle, on_complete, partial_file, space, ElementWraper, ModuleWraper,
+    TLocalSession,
+};
+
+/// How long to wait for the module waker before polling again
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How often to check if there is enough free space for the element
+const SPACE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+struct ThreadWaker(std::thread::Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark()
+    }
+}
+
+/// Polls the element module until the element is disabled, completed or errored
+pub(crate) fn run_element(
+    session: Box<dyn TLocalSession>,
+    element: ElementWraper,
+    module: ModuleWraper,
+) {
+    let runtime = session.runtime();
+    let _guard = runtime.enter();
+
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut ctx = Context::from_waker(&waker);
+    let mut last_second = Instant::now();
+    let mut last_space_check = Instant::now();
+
+    if let Err(error) = partial_file::open(session.as_ref(), &element) {
+        set_error(&element, format!("{error:?}"));
+    }
+
+    loop {
+        {
+            let element = element.element.read().unwrap();
+            if !element.enabled || element.is_completed || element.is_error {
+                break;
+            }
+        }
+
+        // the proxy does the transfer for the module until the proxy ends
+        let proxy = element.element.read().unwrap().proxy.clone();
+        let (module, storage) = match proxy.and_then(|proxy| session.module(proxy.uid).ok()) {
+            Some(proxy) => (proxy, &element.proxy_storage),
+            None => (module.clone(), &element.storage),
+        };
+
+        let res = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            context::call(
+                session.as_ref(),
+                &module,
+                storage,
+                |module, storage, context| {
+                    module.poll_element(&mut ctx, element.element.clone(), storage, context)
+                },
+            )
+        }));
+
+        let error = match res {
+            Ok(Ok(())) => None,
+            Ok(Err(error)) => Some(format!("{error:?}")),
+            Err(panic) => {
+                storage.clear_poison();
+                Some(panic_message(panic))
+            }
+        };
+
+        if let Some(error) = error {
+            set_error(&element, error);
+            break;
+        }
+
+        if last_space_check.elapsed() >= SPACE_CHECK_INTERVAL {
+            last_space_check = Instant::now();
+            if let Err(error) = space::check_free_space(&element) {
+                set_error(&element, format!("{error:?}"));
+                break;
+            }
+        }
+
+        if last_second.elapsed() >= Duration::from_secs(1) {
+            last_second = Instant::now();
+            let mut element = element.element.write().unwrap();
+            element.download_speed = element.download_speed_counter;
+            element.upload_speed = element.upload_speed_counter;
+            element.download_speed_counter = 0;
+            element.upload_speed_counter = 0;
+        }
+
+        std::thread::park_timeout(POLL_INTERVAL);
+    }
+
+    // after an error the module starts again without the proxy, the error is kept
+    let proxy_error = {
+        let element = element.element.read().unwrap();
+        (element.is_error && element.proxy.is_some()).then(|| {
+            let error = element.statuses.get(element.status);
+            error.cloned().unwrap_or_default()
+        })
+    };
+    if let Some(error) = proxy_error {
+        lifecycle::proxy_detached(session.as_ref(), &element);
+        set_error(&element, error);
+    }
+
+    let is_completed = element.element.read().unwrap().is_completed;
+    if is_completed {
+        let res = checksum::verify(session.as_ref(), &element)
+            .and_then(|_| partial_file::complete(session.as_ref(), &element));
+        if let Err(error) = res {
+            element.element.write().unwrap().is_completed = false;
+            set_error(&element, format!("{error:?}"));
+        }
+    } else if let Err(error) = partial_file::pause(&element) {
+        set_error(&element, format!("{error:?}"));
+    }
+
+    let (uid, event) = {
+        let mut element = element.element.write().unwrap();
+        element.enabled = false;
+        element.download_speed = 0;
+        element.upload_speed = 0;
+        let uid = element.id.uid;
+        let event = if element.is_error {
+            Some(Event::Error(uid))
+        } else if element.is_completed {
+            Some(Event::Completed(uid))
+        } else {
+            None
+        };
+        (uid, event)
+    };
+
+    let is_completed = matches!(event, Some(Event::Completed(_)));
+    if let Some(event) = event {
+        let _ = session.emit(uid, event);
+    }
+    if is_completed {
+        on_complete::element_completed(session.as_ref(), &element);
+    }
+}
+
+/// Marks the element as errored and adds `error` as the current status
+pub(crate) fn set_error(element: &ElementWraper, error: String) {
+    let mut element = element.element.write().unwrap();
+    element.is_error = true;
+    element.status = element.statuses.len();
+    element.statuses.push(error);
+}
+
+/// Calls the module like the runner does, a panic becomes an error
+/// `poisoned` should clear the poison of the locks that the module could hold when it panicked
+pub(crate) fn catch_panic<T>(
+    f: impl FnOnce() -> SessionResult<T>,
+    poisoned: impl FnOnce(),
+) -> SessionResult<T> {
+    match std::panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(res) => res,
+        Err(panic) => {
+            poisoned();
+            Err(SessionError::Custom(panic_message(panic)))
+        }
+    }
+}
+
+pub(crate) fn panic_message(panic: Box<dyn std::any::Any + Send>) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Module panicked!".to_string()
+    }
+}
@@ -5,9 +5,7 @@ use std::{
 
 use muzzman_lib::prelude::*;
 
-use crate::{
-    module::RawModule, ElementWraper, LocationWraper, ModuleWraper, Path, UIDPath, Wraper,
-};
+use crate::{module::load, ElementWraper, LocationWraper, ModuleWraper, Path, UIDPath, Wraper};
 
 pub struct LocalSession {
     pub location: LocationWraper,
@@ -26,7 +24,7 @@ impl LocalSession {
                 desc: "This is the default location of the muzzman session".into(),
                 data: Default::default(),
                 path: PathBuf::new(),
-                settings: Default::default(),
+                settings: session_settings::defaults(),
                 module: None,
                 id: LocationId {
                     uid: 0,
@@ -36,6 +34,7 @@ impl LocalSession {
                 locations: vec![],
                 elements: vec![],
                 buffer: vec![],
+                buffer_size: DEFAULT_BUFFER_SIZE,
                 status: 0,
                 statuses: vec![],
                 progress: 0.0,
@@ -84,6 +83,15 @@ impl LocalSession {
     }
 }
 
+impl Drop for LocalSession {
+    fn drop(&mut self) {
+        // the libraries can be unloaded after this
+        for module in self.modules.iter() {
+            crate::lifecycle::unload(module);
+        }
+    }
+}
+
 pub trait TLocalSession: Send + Sync {
     /// create or get
     fn create_location(&self, name: String, path: &[usize]) -> LocationWraper;
@@ -95,13 +103,25 @@ pub trait TLocalSession: Send + Sync {
     fn location(&self, uid: UID) -> SessionResult<LocationWraper>;
     fn element(&self, uid: UID) -> SessionResult<ElementWraper>;
     fn module(&self, uid: UID) -> SessionResult<ModuleWraper>;
+    /// In the order that they were added
+    fn modules(&self) -> Vec<ModuleWraper>;
 
     fn add_module(&self, source: ModuleSource) -> SessionResult<ModuleId>;
+    /// Removes the module from the modules and marks the uid as destroyed
+    fn remove_module(&self, uid: UID) -> SessionResult<ModuleWraper>;
+    /// Removes the element from the parent location and marks the uid as destroyed
+    fn remove_element(&self, uid: UID) -> SessionResult<ElementWraper>;
+    /// Removes the location from the parent location and marks the uid of the location and everything inside as destroyed
+    fn remove_location(&self, uid: UID) -> SessionResult<LocationWraper>;
 
     fn default_location(&self) -> SessionResult<LocationId>;
     fn runtime(&self) -> Arc<tokio::runtime::Runtime>;
 
     fn weak_clone(&self) -> Box<dyn TLocalSession>;
+    /// Like weak_clone but without the module that calls, for the work that the session does for itself
+    fn weak_host(&self) -> Box<dyn TLocalSession>;
+    /// The module that calls the session with this handle, None when it is the user
+    fn caller(&self) -> Option<UID>;
 }
 
 impl TLocalSession for Arc<RwLock<LocalSession>> {
@@ -146,6 +166,7 @@ impl TLocalSession for Arc<RwLock<LocalSession>> {
                             locations: Vec::new(),
                             elements: Vec::new(),
                             buffer: vec![],
+                            buffer_size: DEFAULT_BUFFER_SIZE,
                             status: 0,
                             statuses: vec![],
                             progress: 0.0,
@@ -201,18 +222,24 @@ impl TLocalSession for Arc<RwLock<LocalSession>> {
                 uid,
                 session: Some(session),
             };
+            let file = crate::ElementFile {
+                path: location.path.clone().join(&name),
+                claimed: false,
+            };
             let element = ElementWraper {
                 element: Arc::new(RwLock::new(Element {
                     name: name.clone(),
                     desc: Default::default(),
                     data: Default::default(),
                     settings: Default::default(),
-                    path: location.path.clone().join(name),
+                    path: file.path.clone(),
                     module: None,
+                    proxy: None,
                     id: id.clone(),
                     parent: location.id.clone(),
                     stream: Stream::None,
                     buffer: vec![],
+                    buffer_size: DEFAULT_BUFFER_SIZE,
                     status: 0,
                     statuses: vec![],
                     progress: 0.0,
@@ -229,9 +256,11 @@ impl TLocalSession for Arc<RwLock<LocalSession>> {
                 })),
                 path: path.clone(),
                 storage: Default::default(),
+                proxy_storage: Default::default(),
                 thread: Default::default(),
                 sender: Default::default(),
                 events: Default::default(),
+                file: Arc::new(RwLock::new(file)),
             };
 
             elements.push(element.clone());
@@ -297,6 +326,10 @@ impl TLocalSession for Arc<RwLock<LocalSession>> {
         Ok(module)
     }
 
+    fn modules(&self) -> Vec<ModuleWraper> {
+        self.read().unwrap().modules.clone()
+    }
+
     fn default_location(&self) -> SessionResult<LocationId> {
         let location = self.read().unwrap().location.clone();
         let id = location.location.read().unwrap().id.clone();
@@ -307,22 +340,27 @@ impl TLocalSession for Arc<RwLock<LocalSession>> {
         Box::new(Arc::downgrade(self))
     }
 
+    fn weak_host(&self) -> Box<dyn TLocalSession> {
+        self.weak_clone()
+    }
+
+    fn caller(&self) -> Option<UID> {
+        None
+    }
+
     fn add_module(&self, source: ModuleSource) -> SessionResult<ModuleId> {
         let uid = {
-            let module = match source {
-                ModuleSource::Wasm(_) => unimplemented!(),
-                ModuleSource::Dynamic(path) => RawModule::new_module(&path)?,
-                ModuleSource::Box(module) => module,
-            };
+            let module = load(source, false)?;
             let id = module.id();
             let mut s = self.write().unwrap();
-            let mut index = s.modules.len();
-            for (i, module) in s.modules.iter().enumerate() {
-                if id == module.module.read().unwrap().module.id() {
-                    index = i;
-                    break;
-                }
+            if let Some(module) = s
+                .modules
+                .iter()
+                .find(|module| id == module.module.read().unwrap().module.id())
+            {
+                return Err(SessionError::ModuleAlreadyAdded(module.uid));
             }
+            let index = s.modules.len();
 
             let path = Arc::new(RwLock::new(UIDPath::Module(index)));
             let uid = s.register_path(path.clone());
@@ -331,23 +369,107 @@ impl TLocalSession for Arc<RwLock<LocalSession>> {
                 module: Arc::new(RwLock::new(Module {
                     name: module.name().to_string(),
                     desc: module.desc().to_string(),
-                    proxy: 0,
                     element_settings: module.default_element_settings(),
                     location_settings: module.default_location_settings(),
+                    permissions: module.permissions(),
                     module,
                 })),
                 path,
+                uid,
             };
 
             s.modules.push(module);
             uid
         };
+        let module = self.module(uid)?;
+        if let Err(error) = crate::lifecycle::load(self, &module) {
+            self.remove_module(uid)?;
+            return Err(error);
+        }
         Ok(ModuleId {
             uid,
             session: Some((Box::new(self.weak_clone()) as Box<dyn TSession>).into()),
         })
     }
 
+    fn remove_module(&self, uid: UID) -> SessionResult<ModuleWraper> {
+        let module = self.module(uid)?;
+        let UIDPath::Module(index) = *module.path.read().unwrap() else {
+            return Err(SessionError::UIDIsNotAModule);
+        };
+
+        let mut s = self.write().unwrap();
+        let module = s.modules.remove(index);
+        for module in s.modules[index..].iter() {
+            if let UIDPath::Module(index) = &mut *module.path.write().unwrap() {
+                *index -= 1;
+            }
+        }
+        *module.path.write().unwrap() = UIDPath::None;
+        Ok(module)
+    }
+
+    fn remove_element(&self, uid: UID) -> SessionResult<ElementWraper> {
+        let element = self.element(uid)?;
+        let UIDPath::Element(_, index) = *element.path.read().unwrap() else {
+            return Err(SessionError::UIDIsNotAElement);
+        };
+        let parent = element.element.read().unwrap().parent.clone();
+        let parent = self.location(parent.uid)?;
+
+        let mut elements = parent.elements.write().unwrap();
+        let element = elements.remove(index);
+        parent.location.write().unwrap().elements.remove(index);
+        for element in elements[index..].iter() {
+            if let UIDPath::Element(_, index) = &mut *element.path.write().unwrap() {
+                *index -= 1;
+            }
+        }
+        *element.path.write().unwrap() = UIDPath::None;
+        Ok(element)
+    }
+
+    fn remove_location(&self, uid: UID) -> SessionResult<LocationWraper> {
+        let location = self.location(uid)?;
+        let UIDPath::Location(path) = location.path.read().unwrap().clone() else {
+            return Err(SessionError::UIDIsNotALocation);
+        };
+        let Some((&index, parent_path)) = path.split_last() else {
+            return Err(SessionError::IsRoot);
+        };
+        let Some(parent) = location.location.read().unwrap().parent.clone() else {
+            return Err(SessionError::IsRoot);
+        };
+        let parent = self.location(parent.uid)?;
+
+        let location = parent.locations.write().unwrap().remove(index);
+        parent.location.write().unwrap().locations.remove(index);
+
+        // every path that goes through the parent has to be fixed
+        let refs = self.read().unwrap().refs.clone();
+        for path in refs {
+            let mut path = path.write().unwrap();
+            let location_path = match &mut *path {
+                UIDPath::Element(location_path, _) | UIDPath::Location(location_path) => {
+                    location_path
+                }
+                _ => continue,
+            };
+            if location_path.len() <= parent_path.len()
+                || location_path[..parent_path.len()] != *parent_path
+            {
+                continue;
+            }
+            let component = &mut location_path[parent_path.len()];
+            match (*component).cmp(&index) {
+                std::cmp::Ordering::Less => {}
+                std::cmp::Ordering::Equal => *path = UIDPath::None,
+                std::cmp::Ordering::Greater => *component -= 1,
+            }
+        }
+        Ok(location)
+    }
+
     fn runtime(&self) -> Arc<tokio::runtime::Runtime> {
         self.read().unwrap().runtime.clone()
     }
@@ -383,10 +505,26 @@ impl TLocalSession for Weak<RwLock<LocalSession>> {
         self.upgrade().expect(UPGRADE_ERROR).module(uid)
     }
 
+    fn modules(&self) -> Vec<ModuleWraper> {
+        self.upgrade().expect(UPGRADE_ERROR).modules()
+    }
+
     fn add_module(&self, source: ModuleSource) -> SessionResult<ModuleId> {
         self.upgrade().expect(UPGRADE_ERROR).add_module(source)
     }
 
+    fn remove_module(&self, uid: UID) -> SessionResult<ModuleWraper> {
+        self.upgrade().expect(UPGRADE_ERROR).remove_module(uid)
+    }
+
+    fn remove_element(&self, uid: UID) -> SessionResult<ElementWraper> {
+        self.upgrade().expect(UPGRADE_ERROR).remove_element(uid)
+    }
+
+    fn remove_location(&self, uid: UID) -> SessionResult<LocationWraper> {
+        self.upgrade().expect(UPGRADE_ERROR).remove_location(uid)
+    }
+
     fn default_location(&self) -> SessionResult<LocationId> {
         self.upgrade().expect(UPGRADE_ERROR).default_location()
     }
@@ -395,11 +533,102 @@ impl TLocalSession for Weak<RwLock<LocalSession>> {
         Box::new(self.clone())
     }
 
+    fn weak_host(&self) -> Box<dyn TLocalSession> {
+        self.weak_clone()
+    }
+
+    fn caller(&self) -> Option<UID> {
+        None
+    }
+
     fn runtime(&self) -> Arc<tokio::runtime::Runtime> {
         self.upgrade().expect(UPGRADE_ERROR).runtime()
     }
 }
 
+/// The session handle of a module, the session checks its calls with the permissions of the module
+/// It is given to TModule::on_load, so a module that keeps it is known on any thread
+pub(crate) struct ModuleSession {
+    session: Box<dyn TLocalSession>,
+    module: UID,
+}
+
+impl ModuleSession {
+    #[allow(clippy::new_ret_no_self)]
+    pub(crate) fn new(session: &dyn TLocalSession, module: UID) -> Box<dyn TLocalSession> {
+        Box::new(Self {
+            session: session.weak_host(),
+            module,
+        })
+    }
+}
+
+impl TLocalSession for ModuleSession {
+    fn create_location(&self, name: String, path: &[usize]) -> LocationWraper {
+        self.session.as_ref().create_location(name, path)
+    }
+
+    fn create_element(&self, name: String, path: &[usize]) -> ElementWraper {
+        self.session.as_ref().create_element(name, path)
+    }
+
+    fn get(&self, uid: UID) -> SessionResult<Wraper> {
+        self.session.as_ref().get(uid)
+    }
+
+    fn location(&self, uid: UID) -> SessionResult<LocationWraper> {
+        self.session.as_ref().location(uid)
+    }
+
+    fn element(&self, uid: UID) -> SessionResult<ElementWraper> {
+        self.session.as_ref().element(uid)
+    }
+
+    fn module(&self, uid: UID) -> SessionResult<ModuleWraper> {
+        self.session.as_ref().module(uid)
+    }
+
+    fn modules(&self) -> Vec<ModuleWraper> {
+        self.session.as_ref().modules()
+    }
+
+    fn add_module(&self, source: ModuleSource) -> SessionResult<ModuleId> {
+        self.session.as_ref().add_module(source)
+    }
+
+    fn remove_module(&self, uid: UID) -> SessionResult<ModuleWraper> {
+        self.session.as_ref().remove_module(uid)
+    }
+
+    fn remove_element(&self, uid: UID) -> SessionResult<ElementWraper> {
+        self.session.as_ref().remove_element(uid)
+    }
+
+    fn remove_location(&self, uid: UID) -> SessionResult<LocationWraper> {
+        self.session.as_ref().remove_location(uid)
+    }
+
+    fn default_location(&self) -> SessionResult<LocationId> {
+        self.session.as_ref().default_location()
+    }
+
+    fn runtime(&self) -> Arc<tokio::runtime::Runtime> {
+        self.session.as_ref().runtime()
+    }
+
+    fn weak_clone(&self) -> Box<dyn TLocalSession> {
+        Self::new(self.session.as_ref(), self.module)
+    }
+
+    fn weak_host(&self) -> Box<dyn TLocalSession> {
+        self.session.as_ref().weak_host()
+    }
+
+    fn caller(&self) -> Option<UID> {
+        Some(self.module)
+    }
+}
+
 impl TSession for Box<dyn TLocalSession> {
     fn weak_box(&self) -> Box<dyn TSession> {
         Box::new(self.weak_clone())
@@ -1,6 +1,6 @@
 use muzzman_lib::prelude::*;
 
-use crate::TLocalSession;
+use crate::{context, permissions, TLocalSession};
 
 impl TSessionCommon for Box<dyn TLocalSession> {
     fn get_name(&self, uid: UID) -> SessionResult<String> {
@@ -42,7 +42,7 @@ impl TSessionCommon for Box<dyn TLocalSession> {
         match self
             .as_ref()
             .get(uid)
-            .map_err(|e| SessionError::SetName(Box::new(e)))?
+            .map_err(|e| SessionError::SetDesc(Box::new(e)))?
         {
             crate::Wraper::Element(e) => e.element.write().unwrap().desc = desc,
             crate::Wraper::Location(l) => l.location.write().unwrap().desc = desc,
@@ -53,6 +53,7 @@ impl TSessionCommon for Box<dyn TLocalSession> {
 
     fn emit(&self, uid: UID, event: Event) -> SessionResult<()> {
         let inner = move || {
+            permissions::check_events(self.as_ref(), uid)?;
             let (event, subscribers) = match self.as_ref().get(uid)? {
                 crate::Wraper::Element(e) => {
                     let event =
@@ -96,13 +97,18 @@ impl TSessionCommon for Box<dyn TLocalSession> {
                                 .push_back(event.clone());
                             if let Some(module) = element.element.read().unwrap().module.clone() {
                                 let module = self.as_ref().module(module.uid)?;
-                                let module = module.module.read().unwrap();
-                                let module = &module.module;
-                                let mut storage = element.storage.write().unwrap();
-                                module.element_on_event(
-                                    element.element.clone(),
-                                    event.clone(),
-                                    &mut storage,
+                                context::call(
+                                    self.as_ref(),
+                                    &module,
+                                    &element.storage,
+                                    |module, storage, context| {
+                                        module.element_on_event(
+                                            element.element.clone(),
+                                            event.clone(),
+                                            storage,
+                                            context,
+                                        )
+                                    },
                                 )?;
                             }
                         }
@@ -115,13 +121,18 @@ impl TSessionCommon for Box<dyn TLocalSession> {
                                 .push_back(event.clone());
                             if let Some(module) = location.location.read().unwrap().module.clone() {
                                 let module = self.as_ref().module(module.uid)?;
-                                let module = module.module.read().unwrap();
-                                let module = &module.module;
-                                let mut storage = location.storage.write().unwrap();
-                                module.location_on_event(
-                                    location.location.clone(),
-                                    event.clone(),
-                                    &mut storage,
+                                context::call(
+                                    self.as_ref(),
+                                    &module,
+                                    &location.storage,
+                                    |module, storage, context| {
+                                        module.location_on_event(
+                                            location.location.clone(),
+                                            event.clone(),
+                                            storage,
+                                            context,
+                                        )
+                                    },
                                 )?;
                             }
                         }
@@ -142,6 +153,8 @@ impl TSessionCommon for Box<dyn TLocalSession> {
 
     fn notify(&self, uid: UID, to: UID, event: Event) -> SessionResult<()> {
         let inner = move || {
+            permissions::check_events(self.as_ref(), uid)?;
+            permissions::check_events(self.as_ref(), to)?;
             let event = Event::From(uid, Box::new(event));
             match self.as_ref().get(to)? {
                 crate::Wraper::Element(element) => {
@@ -153,13 +166,18 @@ impl TSessionCommon for Box<dyn TLocalSession> {
                         .push_back(event.clone());
                     if let Some(module) = element.element.read().unwrap().module.clone() {
                         let module = self.as_ref().module(module.uid)?;
-                        let module = module.module.read().unwrap();
-                        let module = &module.module;
-                        let mut storage = element.storage.write().unwrap();
-                        module.element_on_event(
-                            element.element.clone(),
-                            event.clone(),
-                            &mut storage,
+                        context::call(
+                            self.as_ref(),
+                            &module,
+                            &element.storage,
+                            |module, storage, context| {
+                                module.element_on_event(
+                                    element.element.clone(),
+                                    event.clone(),
+                                    storage,
+                                    context,
+                                )
+                            },
                         )?;
                     }
                 }
@@ -172,13 +190,18 @@ impl TSessionCommon for Box<dyn TLocalSession> {
                         .push_back(event.clone());
                     if let Some(module) = location.location.read().unwrap().module.clone() {
                         let module = self.as_ref().module(module.uid)?;
-                        let module = module.module.read().unwrap();
-                        let module = &module.module;
-                        let mut storage = location.storage.write().unwrap();
-                        module.location_on_event(
-                            location.location.clone(),
-                            event.clone(),
-                            &mut storage,
+                        context::call(
+                            self.as_ref(),
+                            &module,
+                            &location.storage,
+                            |module, storage, context| {
+                                module.location_on_event(
+                                    location.location.clone(),
+                                    event.clone(),
+                                    storage,
+                                    context,
+                                )
+                            },
                         )?;
                     }
                 }
@@ -191,6 +214,8 @@ impl TSessionCommon for Box<dyn TLocalSession> {
 
     fn subscribe(&self, uid: UID, to: UID) -> SessionResult<()> {
         let inner = move || {
+            permissions::check_events(self.as_ref(), uid)?;
+            permissions::check_events(self.as_ref(), to)?;
             match self.as_ref().get(to)? {
                 crate::Wraper::Element(e) => {
                     e.events.write().unwrap().subscribers.insert(uid);
@@ -207,6 +232,8 @@ impl TSessionCommon for Box<dyn TLocalSession> {
 
     fn unsubscribe(&self, uid: UID, from: UID) -> SessionResult<()> {
         let inner = move || {
+            permissions::check_events(self.as_ref(), uid)?;
+            permissions::check_events(self.as_ref(), from)?;
             match self.as_ref().get(from)? {
                 crate::Wraper::Element(e) => {
                     e.events.write().unwrap().subscribers.remove(&uid);
@@ -218,38 +245,98 @@ impl TSessionCommon for Box<dyn TLocalSession> {
             }
             Ok(())
         };
-        inner().map_err(|e| SessionError::Subscribe(Box::new(e)))
+        inner().map_err(|e| SessionError::UnSubscribe(Box::new(e)))
     }
 
     fn events(&self, uid: UID, consume: bool) -> SessionResult<Vec<Event>> {
-        todo!()
+        let inner = move || {
+            permissions::check_events(self.as_ref(), uid)?;
+            let events = match self.as_ref().get(uid)? {
+                crate::Wraper::Element(e) => e.events,
+                crate::Wraper::Location(l) => l.events,
+                _ => return Err(SessionError::IsNotAnElementOrLocation),
+            };
+            let mut events = events.write().unwrap();
+            let res = events.events.to_vec();
+            if consume {
+                events.events.clear();
+            }
+            Ok(res)
+        };
+        inner().map_err(|e| SessionError::Events(Box::new(e)))
     }
 
     fn push_event(&self, uid: UID, event: Event) -> SessionResult<()> {
-        todo!()
+        let inner = move || {
+            permissions::check_events(self.as_ref(), uid)?;
+            match self.as_ref().get(uid)? {
+                crate::Wraper::Element(e) => e.events.write().unwrap().events.push_back(event),
+                crate::Wraper::Location(l) => l.events.write().unwrap().events.push_back(event),
+                _ => return Err(SessionError::IsNotAnElementOrLocation),
+            }
+            Ok(())
+        };
+        inner().map_err(|e| SessionError::PushEvent(Box::new(e)))
     }
 
     fn get_buffer_size(&self, uid: UID) -> SessionResult<usize> {
-        todo!()
+        let inner = move || {
+            Ok(match self.as_ref().get(uid)? {
+                crate::Wraper::Element(e) => e.element.read().unwrap().buffer_size,
+                crate::Wraper::Location(l) => l.location.read().unwrap().buffer_size,
+                _ => return Err(SessionError::IsNotAnElementOrLocation),
+            })
+        };
+        inner().map_err(|e| SessionError::GetBufferSize(Box::new(e)))
     }
 
     fn set_buffer_size(&self, uid: UID, size: usize) -> SessionResult<()> {
-        todo!()
+        let inner = move || {
+            match self.as_ref().get(uid)? {
+                crate::Wraper::Element(e) => e.element.write().unwrap().buffer_size = size,
+                crate::Wraper::Location(l) => l.location.write().unwrap().buffer_size = size,
+                _ => return Err(SessionError::IsNotAnElementOrLocation),
+            }
+            Ok(())
+        };
+        inner().map_err(|e| SessionError::SetBufferSize(Box::new(e)))
     }
 
     fn remaining(&self, uid: UID) -> SessionResult<usize> {
-        todo!()
+        let inner = move || {
+            Ok(match self.as_ref().get(uid)? {
+                crate::Wraper::Element(e) => e.element.read().unwrap().buffer.len(),
+                crate::Wraper::Location(l) => l.location.read().unwrap().buffer.len(),
+                _ => return Err(SessionError::IsNotAnElementOrLocation),
+            })
+        };
+        inner().map_err(|e| SessionError::Remaining(Box::new(e)))
     }
 
     fn read(&self, uid: UID, len: usize) -> SessionResult<Vec<u8>> {
-        todo!()
+        let inner = move || {
+            Ok(match self.as_ref().get(uid)? {
+                crate::Wraper::Element(e) => drain(&mut e.element.write().unwrap().buffer, len),
+                crate::Wraper::Location(l) => drain(&mut l.location.write().unwrap().buffer, len),
+                _ => return Err(SessionError::IsNotAnElementOrLocation),
+            })
+        };
+        inner().map_err(|e| SessionError::Read(Box::new(e)))
     }
 
     fn write(&self, uid: UID, data: &[u8]) -> SessionResult<usize> {
         let inner = move || {
-            let event = Event::NewData(data.to_vec());
-            match self.as_ref().get(uid)? {
+            let written = match self.as_ref().get(uid)? {
                 crate::Wraper::Element(element) => {
+                    let written = {
+                        let mut e = element.element.write().unwrap();
+                        let size = e.buffer_size;
+                        fill(&mut e.buffer, size, data)
+                    };
+                    if written == 0 {
+                        return Ok(0);
+                    }
+                    let event = Event::NewData(data[..written].to_vec());
                     element
                         .events
                         .write()
@@ -258,17 +345,32 @@ impl TSessionCommon for Box<dyn TLocalSession> {
                         .push_back(event.clone());
                     if let Some(module) = element.element.read().unwrap().module.clone() {
                         let module = self.as_ref().module(module.uid)?;
-                        let module = module.module.read().unwrap();
-                        let module = &module.module;
-                        let mut storage = element.storage.write().unwrap();
-                        module.element_on_event(
-                            element.element.clone(),
-                            event.clone(),
-                            &mut storage,
+                        context::call(
+                            self.as_ref(),
+                            &module,
+                            &element.storage,
+                            |module, storage, context| {
+                                module.element_on_event(
+                                    element.element.clone(),
+                                    event.clone(),
+                                    storage,
+                                    context,
+                                )
+                            },
                         )?;
                     }
+                    written
                 }
                 crate::Wraper::Location(location) => {
+                    let written = {
+                        let mut l = location.location.write().unwrap();
+                        let size = l.buffer_size;
+                        fill(&mut l.buffer, size, data)
+                    };
+                    if written == 0 {
+                        return Ok(0);
+                    }
+                    let event = Event::NewData(data[..written].to_vec());
                     location
                         .events
                         .write()
@@ -277,20 +379,39 @@ impl TSessionCommon for Box<dyn TLocalSession> {
                         .push_back(event.clone());
                     if let Some(module) = location.location.read().unwrap().module.clone() {
                         let module = self.as_ref().module(module.uid)?;
-                        let module = module.module.read().unwrap();
-                        let module = &module.module;
-                        let mut storage = location.storage.write().unwrap();
-                        module.location_on_event(
-                            location.location.clone(),
-                            event.clone(),
-                            &mut storage,
+                        context::call(
+                            self.as_ref(),
+                            &module,
+                            &location.storage,
+                            |module, storage, context| {
+                                module.location_on_event(
+                                    location.location.clone(),
+                                    event.clone(),
+                                    storage,
+                                    context,
+                                )
+                            },
                         )?;
                     }
+                    written
                 }
                 _ => return Err(SessionError::IsNotAnElementOrLocation),
             };
-            Ok(data.len())
+            Ok(written)
         };
         inner().map_err(|e| SessionError::Write(Box::new(e)))
     }
 }
+
+/// Appends as much from `data` as fits in `size` and returns how much was appended
+fn fill(buffer: &mut Vec<u8>, size: usize, data: &[u8]) -> usize {
+    let len = size.saturating_sub(buffer.len()).min(data.len());
+    buffer.extend_from_slice(&data[..len]);
+    len
+}
+
+/// Removes and returns at most `len` bytes from the start of `buffer`
+fn drain(buffer: &mut Vec<u8>, len: usize) -> Vec<u8> {
+    let len = len.min(buffer.len());
+    buffer.drain(..len).collect()
+}
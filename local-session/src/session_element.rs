@@ -1,10 +1,14 @@
 use muzzman_lib::prelude::*;
 
-use crate::{TLocalSession, UIDPath};
+use crate::{
+    context, lifecycle, module_select, partial_file::part_path, permissions, runner, ElementFile,
+    TLocalSession, UIDPath,
+};
 
 impl TSessionElement for Box<dyn TLocalSession> {
     fn create_element(&self, location: LocationId, name: String) -> SessionResult<ElementId> {
         let inner = move || {
+            permissions::check_create_elements(self.as_ref())?;
             let parent = self.as_ref().location(location.uid)?;
             let UIDPath::Location(mut path) = parent.path.read().unwrap().clone() else {
                 return Err(SessionError::UIDIsNotALocation);
@@ -26,11 +30,50 @@ impl TSessionElement for Box<dyn TLocalSession> {
         inner().map_err(|e| SessionError::CreateElement(Box::new(e)))
     }
 
+    fn create_element_from_url(
+        &self,
+        location: LocationId,
+        url: String,
+    ) -> SessionResult<ElementId> {
+        let inner = move || {
+            let parent = self.as_ref().location(location.uid)?;
+            let Some(module) = module_select::select(self.as_ref(), &parent, &url) else {
+                return Err(SessionError::NoModule);
+            };
+            let element = self.create_element(location, module_select::name(&url))?;
+            let module = ModuleId {
+                uid: module.uid,
+                session: Some(self.as_ref().weak_host().weak_box().into()),
+            };
+            self.element_set_module(element.clone(), Some(module))?;
+            self.element_set_url(element.clone(), url)?;
+            Ok(element)
+        };
+        inner().map_err(|e| SessionError::CreateElementFromUrl(Box::new(e)))
+    }
+
     fn get_element(&self, path: Vec<usize>) -> SessionResult<ElementId> {
-        todo!()
+        let inner = move || {
+            let Some((index, path)) = path.split_last() else {
+                return Err(SessionError::InvalidUID);
+            };
+            let location = self
+                .as_ref()
+                .location(0)?
+                .location_at(path)
+                .ok_or(SessionError::InvalidUID)?;
+            let element = location.elements.read().unwrap().get(*index).cloned();
+            if let Some(element) = element {
+                let id = element.element.read().unwrap().id.clone();
+                Ok(id)
+            } else {
+                Err(SessionError::ThereAreLessElements)
+            }
+        };
+        inner().map_err(|e| SessionError::GetElement(Box::new(e)))
     }
 
-    fn move_element(&self, element: ElementId, location: LocationId) -> SessionResult<()> {
+    fn move_element(&self, _element: ElementId, _location: LocationId) -> SessionResult<()> {
         todo!()
     }
 
@@ -43,7 +86,7 @@ impl TSessionElement for Box<dyn TLocalSession> {
             element.push(index);
             Ok(element)
         };
-        inner().map_err(|e| SessionError::CreateElement(Box::new(e)))
+        inner().map_err(|e| SessionError::ElementPath(Box::new(e)))
     }
 
     fn element_get_parent(&self, element: ElementId) -> SessionResult<LocationId> {
@@ -70,29 +113,61 @@ impl TSessionElement for Box<dyn TLocalSession> {
     fn element_set_enabled(&self, element: ElementId, enabled: bool) -> SessionResult<()> {
         let inner = move || {
             let element = self.as_ref().element(element.uid)?;
+            permissions::check_element(self.as_ref(), &element)?;
             if element.element.read().unwrap().enabled == enabled {
                 return Ok(());
             }
             if !enabled {
-                element.thread.write().unwrap().take().unwrap();
                 element.element.write().unwrap().enabled = false;
+                let thread = element.thread.write().unwrap().take();
+                if let Some(thread) = thread {
+                    // the module can disable the element from inside the poll
+                    if thread.thread().id() != std::thread::current().id() {
+                        let _ = thread.join();
+                    }
+                }
             } else {
                 let Some(module_id) = element.element.read().unwrap().module.clone() else {
                     return Err(SessionError::NoModule);
                 };
                 let module = self.as_ref().module(module_id.uid)?;
-                element.element.write().unwrap().enabled = true;
                 let errors = element.element.read().unwrap().settings.validate();
                 if !errors.is_empty() {
                     return Err(SessionError::InvalidSettings(errors));
                 }
+                // the path is resolved only the first time, after the file is of the element
+                let claimed = element.file.read().unwrap().claimed
+                    || element
+                        .element
+                        .read()
+                        .unwrap()
+                        .data
+                        .contains_key(element_data::PARTIAL_LENGTH);
+                if !claimed {
+                    let path = element.element.read().unwrap().path.clone();
+                    permissions::check_file(self.as_ref(), &element, &path)?;
+                    let Some(path) = crate::collision::resolve(self.as_ref(), &element, path)?
+                    else {
+                        let mut element = element.element.write().unwrap();
+                        element.is_completed = true;
+                        element.status = element.statuses.len();
+                        element
+                            .statuses
+                            .push("Skipped, the path is already used".into());
+                        return Ok(());
+                    };
+                    element.element.write().unwrap().path = path.clone();
+                    *element.file.write().unwrap() = ElementFile {
+                        path,
+                        claimed: true,
+                    };
+                }
+                crate::space::check_start(self.as_ref(), &element)?;
+                element.element.write().unwrap().enabled = true;
+                let session = self.as_ref().weak_host();
                 let thread = element.thread.clone();
-                let runtime = self.runtime();
                 *thread.write().unwrap() = Some(std::thread::spawn(move || {
-                    unimplemented!();
-
-                    // When ends
-                    element.element.write().unwrap().enabled = false;
+                    crate::runner::run_element(session, element, module)
                 }));
             }
             Ok(())
@@ -101,39 +176,111 @@ impl TSessionElement for Box<dyn TLocalSession> {
     }
 
     fn element_get_path(&self, element: ElementId) -> SessionResult<std::path::PathBuf> {
-        todo!()
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            let path = element.element.read().unwrap().path.clone();
+            Ok(path)
+        };
+        inner().map_err(|e| SessionError::ElementGetPath(Box::new(e)))
     }
 
     fn element_set_path(&self, element: ElementId, path: std::path::PathBuf) -> SessionResult<()> {
-        todo!()
+        let inner = move || {
+            permissions::check_write(self.as_ref(), &path)?;
+            let element = self.as_ref().element(element.uid)?;
+            permissions::check_element(self.as_ref(), &element)?;
+            if element.element.read().unwrap().path == path {
+                element.file.write().unwrap().path = path;
+                return Ok(());
+            }
+            // Skip keeps the current path
+            let Some(path) = crate::collision::resolve(self.as_ref(), &element, path)? else {
+                return Ok(());
+            };
+            let old = element.element.read().unwrap().path.clone();
+            permissions::check_file(self.as_ref(), &element, &old)?;
+            let mut file = element.file.write().unwrap();
+            let mut element = element.element.write().unwrap();
+            if element.data.contains_key(element_data::PARTIAL_LENGTH) {
+                let part = part_path(&element.path);
+                if part.exists() {
+                    std::fs::rename(part, part_path(&path))?;
+                }
+            }
+            file.path = path.clone();
+            element.path = path;
+            Ok(())
+        };
+        inner().map_err(|e| SessionError::ElementSetPath(Box::new(e)))
     }
 
     fn element_is_completed(&self, element: ElementId) -> SessionResult<bool> {
-        todo!()
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            let is_completed = element.element.read().unwrap().is_completed;
+            Ok(is_completed)
+        };
+        inner().map_err(|e| SessionError::ElementIsCompleted(Box::new(e)))
     }
 
     fn element_is_error(&self, element: ElementId) -> SessionResult<bool> {
-        todo!()
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            let is_error = element.element.read().unwrap().is_error;
+            Ok(is_error)
+        };
+        inner().map_err(|e| SessionError::ElementIsError(Box::new(e)))
     }
 
     fn element_get_statuses(&self, element: ElementId) -> SessionResult<Vec<String>> {
-        todo!()
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            let statuses = element.element.read().unwrap().statuses.clone();
+            Ok(statuses)
+        };
+        inner().map_err(|e| SessionError::ElementGetStatuses(Box::new(e)))
     }
 
     fn element_set_statuses(&self, element: ElementId, statuses: Vec<String>) -> SessionResult<()> {
-        todo!()
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            permissions::check_element(self.as_ref(), &element)?;
+            element.element.write().unwrap().statuses = statuses;
+            Ok(())
+        };
+        inner().map_err(|e| SessionError::ElementSetStatuses(Box::new(e)))
     }
 
     fn element_get_status(&self, element: ElementId) -> SessionResult<usize> {
-        todo!()
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            let status = element.element.read().unwrap().status;
+            Ok(status)
+        };
+        inner().map_err(|e| SessionError::ElementGetStatus(Box::new(e)))
     }
 
     fn element_set_status(&self, element: ElementId, status: usize) -> SessionResult<()> {
-        todo!()
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            permissions::check_element(self.as_ref(), &element)?;
+            element.element.write().unwrap().status = status;
+            Ok(())
+        };
+        inner().map_err(|e| SessionError::ElementSetStatus(Box::new(e)))
     }
 
     fn element_get_status_str(&self, element: ElementId) -> SessionResult<String> {
-        todo!()
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            let element = element.element.read().unwrap();
+            if let Some(status) = element.statuses.get(element.status) {
+                Ok(status.clone())
+            } else {
+                Err(SessionError::InvalidStatus)
+            }
+        };
+        inner().map_err(|e| SessionError::ElementGetStatusStr(Box::new(e)))
     }
 
     fn element_get_url(&self, element: ElementId) -> SessionResult<String> {
@@ -147,38 +294,84 @@ impl TSessionElement for Box<dyn TLocalSession> {
 
     fn element_set_url(&self, element: ElementId, url: String) -> SessionResult<()> {
         let inner = move || {
-            let element = self.as_ref().element(element.uid)?;
-            element.element.write().unwrap().url = url;
+            let wraper = self.as_ref().element(element.uid)?;
+            permissions::check_element(self.as_ref(), &wraper)?;
+            let (module, parent) = {
+                let mut wraper = wraper.element.write().unwrap();
+                wraper.url = url.clone();
+                (wraper.module.clone(), wraper.parent.clone())
+            };
+            if module.is_none() {
+                let parent = self.as_ref().location(parent.uid)?;
+                if let Some(module) = module_select::select(self.as_ref(), &parent, &url) {
+                    // attached like any other module, after the url is set
+                    let module = ModuleId {
+                        uid: module.uid,
+                        session: Some(self.as_ref().weak_host().weak_box().into()),
+                    };
+                    self.element_set_module(element, Some(module))?;
+                }
+            }
             Ok(())
         };
         inner().map_err(|e| SessionError::ElementSetUrl(Box::new(e)))
     }
 
     fn element_get_progress(&self, element: ElementId) -> SessionResult<f32> {
-        todo!()
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            let progress = element.element.read().unwrap().progress;
+            Ok(progress)
+        };
+        inner().map_err(|e| SessionError::ElementGetProgress(Box::new(e)))
     }
 
     fn element_get_download_speed(&self, element: ElementId) -> SessionResult<usize> {
-        todo!()
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            let download_speed = element.element.read().unwrap().download_speed;
+            Ok(download_speed)
+        };
+        inner().map_err(|e| SessionError::ElementGetDownloadSpeed(Box::new(e)))
     }
 
     fn element_get_upload_speed(&self, element: ElementId) -> SessionResult<usize> {
-        todo!()
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            let upload_speed = element.element.read().unwrap().upload_speed;
+            Ok(upload_speed)
+        };
+        inner().map_err(|e| SessionError::ElementGetUploadSpeed(Box::new(e)))
     }
 
     fn element_get_download_total(&self, element: ElementId) -> SessionResult<usize> {
-        todo!()
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            let total_download = element.element.read().unwrap().total_download;
+            Ok(total_download)
+        };
+        inner().map_err(|e| SessionError::ElementGetDownloadTotal(Box::new(e)))
     }
 
     fn element_get_upload_total(&self, element: ElementId) -> SessionResult<usize> {
-        todo!()
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            let total_upload = element.element.read().unwrap().total_upload;
+            Ok(total_upload)
+        };
+        inner().map_err(|e| SessionError::ElementGetUploadTotal(Box::new(e)))
     }
 
     fn element_get_data(
         &self,
         element: ElementId,
     ) -> SessionResult<std::collections::HashMap<String, Atom>> {
-        todo!()
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            let data = element.element.read().unwrap().data.clone();
+            Ok(data)
+        };
+        inner().map_err(|e| SessionError::ElementGetData(Box::new(e)))
     }
 
     fn element_set_data(
@@ -186,19 +379,43 @@ impl TSessionElement for Box<dyn TLocalSession> {
         element: ElementId,
         data: std::collections::HashMap<String, Atom>,
     ) -> SessionResult<()> {
-        todo!()
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            permissions::check_element(self.as_ref(), &element)?;
+            element.element.write().unwrap().data = data;
+            Ok(())
+        };
+        inner().map_err(|e| SessionError::ElementSetData(Box::new(e)))
     }
 
     fn element_get_settings(&self, element: ElementId) -> SessionResult<Settings> {
-        todo!()
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            let settings = element.element.read().unwrap().settings.clone();
+            Ok(settings)
+        };
+        inner().map_err(|e| SessionError::ElementGetSettings(Box::new(e)))
     }
 
     fn element_set_settings(&self, element: ElementId, settings: Settings) -> SessionResult<()> {
-        todo!()
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            permissions::check_element(self.as_ref(), &element)?;
+            let mut element = element.element.write().unwrap();
+            permissions::check_settings(self.as_ref(), &element.settings, &settings)?;
+            element.settings = settings;
+            Ok(())
+        };
+        inner().map_err(|e| SessionError::ElementSetSettings(Box::new(e)))
     }
 
     fn element_get_module(&self, element: ElementId) -> SessionResult<Option<ModuleId>> {
-        todo!()
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            let module = element.element.read().unwrap().module.clone();
+            Ok(module)
+        };
+        inner().map_err(|e| SessionError::ElementGetModule(Box::new(e)))
     }
 
     fn element_set_module(
@@ -206,20 +423,150 @@ impl TSessionElement for Box<dyn TLocalSession> {
         element: ElementId,
         module_id: Option<ModuleId>,
     ) -> SessionResult<()> {
-        self.as_ref()
-            .element(element.uid)?
-            .element
-            .write()
-            .unwrap()
-            .module = module_id;
-        Ok(())
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            permissions::check_element(self.as_ref(), &element)?;
+            let module_id = module_id.map(|module| ModuleId {
+                uid: module.uid,
+                session: Some(self.as_ref().weak_host().weak_box().into()),
+            });
+            let old = element.element.read().unwrap().module.clone();
+            if old == module_id {
+                return Ok(());
+            }
+            // the runner would keep polling the old module
+            if element.element.read().unwrap().enabled {
+                return Err(SessionError::ElementIsEnabled);
+            }
+            lifecycle::element_detached(self.as_ref(), &element);
+            element.element.write().unwrap().module = module_id;
+            lifecycle::element_attached(self.as_ref(), &element)
+        };
+        inner().map_err(|e| SessionError::ElementSetModule(Box::new(e)))
+    }
+
+    fn element_get_proxy(&self, element: ElementId) -> SessionResult<Option<ModuleId>> {
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            let proxy = element.element.read().unwrap().proxy.clone();
+            Ok(proxy)
+        };
+        inner().map_err(|e| SessionError::ElementGetProxy(Box::new(e)))
+    }
+
+    fn element_set_proxy(&self, element: ElementId, proxy: Option<ModuleId>) -> SessionResult<()> {
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            let (module, old) = {
+                let element = element.element.read().unwrap();
+                (element.module.clone(), element.proxy.clone())
+            };
+            permissions::check_module_of(self.as_ref(), module.as_ref())?;
+            let Some(module) = module else {
+                return Err(SessionError::NoModule);
+            };
+            if old == proxy {
+                return Ok(());
+            }
+            if let Some(proxy) = &proxy {
+                if proxy.uid == module.uid {
+                    return Err(SessionError::ProxyToItself);
+                }
+                self.as_ref().module(proxy.uid)?;
+            }
+            lifecycle::proxy_detached(self.as_ref(), &element);
+            element.element.write().unwrap().proxy = proxy;
+            lifecycle::proxy_attached(self.as_ref(), &element)
+        };
+        inner().map_err(|e| SessionError::ElementSetProxy(Box::new(e)))
+    }
+
+    fn element_get_actions(&self, element: ElementId) -> SessionResult<Vec<Action>> {
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            let Some(module) = element.element.read().unwrap().module.clone() else {
+                return Ok(Vec::new());
+            };
+            let module = self.as_ref().module(module.uid)?;
+            let actions = module.module.read().unwrap().module.element_actions();
+            Ok(actions)
+        };
+        inner().map_err(|e| SessionError::ElementGetActions(Box::new(e)))
+    }
+
+    fn element_run_action(
+        &self,
+        element: ElementId,
+        name: String,
+        args: Vec<Atom>,
+    ) -> SessionResult<()> {
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            let Some(module) = element.element.read().unwrap().module.clone() else {
+                return Err(SessionError::NoModule);
+            };
+            let module = self.as_ref().module(module.uid)?;
+            let action = module
+                .module
+                .read()
+                .unwrap()
+                .module
+                .element_actions()
+                .into_iter()
+                .find(|action| action.name == name);
+            let Some(action) = action else {
+                return Err(SessionError::NoAction(name));
+            };
+            if !action.validate(&args) {
+                return Err(SessionError::InvalidActionArgs(name));
+            }
+            runner::catch_panic(
+                || {
+                    context::call(
+                        self.as_ref(),
+                        &module,
+                        &element.storage,
+                        |module, storage, context| {
+                            module.element_action(
+                                element.element.clone(),
+                                &name,
+                                args,
+                                storage,
+                                context,
+                            )
+                        },
+                    )
+                },
+                || {
+                    element.storage.clear_poison();
+                    element.element.clear_poison();
+                },
+            )
+        };
+        inner().map_err(|e| SessionError::ElementRunAction(Box::new(e)))
     }
 
     fn element_wait(&self, element: ElementId) -> SessionResult<()> {
-        todo!()
+        let inner = move || {
+            let element = self.as_ref().element(element.uid)?;
+            let thread = element.thread.write().unwrap().take();
+            if let Some(thread) = thread {
+                let _ = thread.join();
+            }
+            Ok(())
+        };
+        inner().map_err(|e| SessionError::ElementWait(Box::new(e)))
     }
 
     fn destroy_element(&self, element: ElementId) -> SessionResult<()> {
-        todo!()
+        let inner = move || {
+            permissions::check_create_elements(self.as_ref())?;
+            let wraper = self.as_ref().element(element.uid)?;
+            self.element_set_enabled(element.clone(), false)?;
+            lifecycle::element_destroyed(self.as_ref(), &wraper);
+            self.as_ref().remove_element(element.uid)?;
+            Ok(())
+        };
+        inner().map_err(|e| SessionError::DestroyElement(Box::new(e)))
     }
 }
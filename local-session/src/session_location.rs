@@ -1,10 +1,11 @@
 use muzzman_lib::prelude::*;
 
-use crate::{TLocalSession, UIDPath, Wraper};
+use crate::{context, lifecycle, permissions, runner, TLocalSession, UIDPath};
 
 impl TSessionLocation for Box<dyn TLocalSession> {
     fn create_location(&self, location: LocationId, name: String) -> SessionResult<LocationId> {
         let inner = move || {
+            permissions::check_create_elements(self.as_ref())?;
             let location_parent = self.as_ref().location(location.uid)?;
             let UIDPath::Location(mut path) = location_parent.path.read().unwrap().clone() else {
                 return Err(SessionError::UIDIsNotALocation);
@@ -112,9 +113,9 @@ impl TSessionLocation for Box<dyn TLocalSession> {
         inner().map_err(|e| SessionError::LocationGetEnabled(Box::new(e)))
     }
 
-    fn location_set_enabled(&self, location: LocationId, enabled: bool) -> SessionResult<()> {
+    fn location_set_enabled(&self, location: LocationId, _enabled: bool) -> SessionResult<()> {
         let inner = move || {
-            let location = self.as_ref().location(location.uid)?;
+            let _location = self.as_ref().location(location.uid)?;
             // TODO: LocalSession::location_set_enabled
             // We need to start the location on a separate thread
             // The thread when will finish will set the location enabled to false
@@ -141,6 +142,7 @@ impl TSessionLocation for Box<dyn TLocalSession> {
         path: std::path::PathBuf,
     ) -> SessionResult<()> {
         let inner = move || {
+            permissions::check_write(self.as_ref(), &path)?;
             let location = self.as_ref().location(location.uid)?;
             location.location.write().unwrap().path = path;
             Ok(())
@@ -151,7 +153,7 @@ impl TSessionLocation for Box<dyn TLocalSession> {
     fn location_is_completed(&self, location: LocationId) -> SessionResult<bool> {
         let inner = move || {
             let location = self.as_ref().location(location.uid)?;
-            let is_completed = location.location.read().unwrap().is_completed.clone();
+            let is_completed = location.location.read().unwrap().is_completed;
             Ok(is_completed)
         };
         inner().map_err(|e| SessionError::LocationIsCompleted(Box::new(e)))
@@ -160,7 +162,7 @@ impl TSessionLocation for Box<dyn TLocalSession> {
     fn location_is_error(&self, location: LocationId) -> SessionResult<bool> {
         let inner = move || {
             let location = self.as_ref().location(location.uid)?;
-            let is_error = location.location.read().unwrap().is_error.clone();
+            let is_error = location.location.read().unwrap().is_error;
             Ok(is_error)
         };
         inner().map_err(|e| SessionError::LocationIsError(Box::new(e)))
@@ -191,7 +193,7 @@ impl TSessionLocation for Box<dyn TLocalSession> {
     fn location_get_status(&self, location: LocationId) -> SessionResult<usize> {
         let inner = move || {
             let location = self.as_ref().location(location.uid)?;
-            let status = location.location.read().unwrap().status.clone();
+            let status = location.location.read().unwrap().status;
             Ok(status)
         };
         inner().map_err(|e| SessionError::LocationGetStatus(Box::new(e)))
@@ -220,30 +222,60 @@ impl TSessionLocation for Box<dyn TLocalSession> {
     }
 
     fn location_get_progress(&self, location: LocationId) -> SessionResult<f32> {
-        todo!()
+        let inner = move || {
+            let location = self.as_ref().location(location.uid)?;
+            let progress = location.location.read().unwrap().progress;
+            Ok(progress)
+        };
+        inner().map_err(|e| SessionError::LocationGetProgress(Box::new(e)))
     }
 
     fn location_get_download_speed(&self, location: LocationId) -> SessionResult<usize> {
-        todo!()
+        let inner = move || {
+            let location = self.as_ref().location(location.uid)?;
+            let download_speed = location.location.read().unwrap().download_speed;
+            Ok(download_speed)
+        };
+        inner().map_err(|e| SessionError::LocationGetDownloadSpeed(Box::new(e)))
     }
 
     fn location_get_upload_speed(&self, location: LocationId) -> SessionResult<usize> {
-        todo!()
+        let inner = move || {
+            let location = self.as_ref().location(location.uid)?;
+            let upload_speed = location.location.read().unwrap().upload_speed;
+            Ok(upload_speed)
+        };
+        inner().map_err(|e| SessionError::LocationGetUploadSpeed(Box::new(e)))
     }
 
     fn location_get_download_total(&self, location: LocationId) -> SessionResult<usize> {
-        todo!()
+        let inner = move || {
+            let location = self.as_ref().location(location.uid)?;
+            let total_download = location.location.read().unwrap().total_download;
+            Ok(total_download)
+        };
+        inner().map_err(|e| SessionError::LocationGetDownloadTotal(Box::new(e)))
     }
 
     fn location_get_upload_total(&self, location: LocationId) -> SessionResult<usize> {
-        todo!()
+        let inner = move || {
+            let location = self.as_ref().location(location.uid)?;
+            let total_upload = location.location.read().unwrap().total_upload;
+            Ok(total_upload)
+        };
+        inner().map_err(|e| SessionError::LocationGetUploadTotal(Box::new(e)))
     }
 
     fn location_get_data(
         &self,
         location: LocationId,
     ) -> SessionResult<std::collections::HashMap<String, Atom>> {
-        todo!()
+        let inner = move || {
+            let location = self.as_ref().location(location.uid)?;
+            let data = location.location.read().unwrap().data.clone();
+            Ok(data)
+        };
+        inner().map_err(|e| SessionError::LocationGetData(Box::new(e)))
     }
 
     fn location_set_data(
@@ -251,19 +283,41 @@ impl TSessionLocation for Box<dyn TLocalSession> {
         location: LocationId,
         data: std::collections::HashMap<String, Atom>,
     ) -> SessionResult<()> {
-        todo!()
+        let inner = move || {
+            let location = self.as_ref().location(location.uid)?;
+            location.location.write().unwrap().data = data;
+            Ok(())
+        };
+        inner().map_err(|e| SessionError::LocationSetData(Box::new(e)))
     }
 
     fn location_get_settings(&self, location: LocationId) -> SessionResult<Settings> {
-        todo!()
+        let inner = move || {
+            let location = self.as_ref().location(location.uid)?;
+            let settings = location.location.read().unwrap().settings.clone();
+            Ok(settings)
+        };
+        inner().map_err(|e| SessionError::LocationGetSettings(Box::new(e)))
     }
 
     fn location_set_settings(&self, location: LocationId, settings: Settings) -> SessionResult<()> {
-        todo!()
+        let inner = move || {
+            let location = self.as_ref().location(location.uid)?;
+            let mut location = location.location.write().unwrap();
+            permissions::check_settings(self.as_ref(), &location.settings, &settings)?;
+            location.settings = settings;
+            Ok(())
+        };
+        inner().map_err(|e| SessionError::LocationSetSettings(Box::new(e)))
     }
 
     fn location_get_module(&self, location: LocationId) -> SessionResult<Option<ModuleId>> {
-        todo!()
+        let inner = move || {
+            let location = self.as_ref().location(location.uid)?;
+            let module = location.location.read().unwrap().module.clone();
+            Ok(module)
+        };
+        inner().map_err(|e| SessionError::LocationGetModule(Box::new(e)))
     }
 
     fn location_set_module(
@@ -271,26 +325,129 @@ impl TSessionLocation for Box<dyn TLocalSession> {
         location: LocationId,
         module_id: Option<ModuleId>,
     ) -> SessionResult<()> {
-        todo!()
+        let inner = move || {
+            let location = self.as_ref().location(location.uid)?;
+            let old = location.location.read().unwrap().module.clone();
+            if old == module_id {
+                return Ok(());
+            }
+            lifecycle::location_detached(self.as_ref(), &location);
+            location.location.write().unwrap().module = module_id;
+            lifecycle::location_attached(self.as_ref(), &location)
+        };
+        inner().map_err(|e| SessionError::LocationSetModule(Box::new(e)))
     }
 
-    fn move_location(
+    fn location_get_actions(&self, location: LocationId) -> SessionResult<Vec<Action>> {
+        let inner = move || {
+            let location = self.as_ref().location(location.uid)?;
+            let Some(module) = location.location.read().unwrap().module.clone() else {
+                return Ok(Vec::new());
+            };
+            let module = self.as_ref().module(module.uid)?;
+            let actions = module.module.read().unwrap().module.location_actions();
+            Ok(actions)
+        };
+        inner().map_err(|e| SessionError::LocationGetActions(Box::new(e)))
+    }
+
+    fn location_run_action(
         &self,
         location: LocationId,
-        location_location: LocationId,
+        name: String,
+        args: Vec<Atom>,
+    ) -> SessionResult<()> {
+        let inner = move || {
+            let location = self.as_ref().location(location.uid)?;
+            let Some(module) = location.location.read().unwrap().module.clone() else {
+                return Err(SessionError::NoModule);
+            };
+            let module = self.as_ref().module(module.uid)?;
+            let action = module
+                .module
+                .read()
+                .unwrap()
+                .module
+                .location_actions()
+                .into_iter()
+                .find(|action| action.name == name);
+            let Some(action) = action else {
+                return Err(SessionError::NoAction(name));
+            };
+            if !action.validate(&args) {
+                return Err(SessionError::InvalidActionArgs(name));
+            }
+            runner::catch_panic(
+                || {
+                    context::call(
+                        self.as_ref(),
+                        &module,
+                        &location.storage,
+                        |module, storage, context| {
+                            module.location_action(
+                                location.location.clone(),
+                                &name,
+                                args,
+                                storage,
+                                context,
+                            )
+                        },
+                    )
+                },
+                || {
+                    location.storage.clear_poison();
+                    location.location.clear_poison();
+                },
+            )
+        };
+        inner().map_err(|e| SessionError::LocationRunAction(Box::new(e)))
+    }
+
+    fn move_location(
+        &self,
+        _location: LocationId,
+        _location_location: LocationId,
     ) -> SessionResult<()> {
         todo!()
     }
 
     fn location_path(&self, location: LocationId) -> SessionResult<Vec<usize>> {
-        todo!()
+        let inner = move || {
+            let location = self.as_ref().location(location.uid)?;
+            let UIDPath::Location(path) = location.path.read().unwrap().clone() else {
+                return Err(SessionError::UIDIsNotALocation);
+            };
+            Ok(path)
+        };
+        inner().map_err(|e| SessionError::LocationPath(Box::new(e)))
     }
 
-    fn location_wait(&self, location: LocationId) -> SessionResult<()> {
+    fn location_wait(&self, _location: LocationId) -> SessionResult<()> {
         todo!()
     }
 
     fn destroy_location(&self, location: LocationId) -> SessionResult<()> {
-        todo!()
+        let inner = move || {
+            permissions::check_create_elements(self.as_ref())?;
+            let wraper = self.as_ref().location(location.uid)?;
+            if wraper.location.read().unwrap().parent.is_none() {
+                return Err(SessionError::IsRoot);
+            }
+            // the uids do not change when the paths are fixed
+            let (locations, elements) = {
+                let location = wraper.location.read().unwrap();
+                (location.locations.clone(), location.elements.clone())
+            };
+            for child in locations {
+                self.destroy_location(child)?;
+            }
+            for element in elements {
+                self.destroy_element(element)?;
+            }
+            lifecycle::location_destroyed(self.as_ref(), &wraper);
+            self.as_ref().remove_location(location.uid)?;
+            Ok(())
+        };
+        inner().map_err(|e| SessionError::DestroyLocation(Box::new(e)))
     }
 }
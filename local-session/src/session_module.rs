@@ -1,18 +1,168 @@
+use std::{collections::HashMap, env::consts::DLL_EXTENSION, path::PathBuf};
+
 use muzzman_lib::prelude::*;
 
-use crate::{module::RawModule, TLocalSession};
+use crate::{
+    lifecycle,
+    module::{load, RawModule},
+    permissions,
+    wasm_module::WasmModule,
+    ElementWraper, LocationWraper, ModuleWraper, TLocalSession, UIDPath,
+};
 
 impl TSessionModule for Box<dyn TLocalSession> {
     fn add_module(&self, source: ModuleSource) -> SessionResult<ModuleId> {
+        permissions::check_not_module(self.as_ref())
+            .map_err(|e| SessionError::AddModule(Box::new(e)))?;
         self.as_ref().add_module(source)
     }
 
+    fn load_modules(&self, dirs: Vec<PathBuf>) -> SessionResult<Vec<ModuleLoadReport>> {
+        let inner = move || {
+            permissions::check_not_module(self.as_ref())?;
+            let mut paths = Vec::new();
+            for dir in dirs {
+                let Ok(entries) = std::fs::read_dir(&dir) else {
+                    continue;
+                };
+                let mut dir_paths = entries
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| {
+                        path.is_file()
+                            && path.extension().is_some_and(|extension| {
+                                extension == DLL_EXTENSION || extension == "wasm"
+                            })
+                    })
+                    .collect::<Vec<PathBuf>>();
+                dir_paths.sort();
+                paths.append(&mut dir_paths);
+            }
+
+            let mut reports = Vec::new();
+            let mut loaded = Vec::new();
+            for path in paths {
+                let module = if path
+                    .extension()
+                    .is_some_and(|extension| extension == "wasm")
+                {
+                    std::fs::read(&path)
+                        .map_err(|_| RawLibraryError::NotFound)
+                        .and_then(|wasm| Ok(Box::new(WasmModule::new(&wasm)?) as Box<dyn TModule>))
+                } else {
+                    RawModule::new_module(&path)
+                };
+                match module {
+                    Ok(module) => loaded.push((path, module)),
+                    Err(error) => reports.push(ModuleLoadReport {
+                        path,
+                        result: Err(error),
+                    }),
+                }
+            }
+
+            // for every id the index of the module with the biggest version
+            let mut best = HashMap::<u64, usize>::new();
+            for (index, (_, module)) in loaded.iter().enumerate() {
+                let best = best.entry(module.id()).or_insert(index);
+                if module.version() > loaded[*best].1.version() {
+                    *best = index;
+                }
+            }
+
+            for (index, (path, module)) in loaded.into_iter().enumerate() {
+                let id = module.id();
+                let result = if best[&id] != index {
+                    Err(RawLibraryError::Duplicate(id))
+                } else {
+                    // a module that fails does not stop the others
+                    match self
+                        .add_module(ModuleSource::Box(module))
+                        .and_then(|module| self.module_info(module))
+                    {
+                        Ok(info) => Ok(info),
+                        Err(SessionError::ModuleAlreadyAdded(_)) => {
+                            Err(RawLibraryError::Duplicate(id))
+                        }
+                        Err(error) => Err(RawLibraryError::AddModule(format!("{error:?}"))),
+                    }
+                };
+                reports.push(ModuleLoadReport { path, result });
+            }
+
+            reports.sort_by(|a, b| a.path.cmp(&b.path));
+            Ok(reports)
+        };
+        inner().map_err(|e| SessionError::LoadModules(Box::new(e)))
+    }
+
     fn get_module(&self, path: usize) -> SessionResult<ModuleId> {
-        todo!()
+        let inner = move || {
+            let Some(module) = self.as_ref().modules().get(path).cloned() else {
+                return Err(SessionError::NoModule);
+            };
+            Ok(module_id(self.as_ref(), &module))
+        };
+        inner().map_err(|e| SessionError::GetModule(Box::new(e)))
+    }
+
+    fn get_modules(&self) -> SessionResult<Vec<ModuleId>> {
+        Ok(self
+            .as_ref()
+            .modules()
+            .iter()
+            .map(|module| module_id(self.as_ref(), module))
+            .collect())
+    }
+
+    fn get_modules_info(&self) -> SessionResult<Vec<ModuleInfo>> {
+        Ok(self
+            .as_ref()
+            .modules()
+            .iter()
+            .map(|module| ModuleInfo::new(module.uid, &module.module.read().unwrap()))
+            .collect())
+    }
+
+    fn find_module_by_id(&self, id: u64) -> SessionResult<ModuleId> {
+        let inner = move || {
+            self.as_ref()
+                .modules()
+                .iter()
+                .find(|module| module.module.read().unwrap().module.id() == id)
+                .map(|module| module_id(self.as_ref(), module))
+                .ok_or(SessionError::NoModule)
+        };
+        inner().map_err(|e| SessionError::FindModuleById(Box::new(e)))
+    }
+
+    fn find_module_by_name(&self, name: String) -> SessionResult<ModuleId> {
+        let inner = move || {
+            self.as_ref()
+                .modules()
+                .iter()
+                .find(|module| module.module.read().unwrap().name == name)
+                .map(|module| module_id(self.as_ref(), module))
+                .ok_or(SessionError::NoModule)
+        };
+        inner().map_err(|e| SessionError::FindModuleByName(Box::new(e)))
+    }
+
+    fn module_info(&self, module: ModuleId) -> SessionResult<ModuleInfo> {
+        let inner = move || {
+            let module = self.as_ref().module(module.uid)?;
+            let info = ModuleInfo::new(module.uid, &module.module.read().unwrap());
+            Ok(info)
+        };
+        inner().map_err(|e| SessionError::ModuleInfo(Box::new(e)))
     }
 
     fn module_get_element_settings(&self, module: ModuleId) -> SessionResult<Settings> {
-        todo!()
+        let inner = move || {
+            let module = self.as_ref().module(module.uid)?;
+            let settings = module.module.read().unwrap().element_settings.clone();
+            Ok(settings)
+        };
+        inner().map_err(|e| SessionError::ModuleGetElementSettings(Box::new(e)))
     }
 
     fn module_set_element_settings(
@@ -20,11 +170,27 @@ impl TSessionModule for Box<dyn TLocalSession> {
         module: ModuleId,
         settings: Settings,
     ) -> SessionResult<()> {
-        todo!()
+        let inner = move || {
+            permissions::check_not_module(self.as_ref())?;
+            let module = self.as_ref().module(module.uid)?;
+            let errors = settings.validate();
+            if !errors.is_empty() {
+                return Err(SessionError::InvalidSettings(errors));
+            }
+            // used for the elements that are attached after this
+            module.module.write().unwrap().element_settings = settings;
+            Ok(())
+        };
+        inner().map_err(|e| SessionError::ModuleSetElementSettings(Box::new(e)))
     }
 
     fn module_get_location_settings(&self, module: ModuleId) -> SessionResult<Settings> {
-        todo!()
+        let inner = move || {
+            let module = self.as_ref().module(module.uid)?;
+            let settings = module.module.read().unwrap().location_settings.clone();
+            Ok(settings)
+        };
+        inner().map_err(|e| SessionError::ModuleGetLocationSettings(Box::new(e)))
     }
 
     fn module_set_location_settings(
@@ -32,26 +198,268 @@ impl TSessionModule for Box<dyn TLocalSession> {
         module: ModuleId,
         settings: Settings,
     ) -> SessionResult<()> {
-        todo!()
+        let inner = move || {
+            permissions::check_not_module(self.as_ref())?;
+            let module = self.as_ref().module(module.uid)?;
+            let errors = settings.validate();
+            if !errors.is_empty() {
+                return Err(SessionError::InvalidSettings(errors));
+            }
+            // used for the locations that are attached after this
+            module.module.write().unwrap().location_settings = settings;
+            Ok(())
+        };
+        inner().map_err(|e| SessionError::ModuleSetLocationSettings(Box::new(e)))
     }
 
     fn module_supports_protocols(&self, module: ModuleId) -> SessionResult<Vec<String>> {
-        todo!()
+        let inner = move || {
+            let module = self.as_ref().module(module.uid)?;
+            let protocols = module
+                .module
+                .read()
+                .unwrap()
+                .module
+                .supports_protocols()
+                .iter()
+                .map(|protocol| protocol.to_string())
+                .collect();
+            Ok(protocols)
+        };
+        inner().map_err(|e| SessionError::ModuleSupportsProtocols(Box::new(e)))
     }
 
     fn module_supports_extensions(&self, module: ModuleId) -> SessionResult<Vec<String>> {
-        todo!()
+        let inner = move || {
+            let module = self.as_ref().module(module.uid)?;
+            let extensions = module
+                .module
+                .read()
+                .unwrap()
+                .module
+                .supports_extensions()
+                .iter()
+                .map(|extension| extension.to_string())
+                .collect();
+            Ok(extensions)
+        };
+        inner().map_err(|e| SessionError::ModuleSupportsExtensions(Box::new(e)))
     }
 
     fn module_path(&self, module: ModuleId) -> SessionResult<usize> {
-        todo!()
+        let inner = move || {
+            let module = self.as_ref().module(module.uid)?;
+            let UIDPath::Module(index) = *module.path.read().unwrap() else {
+                return Err(SessionError::UIDIsNotAModule);
+            };
+            Ok(index)
+        };
+        inner().map_err(|e| SessionError::ModulePath(Box::new(e)))
     }
 
     fn module_id(&self, module: ModuleId) -> SessionResult<u64> {
-        todo!()
+        let inner = move || {
+            let module = self.as_ref().module(module.uid)?;
+            let id = module.module.read().unwrap().module.id();
+            Ok(id)
+        };
+        inner().map_err(|e| SessionError::ModuleId(Box::new(e)))
+    }
+
+    fn module_get_permissions(&self, module: ModuleId) -> SessionResult<Permissions> {
+        let inner = move || {
+            let module = self.as_ref().module(module.uid)?;
+            let permissions = module.module.read().unwrap().permissions.clone();
+            Ok(permissions)
+        };
+        inner().map_err(|e| SessionError::ModuleGetPermissions(Box::new(e)))
+    }
+
+    fn module_set_permissions(
+        &self,
+        module: ModuleId,
+        permissions: Permissions,
+    ) -> SessionResult<()> {
+        let inner = move || {
+            permissions::check_not_module(self.as_ref())?;
+            let module = self.as_ref().module(module.uid)?;
+            module.module.write().unwrap().permissions = permissions;
+            Ok(())
+        };
+        inner().map_err(|e| SessionError::ModuleSetPermissions(Box::new(e)))
+    }
+
+    fn module_reload(&self, module: ModuleId, source: ModuleSource) -> SessionResult<()> {
+        let inner = move || {
+            permissions::check_not_module(self.as_ref())?;
+            let wraper = self.as_ref().module(module.uid)?;
+            let new_module = load(source, true)?;
+            let id = wraper.module.read().unwrap().module.id();
+            if new_module.id() != id {
+                return Err(SessionError::ModuleIdMismatch(id, new_module.id()));
+            }
+
+            let mut paused = Vec::new();
+            let (elements, proxied, locations) = match self.as_ref().location(0) {
+                Ok(root) => (
+                    elements_using(&root, module.uid),
+                    elements_proxied_to(&root, module.uid),
+                    locations_using(&root, module.uid),
+                ),
+                Err(_) => Default::default(),
+            };
+            for element in elements.iter().chain(proxied.iter()) {
+                let (enabled, id) = {
+                    let element = element.element.read().unwrap();
+                    (element.enabled, element.id.clone())
+                };
+                if !enabled {
+                    continue;
+                }
+                self.element_set_enabled(id.clone(), false)?;
+                paused.push(id);
+            }
+            // the new module starts from a clean storage
+            for element in elements.iter() {
+                lifecycle::element_detached(self.as_ref(), element);
+            }
+            // the modules of the proxied elements poll them again
+            for element in proxied.iter() {
+                lifecycle::proxy_detached(self.as_ref(), element);
+            }
+            for location in locations.iter() {
+                lifecycle::location_detached(self.as_ref(), location);
+            }
+            lifecycle::unload(&wraper);
+
+            let old_module = {
+                let mut module = wraper.module.write().unwrap();
+                let mut element_settings = new_module.default_element_settings();
+                element_settings.migrate_values(&module.element_settings);
+                let mut location_settings = new_module.default_location_settings();
+                location_settings.migrate_values(&module.location_settings);
+
+                module.name = new_module.name().to_string();
+                module.desc = new_module.desc().to_string();
+                module.element_settings = element_settings;
+                module.location_settings = location_settings;
+                std::mem::replace(&mut module.module, new_module)
+            };
+            // the old library is unloaded after is not used
+            drop(old_module);
+
+            lifecycle::load(self.as_ref(), &wraper)?;
+            let mut errors = Vec::new();
+            for element in elements.iter() {
+                if let Err(error) = lifecycle::element_attached(self.as_ref(), element) {
+                    errors.push(error);
+                }
+            }
+            for location in locations.iter() {
+                if let Err(error) = lifecycle::location_attached(self.as_ref(), location) {
+                    errors.push(error);
+                }
+            }
+            errors.extend(
+                paused
+                    .into_iter()
+                    .filter_map(|element| self.element_set_enabled(element, true).err()),
+            );
+            if !errors.is_empty() {
+                return Err(SessionError::Errors(errors));
+            }
+            Ok(())
+        };
+        inner().map_err(|e| SessionError::ModuleReload(Box::new(e)))
     }
 
     fn destroy_module(&self, module: ModuleId) -> SessionResult<()> {
-        todo!()
+        let inner = move || {
+            permissions::check_not_module(self.as_ref())?;
+            self.as_ref().module(module.uid)?;
+
+            if let Ok(root) = self.as_ref().location(0) {
+                // after this no thread is polling the module
+                for element in elements_using(&root, module.uid) {
+                    let id = element.element.read().unwrap().id.clone();
+                    self.element_set_enabled(id.clone(), false)?;
+                    self.element_set_module(id, None)?;
+                }
+                for element in elements_proxied_to(&root, module.uid) {
+                    let id = element.element.read().unwrap().id.clone();
+                    self.element_set_enabled(id, false)?;
+                    lifecycle::proxy_detached(self.as_ref(), &element);
+                }
+                for location in locations_using(&root, module.uid) {
+                    let id = location.location.read().unwrap().id.clone();
+                    self.location_set_module(id, None)?;
+                }
+            }
+
+            lifecycle::unload(&self.as_ref().module(module.uid)?);
+            let wraper = self.as_ref().remove_module(module.uid)?;
+            // the library is unloaded when the last reference to the module is dropped
+            drop(wraper);
+            Ok(())
+        };
+        inner().map_err(|e| SessionError::DestroyModule(Box::new(e)))
+    }
+}
+
+fn uses(module_id: &Option<ModuleId>, module: UID) -> bool {
+    module_id
+        .as_ref()
+        .is_some_and(|module_id| module_id.uid == module)
+}
+
+/// The elements in the location and sub locations that use the module
+fn elements_using(location: &LocationWraper, module: UID) -> Vec<ElementWraper> {
+    let mut elements = location
+        .elements
+        .read()
+        .unwrap()
+        .iter()
+        .filter(|element| uses(&element.element.read().unwrap().module, module))
+        .cloned()
+        .collect::<Vec<_>>();
+    for location in location.locations.read().unwrap().iter() {
+        elements.append(&mut elements_using(location, module));
+    }
+    elements
+}
+
+/// The elements in the location and sub locations that are proxied to the module
+fn elements_proxied_to(location: &LocationWraper, module: UID) -> Vec<ElementWraper> {
+    let mut elements = location
+        .elements
+        .read()
+        .unwrap()
+        .iter()
+        .filter(|element| uses(&element.element.read().unwrap().proxy, module))
+        .cloned()
+        .collect::<Vec<_>>();
+    for location in location.locations.read().unwrap().iter() {
+        elements.append(&mut elements_proxied_to(location, module));
+    }
+    elements
+}
+
+/// The location and sub locations that use the module
+fn locations_using(location: &LocationWraper, module: UID) -> Vec<LocationWraper> {
+    let mut locations = Vec::new();
+    if uses(&location.location.read().unwrap().module, module) {
+        locations.push(location.clone());
+    }
+    for location in location.locations.read().unwrap().iter() {
+        locations.append(&mut locations_using(location, module));
+    }
+    locations
+}
+
+/// The ids have the session of the user, a module session would check the user like the module
+fn module_id(session: &dyn TLocalSession, module: &ModuleWraper) -> ModuleId {
+    ModuleId {
+        uid: module.uid,
+        session: Some(session.weak_host().weak_box().into()),
     }
 }
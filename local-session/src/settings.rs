@@ -0,0 +1,31 @@
+use muzzman_lib::prelude::*;
+
+use crate::{ElementWraper, LocationWraper, TLocalSession};
+
+/// The value from the element settings or from the first parent location that has it
+pub(crate) fn element_setting(
+    session: &dyn TLocalSession,
+    element: &ElementWraper,
+    name: &str,
+) -> Option<Atom> {
+    let (value, parent) = {
+        let element = element.element.read().unwrap();
+        let value = element.settings.get(name).map(|s| s.value.clone());
+        (value, element.parent.clone())
+    };
+    value.or_else(|| location_setting(session, &session.location(parent.uid).ok()?, name))
+}
+
+/// The value from the location settings or from the first parent location that has it
+pub(crate) fn location_setting(
+    session: &dyn TLocalSession,
+    location: &LocationWraper,
+    name: &str,
+) -> Option<Atom> {
+    let (value, parent) = {
+        let location = location.location.read().unwrap();
+        let value = location.settings.get(name).map(|s| s.value.clone());
+        (value, location.parent.clone())
+    };
+    value.or_else(|| location_setting(session, &session.location(parent?.uid).ok()?, name))
+}
@@ -0,0 +1,99 @@
+use std::path::Path;
+
+use muzzman_lib::prelude::*;
+
+use crate::{ElementWraper, LocationWraper, TLocalSession};
+
+/// Checks the free space and the quota of every parent location before the element starts
+pub(crate) fn check_start(
+    session: &dyn TLocalSession,
+    element: &ElementWraper,
+) -> SessionResult<()> {
+    check_free_space(element)?;
+
+    let (needed, mut parent) = {
+        let e = element.element.read().unwrap();
+        (needed(&e), Some(e.parent.clone()))
+    };
+
+    while let Some(location_id) = parent {
+        let location = session.location(location_id.uid)?;
+        let quota = location
+            .location
+            .read()
+            .unwrap()
+            .settings
+            .get(session_settings::QUOTA)
+            .and_then(|s| s.value.as_u64())
+            .unwrap_or(0);
+        if quota > 0 {
+            let used = total_download(&location) + needed;
+            if used > quota {
+                return Err(SessionError::QuotaExceeded(quota, used));
+            }
+        }
+        parent = location.location.read().unwrap().parent.clone();
+    }
+
+    Ok(())
+}
+
+/// Fails if the filesystem behind the element path has less free space than the element still needs
+pub(crate) fn check_free_space(element: &ElementWraper) -> SessionResult<()> {
+    let (needed, path) = {
+        let element = element.element.read().unwrap();
+        (needed(&element), element.path.clone())
+    };
+    if needed == 0 {
+        return Ok(());
+    }
+
+    let Some(dir) = path.ancestors().skip(1).find(|dir| exists(dir)) else {
+        return Ok(());
+    };
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let available = fs2::available_space(dir)?;
+    if available < needed {
+        return Err(SessionError::NotEnoughSpace(needed, available));
+    }
+    Ok(())
+}
+
+fn exists(dir: &Path) -> bool {
+    dir.as_os_str().is_empty() || dir.exists()
+}
+
+/// How many bytes the element still needs to write on the disk
+fn needed(element: &Element) -> u64 {
+    let Some(size) = element.data.get(element_data::SIZE).and_then(Atom::as_u64) else {
+        return 0;
+    };
+    let allocated = match &element.stream {
+        Stream::File(file, _, _) => file.metadata().map(|m| m.len()).unwrap_or(0),
+        Stream::Segmented(file, _) => file.file().metadata().map(|m| m.len()).unwrap_or(0),
+        _ => element.total_download as u64,
+    };
+    size.saturating_sub(allocated)
+}
+
+fn total_download(location: &LocationWraper) -> u64 {
+    let elements = location
+        .elements
+        .read()
+        .unwrap()
+        .iter()
+        .map(|e| e.element.read().unwrap().total_download as u64)
+        .sum::<u64>();
+    let locations = location
+        .locations
+        .read()
+        .unwrap()
+        .iter()
+        .map(total_download)
+        .sum::<u64>();
+    elements + locations
+}
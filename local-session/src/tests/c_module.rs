@@ -0,0 +1,169 @@
+use std::{
+    ffi::c_void,
+    task::{Context, Waker},
+};
+
+use muzzman_lib::{prelude::*, Storage};
+
+use crate::{module::RawModule, tests::library, LocalSession};
+
+static SUPPORTED_VERSIONS: [u64; 1] = [ABI_VERSION];
+static PROTOCOLS: [CSlice; 1] = [CSlice::new("ctest")];
+
+static VTABLE: CModuleVTable = CModuleVTable {
+    abi_version: C_ABI_VERSION,
+    name: CSlice::new("CTest"),
+    desc: CSlice::new("Writes the url in the element stream"),
+    id: u64::MAX - 1,
+    version: 1,
+    supported_versions: SUPPORTED_VERSIONS.as_ptr(),
+    supported_versions_len: SUPPORTED_VERSIONS.len(),
+    protocols: PROTOCOLS.as_ptr(),
+    protocols_len: PROTOCOLS.len(),
+    extensions: std::ptr::null(),
+    extensions_len: 0,
+    default_element_settings,
+    default_location_settings,
+    poll_element,
+    poll_location,
+    element_on_event: on_event,
+    location_on_event: on_event,
+};
+
+extern "C" fn default_element_settings(host: *const CHost, settings: *mut c_void) {
+    let host = unsafe { &*host };
+    let variants = [CAtom::B(true), CAtom::B(false)];
+    (host.add_setting)(
+        settings,
+        CSlice::new("Fail"),
+        CAtom::B(false),
+        variants.as_ptr(),
+        variants.len(),
+        CSlice::new("Returns an error"),
+    );
+}
+
+extern "C" fn default_location_settings(_host: *const CHost, _settings: *mut c_void) {}
+
+extern "C" fn poll_element(host: *const CHost, handle: *mut c_void) -> i32 {
+    let host = unsafe { &*host };
+    let mut fail = CAtom::B(false);
+    (host.setting)(handle, CSlice::new("Fail"), &mut fail);
+    if let CAtom::B(true) = fail {
+        (host.set_error)(handle, CSlice::new("Failed"));
+        return C_ERROR;
+    }
+
+    let url = unsafe { (host.url)(handle).to_string_lossy() };
+    if (host.write)(handle, CSlice::new(&url)) != C_OK {
+        return C_ERROR;
+    }
+    (host.set_progress)(handle, 1.0);
+    (host.set_completed)(handle);
+    C_OK
+}
+
+extern "C" fn poll_location(_host: *const CHost, _handle: *mut c_void) -> i32 {
+    C_OK
+}
+
+extern "C" fn on_event(_host: *const CHost, _handle: *mut c_void, _event: *const CEvent) -> i32 {
+    C_OK
+}
+
+#[test]
+fn main() {
+    let dir = std::env::temp_dir().join(format!("muzzman-c-module-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let local_session = LocalSession::new();
+    let c_module = unsafe { CModule::new(&VTABLE, None) }.unwrap();
+    let module = local_session
+        .add_module(ModuleSource::Box(Box::new(c_module)))
+        .unwrap();
+
+    let info = module.info().unwrap();
+    assert_eq!(info.name, "CTest");
+    assert_eq!(info.protocols, vec!["ctest".to_string()]);
+    assert!(module.get_element_settings().unwrap().get("Fail").is_some());
+
+    let default_location = local_session.get_default_location().unwrap();
+    default_location.set_path(dir.clone()).unwrap();
+    let mut location_settings = default_location.get_settings().unwrap();
+    location_settings
+        .get_mut(session_settings::PARTIAL_FILE)
+        .unwrap()
+        .value = true.into();
+    default_location.set_settings(location_settings).unwrap();
+
+    let element = default_location
+        .create_element_from_url("ctest://host/file.txt".into())
+        .unwrap();
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+    assert!(element.is_completed().unwrap());
+    assert_eq!(
+        std::fs::read_to_string(dir.join("file.txt")).unwrap(),
+        "ctest://host/file.txt"
+    );
+
+    let element = default_location
+        .create_element_from_url("ctest://host/fail.txt".into())
+        .unwrap();
+    let mut settings = element.get_settings().unwrap();
+    settings.add("Fail", Setting::new(true, vec![true, false], ""));
+    element.set_settings(settings).unwrap();
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+    assert!(element.is_error().unwrap());
+    assert!(element.get_status_str().unwrap().contains("Failed"));
+
+    // a host function that panics, like with a poisoned lock, fails the call without unwinding in the module
+    let element = default_location
+        .create_element_from_url("ctest://host/poisoned.txt".into())
+        .unwrap();
+    let poisoned = local_session.element(element.uid).unwrap().element;
+    let _ = std::thread::spawn({
+        let poisoned = poisoned.clone();
+        move || {
+            let _element = poisoned.write().unwrap();
+            panic!("Poisoned");
+        }
+    })
+    .join();
+    let c_module = unsafe { CModule::new(&VTABLE, None) }.unwrap();
+    let res = c_module.poll_element(
+        &mut Context::from_waker(Waker::noop()),
+        poisoned.clone(),
+        &mut Storage::default(),
+        &mut ModuleContext::default(),
+    );
+    assert!(matches!(
+        res,
+        Err(SessionError::Custom(message)) if message.starts_with("A host function panicked")
+    ));
+    poisoned.clear_poison();
+
+    // a library that exports muzzman_module, it supports no rust ABI_VERSION
+    let path = library("muzzman_module_c");
+    let raw = RawModule::new_module(&path).unwrap();
+    assert_eq!(raw.name(), "CModule");
+    assert!(raw.supported_versions().is_empty());
+    drop(raw);
+
+    local_session
+        .add_module(ModuleSource::Dynamic(path))
+        .unwrap();
+    let element = default_location
+        .create_element_from_url("cmodule://host/real.txt".into())
+        .unwrap();
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+    assert!(element.is_completed().unwrap());
+    assert_eq!(
+        std::fs::read_to_string(dir.join("real.txt")).unwrap(),
+        "cmodule://host/real.txt"
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,135 @@
+use muzzman_lib::prelude::*;
+
+use crate::{
+    tests::test_module::{HookModule, TestModule},
+    LocalSession,
+};
+
+const HELLO_WORLD_SHA256: &str = "a591a6d40bf420404a011733cfb7b190d62c65bf0bcda32b57b277d9ad9f146e";
+
+/// Writes the url to the element path without the element stream
+fn file_module() -> HookModule {
+    HookModule::new("File", u64::MAX - 6).poll_element(|element, _, _| {
+        let mut element = element.write().unwrap();
+        std::fs::write(&element.path, &element.url)?;
+        element.is_completed = true;
+        Ok(())
+    })
+}
+
+#[test]
+fn main() {
+    let dir = std::env::temp_dir().join(format!("muzzman-checksum-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let local_session = LocalSession::new();
+    let module = local_session
+        .add_module(ModuleSource::Box(Box::new(TestModule)))
+        .unwrap();
+    let default_location = local_session.get_default_location().unwrap();
+    default_location.set_path(dir.clone()).unwrap();
+
+    let mut location_settings = default_location.get_settings().unwrap();
+    location_settings
+        .get_mut(session_settings::PARTIAL_FILE)
+        .unwrap()
+        .value = true.into();
+    default_location.set_settings(location_settings).unwrap();
+
+    for (name, checksum) in [("good", HELLO_WORLD_SHA256), ("bad", "00")] {
+        let element = default_location.create_element(name.into()).unwrap();
+        let mut settings = Settings::default();
+        settings.add(
+            session_settings::CHECKSUM_ALGORITHM,
+            Setting::new(
+                "SHA-256",
+                session_settings::CHECKSUM_ALGORITHMS.to_vec(),
+                "",
+            ),
+        );
+        settings.add(
+            session_settings::CHECKSUM,
+            Setting::new(checksum, Vec::<String>::new(), ""),
+        );
+        element.set_settings(settings).unwrap();
+        element.set_module(Some(module.clone())).unwrap();
+        element.set_url("Hello World".into()).unwrap();
+        element.set_enabled(true).unwrap();
+        element.wait().unwrap();
+
+        let good = name == "good";
+        assert_eq!(element.is_completed().unwrap(), good);
+        assert_eq!(element.is_error().unwrap(), !good);
+        assert_eq!(dir.join(name).exists(), good);
+        assert_eq!(
+            element.get_data().unwrap().get(element_data::CHECKSUM),
+            Some(&Atom::S(HELLO_WORLD_SHA256.into()))
+        );
+    }
+
+    // without Session.PartialFile the module wrote the file, it is not kept if it does not match
+    let direct = default_location.create_location("Direct".into()).unwrap();
+    direct.set_path(dir.join("direct")).unwrap();
+    let mut location_settings = direct.get_settings().unwrap();
+    location_settings.add(
+        session_settings::PARTIAL_FILE,
+        Setting::new(false, Vec::<bool>::new(), ""),
+    );
+    direct.set_settings(location_settings).unwrap();
+    std::fs::create_dir_all(dir.join("direct")).unwrap();
+    let file_module = local_session
+        .add_module(ModuleSource::Box(Box::new(file_module())))
+        .unwrap();
+    let mut settings = direct.get_settings().unwrap();
+    settings.add(
+        session_settings::CHECKSUM_ALGORITHM,
+        Setting::new(
+            "SHA-256",
+            session_settings::CHECKSUM_ALGORITHMS.to_vec(),
+            "",
+        ),
+    );
+    direct.set_settings(settings).unwrap();
+    let direct_element = |name: &str, checksum: Option<&str>| {
+        let element = direct.create_element(name.into()).unwrap();
+        if let Some(checksum) = checksum {
+            let mut settings = Settings::default();
+            settings.add(
+                session_settings::CHECKSUM,
+                Setting::new(checksum, Vec::<String>::new(), ""),
+            );
+            element.set_settings(settings).unwrap();
+        }
+        element.set_module(Some(file_module.clone())).unwrap();
+        element.set_url("Hello World".into()).unwrap();
+        element.set_enabled(true).unwrap();
+        element.wait().unwrap();
+        element
+    };
+
+    let corrupt = direct_element("corrupt", Some("00"));
+    assert!(corrupt.is_error().unwrap());
+    assert!(!dir.join("direct").join("corrupt").exists());
+    assert!(dir.join("direct").join("corrupt.corrupt").exists());
+
+    // there is nothing to compare with, the digest is only stored
+    let missing = direct_element("missing", None);
+    assert!(missing.is_completed().unwrap());
+    assert!(dir.join("direct").join("missing").exists());
+    assert_eq!(
+        missing.get_data().unwrap().get(element_data::CHECKSUM),
+        Some(&Atom::S(HELLO_WORLD_SHA256.into()))
+    );
+
+    // the expected checksum can also be set on the location
+    let mut settings = direct.get_settings().unwrap();
+    settings.add(
+        session_settings::CHECKSUM,
+        Setting::new(HELLO_WORLD_SHA256, Vec::<String>::new(), ""),
+    );
+    direct.set_settings(settings).unwrap();
+    let inherited = direct_element("inherited", None);
+    assert!(inherited.is_completed().unwrap());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,117 @@
+use std::io::Write;
+
+use muzzman_lib::prelude::*;
+
+use crate::{
+    tests::test_module::{HookModule, TestModule},
+    LocalSession,
+};
+
+#[test]
+fn main() {
+    let dir = std::env::temp_dir().join(format!("muzzman-collision-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let local_session = LocalSession::new();
+    let module = local_session
+        .add_module(ModuleSource::Box(Box::new(TestModule)))
+        .unwrap();
+    let default_location = local_session.get_default_location().unwrap();
+    default_location.set_path(dir.clone()).unwrap();
+
+    let mut location_settings = default_location.get_settings().unwrap();
+    location_settings
+        .get_mut(session_settings::PARTIAL_FILE)
+        .unwrap()
+        .value = true.into();
+    default_location.set_settings(location_settings).unwrap();
+
+    let start = |url: &str, policy: &str| {
+        let element = default_location.create_element("file.txt".into()).unwrap();
+        let mut settings = Settings::default();
+        settings.add(
+            session_settings::ON_COLLISION,
+            Setting::new(policy, session_settings::ON_COLLISION_POLICIES.to_vec(), ""),
+        );
+        element.set_settings(settings).unwrap();
+        element.set_module(Some(module.clone())).unwrap();
+        element.set_url(url.into()).unwrap();
+        let res = element.set_enabled(true);
+        element.wait().unwrap();
+        (element, res)
+    };
+
+    let (first, _) = start("first", "Rename");
+    let (second, _) = start("second", "Rename");
+    assert_eq!(first.get_path().unwrap(), dir.join("file.txt"));
+    assert_eq!(second.get_path().unwrap(), dir.join("file (1).txt"));
+    assert_eq!(
+        std::fs::read_to_string(dir.join("file.txt")).unwrap(),
+        "first"
+    );
+
+    let (_, res) = start("fail", "Fail");
+    let Err(SessionError::ElementSetEnabled(error)) = res else {
+        panic!("Fail policy did not fail")
+    };
+    assert!(matches!(*error, SessionError::PathCollision(_)));
+
+    let (skipped, _) = start("skip", "Skip");
+    assert!(skipped.is_completed().unwrap());
+    assert_eq!(
+        std::fs::read_to_string(dir.join("file.txt")).unwrap(),
+        "first"
+    );
+
+    let (overwrite, _) = start("overwrite", "Overwrite");
+    assert!(overwrite.is_completed().unwrap());
+    assert_eq!(
+        std::fs::read_to_string(dir.join("file.txt")).unwrap(),
+        "overwrite"
+    );
+
+    // without partial files the element path is already used by its own file when it is resumed
+    let held = local_session
+        .add_module(ModuleSource::Box(Box::new(
+            HookModule::new("Held", u64::MAX - 14).poll_element(|element, _, _| {
+                let mut element = element.write().unwrap();
+                if element.data.contains_key("Done") {
+                    let data = element.url.clone().into_bytes();
+                    element.stream.write_all(&data)?;
+                    element.is_completed = true;
+                }
+                Ok(())
+            }),
+        )))
+        .unwrap();
+    let direct = default_location.create_location("direct".into()).unwrap();
+    direct.set_path(dir.join("direct")).unwrap();
+    let mut direct_settings = direct.get_settings().unwrap();
+    direct_settings.add(
+        session_settings::PARTIAL_FILE,
+        Setting::new(false, Vec::<bool>::new(), ""),
+    );
+    direct.set_settings(direct_settings).unwrap();
+    std::fs::create_dir_all(dir.join("direct")).unwrap();
+    let element = direct.create_element("held.txt".into()).unwrap();
+    element.set_module(Some(held)).unwrap();
+    element.set_url("held".into()).unwrap();
+    element.set_enabled(true).unwrap();
+    element.set_enabled(false).unwrap();
+    assert!(dir.join("direct/held.txt").exists());
+
+    element
+        .set_data([("Done".to_string(), Atom::B(true))].into())
+        .unwrap();
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+    assert!(element.is_completed().unwrap());
+    assert_eq!(element.get_path().unwrap(), dir.join("direct/held.txt"));
+    assert!(!dir.join("direct/held (1).txt").exists());
+    assert_eq!(
+        std::fs::read_to_string(dir.join("direct/held.txt")).unwrap(),
+        "held"
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
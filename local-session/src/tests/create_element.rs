@@ -12,5 +12,13 @@ fn main() {
 
     assert_eq!(new_element.get_name().unwrap(), "NewElement".to_string());
     assert_eq!(new_element.get_parent().unwrap(), default_location);
-    assert_eq!(new_element.path().unwrap(), vec![0])
+    assert_eq!(new_element.path().unwrap(), vec![0]);
+
+    assert_eq!(local_session.get_element(vec![0]).unwrap(), new_element);
+    assert!(matches!(
+        local_session.get_element(vec![3, 0]),
+        Err(SessionError::GetElement(error)) if matches!(*error, SessionError::InvalidUID)
+    ));
+    // looking for an element does not create the locations of the path
+    assert_eq!(default_location.get_locations_len().unwrap(), 0);
 }
@@ -0,0 +1,28 @@
+use muzzman_lib::prelude::*;
+
+use crate::LocalSession;
+
+#[test]
+fn main() {
+    let local_session = LocalSession::new();
+    let default_location = local_session.get_default_location().unwrap();
+    let element = default_location.create_element("Channel".into()).unwrap();
+
+    element.set_buffer_size(4).unwrap();
+    assert_eq!(element.get_buffer_size().unwrap(), 4);
+
+    assert_eq!(element.write(b"abcdef").unwrap(), 4);
+    assert_eq!(element.write(b"ef").unwrap(), 0);
+    assert_eq!(element.remaining().unwrap(), 4);
+
+    assert_eq!(element.read(3).unwrap(), b"abc".to_vec());
+    assert_eq!(element.remaining().unwrap(), 1);
+
+    assert_eq!(element.write(b"ef").unwrap(), 2);
+    assert_eq!(element.read(10).unwrap(), b"def".to_vec());
+    assert_eq!(element.remaining().unwrap(), 0);
+
+    let events = element.events(true).unwrap();
+    assert_eq!(events.len(), 2);
+    assert!(element.events(false).unwrap().is_empty());
+}
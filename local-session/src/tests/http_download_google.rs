@@ -1,22 +1,81 @@
-use crate::LocalSession;
+use std::{
+    io::{Read, Write},
+    net::TcpListener,
+};
+
+use crate::{tests::library, LocalSession};
 use muzzman_lib::prelude::*;
 
+const BODY: &str = "<html>Google</html>";
+
+/// Answers one request like google.com would, so the test doesn't need the network
+fn serve() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0; 4096];
+        let _ = stream.read(&mut request).unwrap();
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{BODY}",
+            BODY.len()
+        )
+        .unwrap();
+    });
+    format!("http://{address}")
+}
+
 #[test]
 fn main() {
-    let mut local_session = LocalSession::new();
+    let http = library("muzzman_module_http");
+    let dir = std::env::temp_dir().join(format!("muzzman-http-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let local_session = LocalSession::new();
     let http = local_session
-        .add_module(ModuleSource::Dynamic(
-            "../target/debug/libmuzzman_module_http.so".into(),
-        ))
+        .add_module(ModuleSource::Dynamic(http))
         .unwrap();
     let default_location = local_session.get_default_location().unwrap();
+    default_location.set_path(dir.clone()).unwrap();
     let element = default_location.create_element("HTTP".into()).unwrap();
-    element.set_module(Some(http)).unwrap();
-    element.set_url("http://google.com".into()).unwrap();
+    element.set_module(Some(http.clone())).unwrap();
+    element.set_url(serve()).unwrap();
     element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+
+    assert!(
+        element.is_completed().unwrap(),
+        "{}",
+        element.get_status_str().unwrap()
+    );
+    assert_eq!(
+        element.get_data().unwrap().get(element_data::SIZE),
+        Some(&Atom::U(BODY.len() as u64))
+    );
     let path = element.get_path().unwrap();
-    println!("Path: {path:?}");
-    while !element.is_completed().unwrap() && !element.is_error().unwrap() {
-        println!("Waiting");
-    }
+    assert_eq!(path, dir.join("HTTP"));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), BODY);
+    // the body is also in the data channel
+    assert_eq!(element.read(BODY.len()).unwrap(), BODY.as_bytes());
+
+    // a method without variants is checked by the module
+    let element = default_location.create_element("Invalid".into()).unwrap();
+    element.set_module(Some(http)).unwrap();
+    let mut settings = element.get_settings().unwrap();
+    settings.add(
+        "Method",
+        Setting::new("NOT A METHOD", Vec::<String>::new(), ""),
+    );
+    element.set_settings(settings).unwrap();
+    element.set_url(serve()).unwrap();
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+    assert!(element.is_error().unwrap());
+    assert!(element
+        .get_status_str()
+        .unwrap()
+        .contains("InvalidSettings"));
+
+    std::fs::remove_dir_all(dir).unwrap();
 }
@@ -1,2 +1,68 @@
+mod c_module;
+mod checksum;
+mod collision;
 mod create_element;
+mod data_channel;
 mod http_download_google;
+mod module_abi;
+mod module_actions;
+mod module_context;
+mod module_destroy;
+mod module_discovery;
+mod module_lifecycle;
+mod module_permissions;
+mod module_proxy;
+mod module_registry;
+mod module_reload;
+mod module_select;
+mod module_settings;
+mod on_complete;
+mod partial_file;
+mod process_module;
+mod segmented_write;
+mod space_check;
+mod stream;
+mod test_module;
+mod wasm_module;
+
+use std::path::PathBuf;
+
+/// The path of a dynamic library built by the workspace, like `muzzman_module_http`
+/// Panics if it was not built, `cargo build --workspace` should run before the tests
+fn library(name: &str) -> PathBuf {
+    let target = std::env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("target"));
+    let profile = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    };
+    // the tests run in the directory of local-session, a relative target is from the workspace
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("..")
+        .join(target)
+        .join(profile)
+        .join(format!(
+            "{}{name}{}",
+            std::env::consts::DLL_PREFIX,
+            std::env::consts::DLL_SUFFIX
+        ));
+    assert!(
+        path.is_file(),
+        "{} is not built, run `cargo build --workspace` before the tests",
+        path.display()
+    );
+    path
+}
+
+/// A library that exists on every system and is not a module
+fn system_library() -> &'static str {
+    if cfg!(target_os = "windows") {
+        "kernel32.dll"
+    } else if cfg!(target_os = "macos") {
+        "/usr/lib/libSystem.B.dylib"
+    } else {
+        "libc.so.6"
+    }
+}
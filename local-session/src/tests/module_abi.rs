@@ -0,0 +1,36 @@
+use crate::{
+    read_manifest,
+    tests::{library, system_library},
+    LocalSession,
+};
+use muzzman_lib::prelude::*;
+
+#[test]
+fn main() {
+    let http_path = library("muzzman_module_http");
+    let local_session = LocalSession::new();
+
+    let http = local_session
+        .add_module(ModuleSource::Dynamic(http_path.clone()))
+        .unwrap();
+    assert_eq!(http.info().unwrap().supported_versions, vec![ABI_VERSION]);
+
+    let manifest = read_manifest(&http_path).unwrap();
+    assert_eq!(manifest.abi_version, ABI_VERSION);
+    assert_eq!(manifest.build_id, BUILD_ID.trim_end_matches('\0'));
+    assert_eq!(manifest.package_name, "muzzman-module-http");
+    assert_eq!(manifest.module_type, "ModuleHttp");
+
+    // A library that is not a module
+    let res = local_session.add_module(ModuleSource::Dynamic(system_library().into()));
+    assert!(matches!(
+        res,
+        Err(SessionError::RawModule(
+            RawLibraryError::DontHaveSymbolAbiVersion
+        ))
+    ));
+    assert!(matches!(
+        read_manifest(system_library().as_ref()),
+        Err(RawLibraryError::DontHaveSymbolManifest)
+    ));
+}
@@ -0,0 +1,87 @@
+use muzzman_lib::prelude::*;
+
+use crate::{tests::test_module::HookModule, LocalSession};
+
+/// Has the element action "Verify" that checks the element url
+fn action_module() -> HookModule {
+    HookModule::new("Action", u64::MAX - 3)
+        .element_actions(vec![Action::new(
+            "Verify",
+            "Checks that the url is the expected one",
+            vec![("Expected".into(), "".into())],
+        )])
+        .element_action(|element, name, args, storage, _| {
+            if name != "Verify" {
+                return Err(SessionError::NoAction(name.to_string()));
+            }
+            // how many times the action was run on this element
+            if storage.get::<u64>(0).is_none() {
+                storage.push(0u64);
+            }
+            let runs = storage.get_mut::<u64>(0).unwrap();
+            *runs += 1;
+
+            let mut element = element.write().unwrap();
+            if args[0].as_str() == Some("panic") {
+                panic!("Action panicked");
+            }
+            let verified = Some(element.url.as_str()) == args[0].as_str();
+            element.data.insert("Verified".into(), verified.into());
+            element.data.insert("Runs".into(), (*runs).into());
+            Ok(())
+        })
+}
+
+#[test]
+fn main() {
+    let local_session = LocalSession::new();
+    let module = local_session
+        .add_module(ModuleSource::Box(Box::new(action_module())))
+        .unwrap();
+    let default_location = local_session.get_default_location().unwrap();
+    let element = default_location.create_element("Action".into()).unwrap();
+    assert!(element.get_actions().unwrap().is_empty());
+    assert!(matches!(
+        element.run_action("Verify".into(), vec!["".into()]),
+        Err(SessionError::ElementRunAction(error)) if matches!(*error, SessionError::NoModule)
+    ));
+
+    element.set_module(Some(module)).unwrap();
+    element.set_url("action://file".into()).unwrap();
+    let actions = element.get_actions().unwrap();
+    assert_eq!(actions.len(), 1);
+    assert_eq!(actions[0].name, "Verify");
+
+    assert!(matches!(
+        element.run_action("Refresh".into(), Vec::new()),
+        Err(SessionError::ElementRunAction(error)) if matches!(*error, SessionError::NoAction(_))
+    ));
+    assert!(matches!(
+        element.run_action("Verify".into(), vec![1u64.into()]),
+        Err(SessionError::ElementRunAction(error)) if matches!(*error, SessionError::InvalidActionArgs(_))
+    ));
+
+    element
+        .run_action("Verify".into(), vec!["action://file".into()])
+        .unwrap();
+    let data = element.get_data().unwrap();
+    assert_eq!(data.get("Verified"), Some(&Atom::B(true)));
+
+    element
+        .run_action("Verify".into(), vec!["action://other".into()])
+        .unwrap();
+    let data = element.get_data().unwrap();
+    assert_eq!(data.get("Verified"), Some(&Atom::B(false)));
+    assert_eq!(data.get("Runs"), Some(&Atom::U(2)));
+
+    // a panic is an error like in the runner, the element and the storage can still be used
+    assert!(matches!(
+        element.run_action("Verify".into(), vec!["panic".into()]),
+        Err(SessionError::ElementRunAction(error))
+            if matches!(&*error, SessionError::Custom(message) if message == "Action panicked")
+    ));
+    element
+        .run_action("Verify".into(), vec!["action://file".into()])
+        .unwrap();
+    assert_eq!(element.get_data().unwrap().get("Runs"), Some(&Atom::U(4)));
+}
@@ -0,0 +1,150 @@
+use muzzman_lib::prelude::*;
+
+use crate::{tests::test_module::HookModule, LocalSession};
+
+/// Expands "playlist://a,b" in the elements "child://a" and "child://b" next to it
+/// and a location "Extras" with the element "Cover", the children complete in one poll
+fn playlist_module() -> HookModule {
+    HookModule::new("Playlist", u64::MAX - 9)
+        .protocols(&["playlist", "child"])
+        .element_setting("Quality", Setting::new("High", vec!["High", "Low"], ""))
+        .permissions(Permissions {
+            create_elements: true,
+            ..Default::default()
+        })
+        .poll_element(|element, _, context| {
+            let (id, url) = {
+                let mut element = element.write().unwrap();
+                element.is_completed = true;
+                (element.id.clone(), element.url.clone())
+            };
+            let Some(items) = url.strip_prefix("playlist://") else {
+                return Ok(());
+            };
+            // the element is not locked anymore so the session can be used
+            let parent = id.get_parent()?;
+            for (index, item) in items.split(',').enumerate() {
+                context.create_element(
+                    &parent,
+                    NewElement {
+                        name: item.to_string(),
+                        url: format!("child://{item}"),
+                        settings: [("Quality".to_string(), "Low".into())].into(),
+                        data: [("Index".to_string(), (index as u64).into())].into(),
+                        enabled: true,
+                        ..Default::default()
+                    },
+                );
+            }
+            context.create_location(
+                &parent,
+                NewLocation {
+                    name: "Extras".into(),
+                    elements: vec![NewElement {
+                        name: "Cover".into(),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            );
+            context.set_setting(id.uid, "Quality", "Low");
+            context.set_data(id.uid, "Items", items.split(',').count() as u64);
+            context.emit(id.uid, Event::Custom("Expanded".into()));
+            Ok(())
+        })
+        .element_actions(vec![
+            Action::new("Discard", "Fails after adding an element", Vec::new()),
+            Action::new("Broken", "Enables a location", Vec::new()),
+            Action::new("Redirect", "Changes the url and detaches", Vec::new()),
+        ])
+        .element_action(|element, name, _, _, context| {
+            let id = element.read().unwrap().id.clone();
+            let parent = id.get_parent()?;
+            match name {
+                "Discard" => {
+                    context.create_element(&parent, NewElement::default());
+                    Err(SessionError::Custom("Discarded".into()))
+                }
+                "Broken" => {
+                    let location = ElementId {
+                        uid: parent.uid,
+                        session: None,
+                    };
+                    context.set_enabled(&location, true);
+                    Ok(())
+                }
+                "Redirect" => {
+                    context.set_url(&id, "child://c");
+                    context.set_module(&id, None);
+                    Ok(())
+                }
+                _ => Err(SessionError::NoAction(name.to_string())),
+            }
+        })
+}
+
+#[test]
+fn main() {
+    let local_session = LocalSession::new();
+    local_session
+        .add_module(ModuleSource::Box(Box::new(playlist_module())))
+        .unwrap();
+    let default_location = local_session.get_default_location().unwrap();
+    let location = default_location.create_location("Playlist".into()).unwrap();
+    let observer = location.create_element("Observer".into()).unwrap();
+
+    let playlist = location
+        .create_element_from_url("playlist://a,b".into())
+        .unwrap();
+    observer.subscribe(playlist.uid).unwrap();
+    playlist.set_enabled(true).unwrap();
+    playlist.wait().unwrap();
+
+    assert!(playlist.is_completed().unwrap());
+    let settings = playlist.get_settings().unwrap();
+    assert_eq!(settings.get("Quality").unwrap().value, Atom::from("Low"));
+    assert_eq!(playlist.get_data().unwrap().get("Items"), Some(&Atom::U(2)));
+    assert!(observer.events(true).unwrap().iter().any(|event| matches!(
+        event,
+        Event::From(uid, event) if *uid == playlist.uid && matches!(**event, Event::Custom(ref name) if name == "Expanded")
+    )));
+
+    // Observer, playlist, a, b
+    assert_eq!(location.get_elements_len().unwrap(), 4);
+    for index in 0..2 {
+        let child = location
+            .get_elements(index + 2, index + 2)
+            .unwrap()
+            .remove(0);
+        child.wait().unwrap();
+        assert!(child.is_completed().unwrap());
+        assert!(child.get_module().unwrap().is_some());
+        assert_eq!(
+            child.get_url().unwrap(),
+            format!("child://{}", ["a", "b"][index])
+        );
+        let settings = child.get_settings().unwrap();
+        assert_eq!(settings.get("Quality").unwrap().value, Atom::from("Low"));
+        let data = child.get_data().unwrap();
+        assert_eq!(data.get("Index"), Some(&Atom::U(index as u64)));
+    }
+
+    let extras = location.get_locations(0, 0).unwrap().remove(0);
+    assert_eq!(extras.get_name().unwrap(), "Extras");
+    let cover = extras.get_elements(0, 0).unwrap().remove(0);
+    assert_eq!(cover.get_name().unwrap(), "Cover");
+    assert!(cover.get_module().unwrap().is_none());
+
+    // the operations of a failed callback are not done
+    assert!(playlist.run_action("Discard".into(), Vec::new()).is_err());
+    assert_eq!(location.get_elements_len().unwrap(), 4);
+
+    assert!(matches!(
+        playlist.run_action("Broken".into(), Vec::new()),
+        Err(SessionError::ElementRunAction(error)) if matches!(*error, SessionError::Errors(_))
+    ));
+
+    playlist.run_action("Redirect".into(), Vec::new()).unwrap();
+    assert_eq!(playlist.get_url().unwrap(), "child://c");
+    assert!(playlist.get_module().unwrap().is_none());
+}
@@ -0,0 +1,60 @@
+use muzzman_lib::prelude::*;
+
+use crate::{
+    tests::{library, test_module::TestModule},
+    LocalSession,
+};
+
+#[test]
+fn main() {
+    let http_path = library("muzzman_module_http");
+    let local_session = LocalSession::new();
+    let module = local_session
+        .add_module(ModuleSource::Box(Box::new(TestModule)))
+        .unwrap();
+    let http = local_session
+        .add_module(ModuleSource::Dynamic(http_path))
+        .unwrap();
+    assert_eq!(http.path().unwrap(), 1);
+
+    let default_location = local_session.get_default_location().unwrap();
+    default_location.set_module(Some(module.clone())).unwrap();
+    let element = default_location
+        .create_element_from_url("test://host/file.txt".into())
+        .unwrap();
+    assert_eq!(element.get_module().unwrap(), Some(module.clone()));
+
+    // what the module stored can be from its library, it is cleared with the module
+    let element_wraper = local_session.element(element.uid).unwrap();
+    element_wraper.storage.write().unwrap().push(1u64);
+    let location_wraper = local_session.location(default_location.uid).unwrap();
+    location_wraper.storage.write().unwrap().push(1u64);
+
+    module.clone().destroy().unwrap();
+    assert_eq!(element.get_module().unwrap(), None);
+    assert_eq!(default_location.get_module().unwrap(), None);
+    assert!(element_wraper
+        .storage
+        .read()
+        .unwrap()
+        .get::<u64>(0)
+        .is_none());
+    assert!(location_wraper
+        .storage
+        .read()
+        .unwrap()
+        .get::<u64>(0)
+        .is_none());
+    assert!(matches!(
+        module.info(),
+        Err(SessionError::ModuleInfo(error)) if matches!(*error, SessionError::UIDWasDestroyed)
+    ));
+    assert!(matches!(
+        module.clone().destroy(),
+        Err(SessionError::DestroyModule(_))
+    ));
+
+    assert_eq!(local_session.get_modules().unwrap(), vec![http.clone()]);
+    assert_eq!(http.path().unwrap(), 0);
+    assert_eq!(http.info().unwrap().name, "HTTP");
+}
@@ -0,0 +1,47 @@
+use muzzman_lib::prelude::*;
+
+use crate::{tests::library, LocalSession};
+
+#[test]
+fn main() {
+    let http = library("muzzman_module_http");
+    let fail = library("muzzman_module_fail");
+    let dir = std::env::temp_dir().join(format!("muzzman-discovery-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = |name: &str| dir.join(format!("{name}.{}", std::env::consts::DLL_EXTENSION));
+    std::fs::copy(&fail, file("a")).unwrap();
+    std::fs::copy(&http, file("b")).unwrap();
+    std::fs::copy(&http, file("c")).unwrap();
+    std::fs::write(file("d"), "not a library").unwrap();
+    std::fs::write(dir.join("readme.txt"), "not a module").unwrap();
+
+    let local_session = LocalSession::new();
+    let reports = local_session
+        .load_modules(vec![dir.join("missing"), dir.clone()])
+        .unwrap();
+    assert_eq!(reports.len(), 4);
+
+    // on_load fails, the modules after it are still loaded
+    assert_eq!(reports[0].path, file("a"));
+    assert!(matches!(
+        &reports[0].result,
+        Err(RawLibraryError::AddModule(error)) if error.contains("Cannot be loaded")
+    ));
+
+    assert_eq!(reports[1].path, file("b"));
+    let info = reports[1].result.clone().unwrap();
+    assert_eq!(info.name, "HTTP");
+
+    assert_eq!(reports[2].path, file("c"));
+    assert!(matches!(
+        reports[2].result,
+        Err(RawLibraryError::Duplicate(id)) if id == info.id
+    ));
+
+    assert_eq!(reports[3].path, file("d"));
+    assert!(matches!(reports[3].result, Err(RawLibraryError::NotFound)));
+
+    assert_eq!(local_session.get_modules_info().unwrap(), vec![info]);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,173 @@
+use std::sync::{Arc, Mutex};
+
+use muzzman_lib::prelude::*;
+
+use crate::{tests::test_module::HookModule, LocalSession};
+
+type Log = Arc<Mutex<Vec<String>>>;
+
+/// Saves the lifecycle hooks that were called, the elements named "Reject" cannot use it
+fn lifecycle_module(id: u64, log: &Log) -> HookModule {
+    let logger = || {
+        let log = log.clone();
+        move |entry: String| log.lock().unwrap().push(entry)
+    };
+    let (load, unload, attached, detached, destroyed) =
+        (logger(), logger(), logger(), logger(), logger());
+    let (location_attached, location_detached, location_destroyed) = (logger(), logger(), logger());
+    HookModule::new("Lifecycle", id)
+        .poll_element(|element, storage, _| {
+            let initialized = storage.get::<&str>(0).copied();
+            let mut element = element.write().unwrap();
+            element
+                .data
+                .insert("Initialized".into(), initialized.unwrap_or_default().into());
+            element.is_completed = true;
+            Ok(())
+        })
+        .on_load(move |session| {
+            if id == u64::MAX - 5 {
+                return Err(SessionError::Custom("Cannot load".into()));
+            }
+            // the module can use the session before anything else
+            session.get_default_location()?;
+            load("Load".into());
+            Ok(())
+        })
+        .on_unload(move || unload("Unload".into()))
+        .on_element_attached(move |element, storage| {
+            let name = element.read().unwrap().name.clone();
+            if name == "Reject" {
+                return Err(SessionError::Custom("Rejected".into()));
+            }
+            if name == "Panic" {
+                let _element = element.write().unwrap();
+                panic!("Attach panicked");
+            }
+            storage.push("Yes");
+            attached(format!("Attached {name}"));
+            Ok(())
+        })
+        .on_element_detached(move |element, _| {
+            detached(format!("Detached {}", element.read().unwrap().name))
+        })
+        .on_element_destroyed(move |element, _| {
+            destroyed(format!("Destroyed {}", element.read().unwrap().name))
+        })
+        .on_location_attached(move |location, _| {
+            location_attached(format!("Attached {}", location.read().unwrap().name));
+            Ok(())
+        })
+        .on_location_detached(move |location, _| {
+            location_detached(format!("Detached {}", location.read().unwrap().name))
+        })
+        .on_location_destroyed(move |location, _| {
+            location_destroyed(format!("Destroyed {}", location.read().unwrap().name))
+        })
+}
+
+fn take(log: &Mutex<Vec<String>>) -> Vec<String> {
+    std::mem::take(&mut *log.lock().unwrap())
+}
+
+#[test]
+fn main() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let local_session = LocalSession::new();
+
+    assert!(local_session
+        .add_module(ModuleSource::Box(Box::new(lifecycle_module(
+            u64::MAX - 5,
+            &log
+        ))))
+        .is_err());
+    assert!(local_session.get_modules().unwrap().is_empty());
+
+    let module = local_session
+        .add_module(ModuleSource::Box(Box::new(lifecycle_module(
+            u64::MAX - 4,
+            &log,
+        ))))
+        .unwrap();
+    assert_eq!(take(&log), ["Load"]);
+
+    let default_location = local_session.get_default_location().unwrap();
+    let element = default_location.create_element("A".into()).unwrap();
+    element.set_module(Some(module.clone())).unwrap();
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+    assert_eq!(
+        element.get_data().unwrap().get("Initialized"),
+        Some(&Atom::from("Yes"))
+    );
+    element.set_module(None).unwrap();
+    assert_eq!(take(&log), ["Attached A", "Detached A"]);
+
+    let rejected = default_location.create_element("Reject".into()).unwrap();
+    assert!(rejected.set_module(Some(module.clone())).is_err());
+    assert!(rejected.get_module().unwrap().is_none());
+
+    // the elements after a destroyed element are moved back
+    element.set_module(Some(module.clone())).unwrap();
+    let last = default_location.create_element("C".into()).unwrap();
+    assert_eq!(last.path().unwrap(), vec![2]);
+    rejected.destroy().unwrap();
+    assert_eq!(last.path().unwrap(), vec![1]);
+    assert_eq!(last.get_name().unwrap(), "C");
+    assert_eq!(default_location.get_elements_len().unwrap(), 2);
+
+    let sub = default_location.create_location("Sub".into()).unwrap();
+    let inner = sub.create_location("Inner".into()).unwrap();
+    let nested = inner.create_element("Nested".into()).unwrap();
+    let after = default_location.create_location("After".into()).unwrap();
+    let after_element = after.create_element("AfterElement".into()).unwrap();
+    sub.set_module(Some(module.clone())).unwrap();
+    nested.set_module(Some(module.clone())).unwrap();
+    assert_eq!(after.path().unwrap(), vec![1]);
+    take(&log);
+
+    sub.clone().destroy().unwrap();
+    assert_eq!(take(&log), ["Destroyed Nested", "Destroyed Sub"]);
+    assert!(matches!(
+        nested.get_name(),
+        Err(SessionError::GetName(error)) if matches!(*error, SessionError::UIDWasDestroyed)
+    ));
+    assert!(inner.get_name().is_err());
+    assert!(sub.get_name().is_err());
+    assert_eq!(after.path().unwrap(), vec![0]);
+    assert_eq!(after_element.path().unwrap(), vec![0, 0]);
+    assert_eq!(after_element.get_name().unwrap(), "AfterElement");
+    assert!(default_location.clone().destroy().is_err());
+
+    // a panic is an error like in the runner, the element is left without the module
+    let panicked = default_location.create_element("Panic".into()).unwrap();
+    assert!(matches!(
+        panicked.set_module(Some(module.clone())),
+        Err(SessionError::ElementSetModule(error))
+            if matches!(&*error, SessionError::Custom(message) if message == "Attach panicked")
+    ));
+    assert!(panicked.get_module().unwrap().is_none());
+    panicked.destroy().unwrap();
+
+    // the module cannot change while the element is polled by it
+    let busy = local_session
+        .add_module(ModuleSource::Box(Box::new(
+            HookModule::new("Busy", u64::MAX - 15).poll_element(|_, _, _| Ok(())),
+        )))
+        .unwrap();
+    let running = default_location.create_element("Running".into()).unwrap();
+    running.set_module(Some(busy.clone())).unwrap();
+    running.set_enabled(true).unwrap();
+    assert!(matches!(
+        running.set_module(Some(module.clone())),
+        Err(SessionError::ElementSetModule(error))
+            if matches!(*error, SessionError::ElementIsEnabled)
+    ));
+    assert_eq!(running.get_module().unwrap(), Some(busy));
+    running.set_enabled(false).unwrap();
+    running.set_module(None).unwrap();
+
+    local_session.destroy_module(module).unwrap();
+    assert_eq!(take(&log), ["Detached A", "Unload"]);
+    assert!(element.get_module().unwrap().is_none());
+}
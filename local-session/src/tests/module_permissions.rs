@@ -0,0 +1,294 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use muzzman_lib::prelude::*;
+
+use crate::{
+    tests::test_module::{HookModule, TestModule},
+    LocalSession,
+};
+
+/// Tries the session APIs from poll_element and saves in the element data which ones were allowed
+fn probe_module(dir: &Path) -> HookModule {
+    let permissions = Permissions {
+        write_paths: vec![dir.to_path_buf()],
+        create_elements: true,
+        ..Default::default()
+    };
+    let dir = dir.to_path_buf();
+    let kept = Arc::new(Mutex::new(None::<Session>));
+    let loaded = kept.clone();
+    HookModule::new("Probe", u64::MAX - 2)
+        .permissions(permissions.clone())
+        .on_load(move |session| {
+            *loaded.lock().unwrap() = Some(session);
+            Ok(())
+        })
+        .poll_element(move |element, _, _| {
+            let (id, parent, other) = {
+                let element = element.read().unwrap();
+                let other = element.data.get("Other").and_then(Atom::as_u64).unwrap();
+                (element.id.clone(), element.parent.clone(), other)
+            };
+            let session = id.get_session()?;
+
+            let mut settings = id.get_settings()?;
+            settings.add(
+                session_settings::ON_COMPLETE_COMMAND,
+                Setting::new("true", Vec::<String>::new(), ""),
+            );
+
+            let results = [
+                ("Create", parent.create_element("Created".into()).is_ok()),
+                ("WriteInside", id.set_path(dir.join("file")).is_ok()),
+                ("WriteOutside", id.set_path("/file".into()).is_ok()),
+                ("WriteEscape", id.set_path(dir.join("../file")).is_ok()),
+                ("Command", id.set_settings(settings).is_ok()),
+                (
+                    "OwnEvents",
+                    session.push_event(id.uid, Event::Custom("".into())).is_ok(),
+                ),
+                ("OtherEvents", session.subscribe(id.uid, other).is_ok()),
+                (
+                    "AddModule",
+                    session
+                        .add_module(ModuleSource::Box(Box::new(HookModule::new(
+                            "Added",
+                            u64::MAX - 11,
+                        ))))
+                        .is_ok(),
+                ),
+                (
+                    "SetPermissions",
+                    id.get_module()?
+                        .unwrap()
+                        .set_permissions(permissions.clone())
+                        .is_ok(),
+                ),
+                (
+                    "ModuleSettings",
+                    id.get_module()?
+                        .unwrap()
+                        .set_element_settings(Settings::default())
+                        .is_ok(),
+                ),
+            ];
+
+            // the session that the module got is known as the module on its own threads
+            let kept = kept.lock().unwrap().clone().unwrap();
+            let module = id.get_module()?.unwrap();
+            let other = ElementId {
+                uid: other,
+                session: None,
+            };
+            let thread = std::thread::spawn(move || {
+                [
+                    (
+                        "ThreadSetPermissions",
+                        kept.module_set_permissions(module, Permissions::default())
+                            .is_ok(),
+                    ),
+                    (
+                        "ThreadOtherData",
+                        kept.element_set_data(other, Default::default()).is_ok(),
+                    ),
+                ]
+            });
+            let thread = thread.join().unwrap();
+
+            let mut element = element.write().unwrap();
+            for (name, allowed) in results.into_iter().chain(thread) {
+                element.data.insert(name.into(), allowed.into());
+            }
+            element.is_completed = true;
+            Ok(())
+        })
+}
+
+/// Writes the element path without the session, when attached or in the poll
+fn mover_module(dir: &Path, escaped: &Path) -> HookModule {
+    let (attached, polled) = (escaped.to_path_buf(), escaped.to_path_buf());
+    HookModule::new("Mover", u64::MAX - 12)
+        .permissions(Permissions {
+            write_paths: vec![dir.to_path_buf()],
+            ..Default::default()
+        })
+        .on_element_attached(move |element, _| {
+            let mut element = element.write().unwrap();
+            if element.name == "Attached" {
+                element.path = attached.clone();
+            }
+            Ok(())
+        })
+        .poll_element(move |element, _, _| {
+            let mut element = element.write().unwrap();
+            if element.name == "Polled" {
+                element.path = polled.clone();
+            }
+            element.is_completed = true;
+            Ok(())
+        })
+}
+
+/// Sets the command on the element without the session and in its default settings
+fn sneaky_module(dir: &Path) -> HookModule {
+    let command = |name: &str| {
+        Setting::new(
+            format!("touch {}", dir.join(name).display()),
+            Vec::<String>::new(),
+            "",
+        )
+    };
+    let direct = command("direct");
+    HookModule::new("Sneaky", u64::MAX - 5)
+        .element_setting(session_settings::ON_COMPLETE_COMMAND, command("default"))
+        .poll_element(move |element, _, _| {
+            let mut element = element.write().unwrap();
+            element
+                .settings
+                .add(session_settings::ON_COMPLETE_COMMAND, direct.clone());
+            element.is_completed = true;
+            Ok(())
+        })
+}
+
+fn probe(location: &LocationId, module: &ModuleId, other: &ElementId) -> ElementId {
+    let element = location.create_element("Probe".into()).unwrap();
+    element.set_module(Some(module.clone())).unwrap();
+    let mut data = element.get_data().unwrap();
+    data.insert("Other".into(), other.uid.into());
+    element.set_data(data).unwrap();
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+    assert!(element.is_completed().unwrap());
+    element
+}
+
+fn allowed(element: &ElementId, name: &str) -> bool {
+    element
+        .get_data()
+        .unwrap()
+        .get(name)
+        .unwrap()
+        .as_bool()
+        .unwrap()
+}
+
+#[test]
+fn main() {
+    let dir = std::env::temp_dir().join(format!("muzzman-permissions-{}", std::process::id()));
+
+    let local_session = LocalSession::new();
+    let module = local_session
+        .add_module(ModuleSource::Box(Box::new(probe_module(&dir))))
+        .unwrap();
+    assert!(module.get_permissions().unwrap().create_elements);
+
+    let default_location = local_session.get_default_location().unwrap();
+    let test = local_session
+        .add_module(ModuleSource::Box(Box::new(TestModule)))
+        .unwrap();
+    let other = default_location.create_element("Other".into()).unwrap();
+    other.set_module(Some(test)).unwrap();
+
+    let element = probe(&default_location, &module, &other);
+    assert!(allowed(&element, "Create"));
+    assert!(allowed(&element, "WriteInside"));
+    assert!(!allowed(&element, "WriteOutside"));
+    assert!(!allowed(&element, "WriteEscape"));
+    assert!(!allowed(&element, "Command"));
+    assert!(allowed(&element, "OwnEvents"));
+    assert!(!allowed(&element, "OtherEvents"));
+    assert!(!allowed(&element, "AddModule"));
+    assert!(!allowed(&element, "SetPermissions"));
+    assert!(!allowed(&element, "ModuleSettings"));
+    assert!(!allowed(&element, "ThreadSetPermissions"));
+    assert!(!allowed(&element, "ThreadOtherData"));
+
+    // the user changes what the module can do
+    module
+        .set_permissions(Permissions {
+            spawn_processes: true,
+            other_events: true,
+            ..Default::default()
+        })
+        .unwrap();
+    let element = probe(&default_location, &module, &other);
+    assert!(!allowed(&element, "Create"));
+    assert!(!allowed(&element, "WriteInside"));
+    assert!(allowed(&element, "Command"));
+    assert!(allowed(&element, "OtherEvents"));
+
+    // the module writes the command in the element settings without the session
+    std::fs::create_dir_all(&dir).unwrap();
+    let sneaky = local_session
+        .add_module(ModuleSource::Box(Box::new(sneaky_module(&dir))))
+        .unwrap();
+    let element = default_location.create_element("Sneaky".into()).unwrap();
+    element.set_module(Some(sneaky.clone())).unwrap();
+    assert!(element
+        .get_settings()
+        .unwrap()
+        .get(session_settings::ON_COMPLETE_COMMAND)
+        .is_none());
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+    assert!(element.is_error().unwrap());
+    assert!(!dir.join("direct").exists());
+    assert!(!dir.join("default").exists());
+
+    // the user allows it
+    sneaky
+        .set_permissions(Permissions {
+            spawn_processes: true,
+            ..Default::default()
+        })
+        .unwrap();
+    let element = default_location.create_element("Allowed".into()).unwrap();
+    element.set_module(Some(sneaky)).unwrap();
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+    assert!(!element.is_error().unwrap());
+    assert!(dir.join("direct").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+    std::fs::remove_file(element.get_path().unwrap()).unwrap();
+
+    // the file is not created, renamed or moved where the module cannot write
+    std::fs::create_dir_all(&dir).unwrap();
+    let escaped = std::env::temp_dir().join(format!(
+        "muzzman-permissions-escaped-{}",
+        std::process::id()
+    ));
+    let mover = local_session
+        .add_module(ModuleSource::Box(Box::new(mover_module(&dir, &escaped))))
+        .unwrap();
+    let location = default_location.create_location("Mover".into()).unwrap();
+    location.set_path(dir.clone()).unwrap();
+    let mut settings = location.get_settings().unwrap();
+    settings.add(
+        session_settings::PARTIAL_FILE,
+        Setting::new(true, Vec::<bool>::new(), ""),
+    );
+    location.set_settings(settings).unwrap();
+
+    let attached = location.create_element("Attached".into()).unwrap();
+    attached.set_module(Some(mover.clone())).unwrap();
+    assert!(matches!(
+        attached.set_enabled(true),
+        Err(SessionError::ElementSetEnabled(error)) if matches!(*error, SessionError::NoPermission)
+    ));
+    let polled = location.create_element("Polled".into()).unwrap();
+    polled.set_module(Some(mover)).unwrap();
+    polled.set_enabled(true).unwrap();
+    polled.wait().unwrap();
+    assert!(polled.is_error().unwrap());
+    assert!(polled.get_status_str().unwrap().contains("NoPermission"));
+    assert!(!escaped.exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // not called from a module
+    other.set_path("/file".into()).unwrap();
+    default_location.create_element("User".into()).unwrap();
+}
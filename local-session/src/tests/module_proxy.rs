@@ -0,0 +1,134 @@
+use muzzman_lib::prelude::*;
+
+use crate::{tests::test_module::HookModule, LocalSession};
+
+/// Resolves "page://name" to "media://name" and gives the transfer to the Transfer module
+fn page_module() -> HookModule {
+    HookModule::new("Page", u64::MAX - 7)
+        .protocols(&["page"])
+        .element_setting("Quality", Setting::new("High", vec!["High", "Low"], ""))
+        .poll_element(|element, _, context| {
+            let id = {
+                let mut element = element.write().unwrap();
+                element.url = element.url.replace("page://", "media://");
+                // the status is of this module, the proxy starts from its first status
+                element.statuses = vec!["Resolving".into(), "Resolved".into(), "Proxied".into()];
+                element.status = 2;
+                element.progress = 0.5;
+                let resolved = element.data.get("Resolved").and_then(Atom::as_u64);
+                element
+                    .data
+                    .insert("Resolved".into(), (resolved.unwrap_or(0) + 1).into());
+                element.id.clone()
+            };
+            let session = id.get_session()?;
+            for module in session.get_modules()? {
+                if module.get_name()? == "Transfer" {
+                    context.set_proxy(&id, Some(&module));
+                }
+            }
+            Ok(())
+        })
+}
+
+/// Transfers "media://" urls in two polls like the http module, the urls with "fail" fail
+fn transfer_module() -> HookModule {
+    HookModule::new("Transfer", u64::MAX - 8)
+        .protocols(&["media"])
+        .element_setting("Chunk", Setting::new(1024u64, Vec::<u64>::new(), ""))
+        .poll_element(|element, _, _| {
+            let mut element = element.write().unwrap();
+            if element.url.contains("fail") {
+                return Err(SessionError::Custom("Transfer failed".into()));
+            }
+            match element.status {
+                0 => {
+                    assert_eq!(element.progress, 0.0);
+                    element.statuses = vec!["Transferring".into(), "Done".into()];
+                    element.status = 1;
+                    element.progress = 0.5;
+                }
+                1 => {
+                    element.progress = 1.0;
+                    element.data.insert("Transferred".into(), true.into());
+                    element.is_completed = true;
+                }
+                status => return Err(SessionError::Custom(format!("Invalid status {status}"))),
+            }
+            Ok(())
+        })
+}
+
+fn page(location: &LocationId, module: &ModuleId, url: &str) -> ElementId {
+    let element = location.create_element("Page".into()).unwrap();
+    element.set_module(Some(module.clone())).unwrap();
+    element.set_url(url.into()).unwrap();
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+    element
+}
+
+#[test]
+fn main() {
+    let local_session = LocalSession::new();
+    let front = local_session
+        .add_module(ModuleSource::Box(Box::new(page_module())))
+        .unwrap();
+    let back = local_session
+        .add_module(ModuleSource::Box(Box::new(transfer_module())))
+        .unwrap();
+    let default_location = local_session.get_default_location().unwrap();
+
+    let element = page(&default_location, &front, "page://video");
+    assert!(element.is_completed().unwrap());
+    assert_eq!(element.get_url().unwrap(), "media://video");
+    assert_eq!(element.get_proxy().unwrap(), Some(back.clone()));
+    assert_eq!(element.get_module().unwrap(), Some(front.clone()));
+    // the status and progress of the back module are the ones of the element
+    assert_eq!(element.get_status_str().unwrap(), "Done");
+    assert_eq!(element.get_progress().unwrap(), 1.0);
+    let data = element.get_data().unwrap();
+    assert_eq!(data.get("Resolved"), Some(&Atom::U(1)));
+    assert_eq!(data.get("Transferred"), Some(&Atom::B(true)));
+    let settings = element.get_settings().unwrap();
+    assert!(settings.get("Quality").is_some());
+    assert!(settings.get("Chunk").is_some());
+
+    // the error of the back module is the error of the element and the proxy ends
+    let failed = page(&default_location, &front, "page://fail");
+    assert!(failed.is_error().unwrap());
+    assert!(failed.get_status_str().unwrap().contains("Transfer failed"));
+    assert_eq!(failed.get_proxy().unwrap(), None);
+
+    assert!(matches!(
+        element.set_proxy(Some(front.clone())),
+        Err(SessionError::ElementSetProxy(error)) if matches!(*error, SessionError::ProxyToItself)
+    ));
+    let without_module = default_location.create_element("None".into()).unwrap();
+    assert!(matches!(
+        without_module.set_proxy(Some(back.clone())),
+        Err(SessionError::ElementSetProxy(error)) if matches!(*error, SessionError::NoModule)
+    ));
+
+    // the statuses of the proxy are cleared when it ends
+    let proxied = default_location.create_element("Ended".into()).unwrap();
+    proxied.set_module(Some(front.clone())).unwrap();
+    proxied.set_proxy(Some(back.clone())).unwrap();
+    proxied
+        .set_statuses(vec!["Transferring".into(), "Done".into()])
+        .unwrap();
+    proxied.set_status(1).unwrap();
+    proxied.set_proxy(None).unwrap();
+    assert!(proxied.get_statuses().unwrap().is_empty());
+    assert_eq!(proxied.get_status().unwrap(), 0);
+
+    // changing the front module ends the proxy
+    element.set_module(None).unwrap();
+    assert_eq!(element.get_proxy().unwrap(), None);
+
+    let element = default_location.create_element("Proxied".into()).unwrap();
+    element.set_module(Some(front)).unwrap();
+    element.set_proxy(Some(back.clone())).unwrap();
+    local_session.destroy_module(back).unwrap();
+    assert_eq!(element.get_proxy().unwrap(), None);
+}
@@ -0,0 +1,41 @@
+use muzzman_lib::prelude::*;
+
+use crate::{tests::test_module::TestModule, LocalSession};
+
+#[test]
+fn main() {
+    let local_session = LocalSession::new();
+    let module = local_session
+        .add_module(ModuleSource::Box(Box::new(TestModule)))
+        .unwrap();
+
+    let modules = local_session.get_modules().unwrap();
+    assert_eq!(modules, vec![module.clone()]);
+    assert_eq!(local_session.get_module(0).unwrap(), module);
+    assert_eq!(module.path().unwrap(), 0);
+    assert_eq!(module.id().unwrap(), u64::MAX);
+
+    let info = module.info().unwrap();
+    assert_eq!(info.uid, module.uid);
+    assert_eq!(info.name, "Test");
+    assert_eq!(info.id, u64::MAX);
+    assert_eq!(info.version, 1);
+    assert_eq!(info.supported_versions, vec![1]);
+    assert_eq!(info.protocols, vec!["test".to_string()]);
+    assert!(info.extensions.is_empty());
+    assert_eq!(local_session.get_modules_info().unwrap(), vec![info]);
+
+    assert_eq!(local_session.find_module_by_id(u64::MAX).unwrap(), module);
+    assert_eq!(
+        local_session.find_module_by_name("Test".into()).unwrap(),
+        module
+    );
+    assert!(matches!(
+        local_session.find_module_by_id(0),
+        Err(SessionError::FindModuleById(_))
+    ));
+    assert!(matches!(
+        local_session.get_module(1),
+        Err(SessionError::GetModule(_))
+    ));
+}
@@ -0,0 +1,43 @@
+use muzzman_lib::prelude::*;
+
+use crate::{
+    tests::{library, test_module::TestModule},
+    LocalSession,
+};
+
+#[test]
+fn main() {
+    let http_path = library("muzzman_module_http");
+    let local_session = LocalSession::new();
+    let http = local_session
+        .add_module(ModuleSource::Dynamic(http_path.clone()))
+        .unwrap();
+
+    let res = local_session.add_module(ModuleSource::Dynamic(http_path.clone()));
+    let Err(SessionError::ModuleAlreadyAdded(uid)) = res else {
+        panic!("The module was added two times")
+    };
+    assert_eq!(uid, http.uid);
+    assert_eq!(local_session.get_modules().unwrap().len(), 1);
+
+    let mut settings = http.get_element_settings().unwrap();
+    settings.get_mut("Method").unwrap().value = "POST".into();
+    settings.get_mut("Port").unwrap().value = 8080.into();
+    http.set_element_settings(settings).unwrap();
+
+    http.reload(ModuleSource::Dynamic(http_path.clone()))
+        .unwrap();
+    let settings = http.get_element_settings().unwrap();
+    assert_eq!(settings.get("Method").unwrap().value, "POST".into());
+    assert_eq!(settings.get("Port").unwrap().value, 8080.into());
+    assert_eq!(local_session.get_modules().unwrap(), vec![http.clone()]);
+
+    let res = http.reload(ModuleSource::Box(Box::new(TestModule)));
+    let Err(SessionError::ModuleReload(error)) = res else {
+        panic!("Reloaded with a module with other id")
+    };
+    assert!(matches!(
+        *error,
+        SessionError::ModuleIdMismatch(_, u64::MAX)
+    ));
+}
@@ -0,0 +1,106 @@
+use muzzman_lib::prelude::*;
+
+use crate::{
+    tests::test_module::{HookModule, TestModule},
+    LocalSession,
+};
+
+/// Supports the "multi" scheme, its elements get the setting "Module" with the id
+fn multi_module(id: u64, version: u64, extensions: &'static [&'static str]) -> HookModule {
+    HookModule::new("Multi", id)
+        .version(version)
+        .protocols(&["multi"])
+        .extensions(extensions)
+        .element_setting("Module", Setting::new(id, Vec::<u64>::new(), ""))
+}
+
+/// Sets the url on a new element and returns the id from the settings of the selected module
+fn selected(location: &LocationId, url: &str) -> u64 {
+    let element = location.create_element("multi".into()).unwrap();
+    element.set_url(url.into()).unwrap();
+    let module = element.get_module().unwrap().unwrap();
+    let id = element
+        .get_settings()
+        .unwrap()
+        .get("Module")
+        .and_then(|setting| setting.value.as_u64())
+        .unwrap();
+    assert_eq!(module.info().unwrap().id, id);
+    id
+}
+
+#[test]
+fn main() {
+    let local_session = LocalSession::new();
+    let module = local_session
+        .add_module(ModuleSource::Box(Box::new(TestModule)))
+        .unwrap();
+    let default_location = local_session.get_default_location().unwrap();
+
+    let element = default_location
+        .create_element_from_url("test://host/dir/file.txt?query".into())
+        .unwrap();
+    assert_eq!(element.get_name().unwrap(), "file.txt");
+    assert_eq!(element.get_module().unwrap().unwrap().uid, module.uid);
+    assert_eq!(element.get_url().unwrap(), "test://host/dir/file.txt?query");
+
+    let element = default_location
+        .create_element_from_url("test://host".into())
+        .unwrap();
+    assert_eq!(element.get_name().unwrap(), "host");
+
+    // the name cannot leave the location directory
+    for url in [
+        "test://host/dir/..",
+        "test://host/.",
+        "test://host/dir/..\\..\\file",
+        "test://../",
+    ] {
+        let element = default_location
+            .create_element_from_url(url.into())
+            .unwrap();
+        assert_eq!(element.get_name().unwrap(), "Element");
+    }
+
+    let res = default_location.create_element_from_url("unknown://host/file.txt".into());
+    let Err(SessionError::CreateElementFromUrl(error)) = res else {
+        panic!("Created an element without a module")
+    };
+    assert!(matches!(*error, SessionError::NoModule));
+
+    let element = default_location.create_element("manual".into()).unwrap();
+    element.set_url("test://host/manual".into()).unwrap();
+    assert_eq!(element.get_module().unwrap().unwrap().uid, module.uid);
+
+    for module in [
+        multi_module(10, 1, &["zip"]),
+        multi_module(11, 2, &[]),
+        multi_module(12, 2, &[]),
+    ] {
+        local_session
+            .add_module(ModuleSource::Box(Box::new(module)))
+            .unwrap();
+    }
+
+    // the extension is more important than the version
+    assert_eq!(selected(&default_location, "multi://host/file.ZIP"), 10);
+    // the bigger version, then the first added
+    assert_eq!(selected(&default_location, "multi://host/file.txt"), 11);
+
+    // the preferred module of the location is more important than the extension
+    let preferred = default_location
+        .create_location("Preferred".into())
+        .unwrap();
+    let mut settings = preferred.get_settings().unwrap();
+    settings.add(
+        session_settings::PREFERRED_MODULE,
+        Setting::new(12u64, Vec::<u64>::new(), ""),
+    );
+    preferred.set_settings(settings).unwrap();
+    assert_eq!(selected(&preferred, "multi://host/file.zip"), 12);
+
+    // the preferred module is used only if it supports the scheme
+    let element = preferred.create_element("test".into()).unwrap();
+    element.set_url("test://host".into()).unwrap();
+    assert_eq!(element.get_module().unwrap().unwrap().uid, module.uid);
+}
@@ -0,0 +1,94 @@
+use muzzman_lib::prelude::*;
+
+use crate::{tests::test_module::HookModule, LocalSession};
+
+/// Has the element setting "Method" and the location setting "Threads"
+fn settings_module() -> HookModule {
+    HookModule::new("Settings", u64::MAX - 6)
+        .element_setting("Method", Setting::new("GET", vec!["GET", "POST"], ""))
+        .location_setting("Threads", Setting::new(4u64, Vec::<u64>::new(), ""))
+        .on_element_attached(|element, _| {
+            // the settings are merged before the module sees the element
+            element
+                .read()
+                .unwrap()
+                .settings
+                .get("Method")
+                .map(|_| ())
+                .ok_or(SessionError::InvalidSettings(vec!["Method".into()]))
+        })
+}
+
+fn method(element: &ElementId) -> Atom {
+    element
+        .get_settings()
+        .unwrap()
+        .get("Method")
+        .unwrap()
+        .value
+        .clone()
+}
+
+#[test]
+fn main() {
+    let local_session = LocalSession::new();
+    let module = local_session
+        .add_module(ModuleSource::Box(Box::new(settings_module())))
+        .unwrap();
+    let default_location = local_session.get_default_location().unwrap();
+
+    let element = default_location.create_element("Defaults".into()).unwrap();
+    let mut settings = element.get_settings().unwrap();
+    settings.add(
+        session_settings::CHECKSUM,
+        Setting::new("", Vec::<String>::new(), ""),
+    );
+    settings.add("Unknown", Setting::new(1u64, Vec::<u64>::new(), ""));
+    element.set_settings(settings).unwrap();
+    element.set_module(Some(module.clone())).unwrap();
+    let settings = element.get_settings().unwrap();
+    assert_eq!(method(&element), Atom::from("GET"));
+    assert!(settings.get(session_settings::CHECKSUM).is_some());
+    assert!(settings.get("Unknown").is_none());
+
+    // the values set by the user are kept if valid
+    let kept = default_location.create_element("Kept".into()).unwrap();
+    let mut settings = kept.get_settings().unwrap();
+    settings.add("Method", Setting::new("POST", Vec::<String>::new(), ""));
+    kept.set_settings(settings).unwrap();
+    kept.set_module(Some(module.clone())).unwrap();
+    assert_eq!(method(&kept), Atom::from("POST"));
+
+    let invalid = default_location.create_element("Invalid".into()).unwrap();
+    let mut settings = invalid.get_settings().unwrap();
+    settings.add("Method", Setting::new("PUT", Vec::<String>::new(), ""));
+    invalid.set_settings(settings).unwrap();
+    invalid.set_module(Some(module.clone())).unwrap();
+    assert_eq!(method(&invalid), Atom::from("GET"));
+
+    // the defaults are changed for the whole session
+    let mut settings = module.get_element_settings().unwrap();
+    settings.get_mut("Method").unwrap().value = "POST".into();
+    module.set_element_settings(settings.clone()).unwrap();
+    let element = default_location
+        .create_element("Customised".into())
+        .unwrap();
+    element.set_module(Some(module.clone())).unwrap();
+    assert_eq!(method(&element), Atom::from("POST"));
+
+    settings.get_mut("Method").unwrap().value = "PUT".into();
+    assert!(matches!(
+        module.set_element_settings(settings),
+        Err(SessionError::ModuleSetElementSettings(error)) if matches!(*error, SessionError::InvalidSettings(_))
+    ));
+
+    let location = default_location.create_location("Location".into()).unwrap();
+    location.set_module(Some(module.clone())).unwrap();
+    let settings = location.get_settings().unwrap();
+    assert_eq!(settings.get("Threads").unwrap().value, Atom::U(4));
+    // the session settings of the default location are kept
+    default_location.set_module(Some(module)).unwrap();
+    let settings = default_location.get_settings().unwrap();
+    assert!(settings.get("Threads").is_some());
+    assert!(settings.get(session_settings::ON_COLLISION).is_some());
+}
@@ -0,0 +1,125 @@
+use muzzman_lib::prelude::*;
+
+use crate::{tests::test_module::TestModule, LocalSession};
+
+#[test]
+fn main() {
+    let dir = std::env::temp_dir().join(format!("muzzman-on-complete-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("done")).unwrap();
+
+    let local_session = LocalSession::new();
+    let module = local_session
+        .add_module(ModuleSource::Box(Box::new(TestModule)))
+        .unwrap();
+    // the command runs only if the module of the element can spawn processes
+    module
+        .set_permissions(Permissions {
+            spawn_processes: true,
+            ..Default::default()
+        })
+        .unwrap();
+    let default_location = local_session.get_default_location().unwrap();
+    default_location.set_path(dir.clone()).unwrap();
+    let mut location_settings = default_location.get_settings().unwrap();
+    location_settings
+        .get_mut(session_settings::PARTIAL_FILE)
+        .unwrap()
+        .value = true.into();
+    default_location.set_settings(location_settings).unwrap();
+
+    let done = default_location.create_location("Done".into()).unwrap();
+    done.set_path(dir.join("done")).unwrap();
+    let downloads = default_location
+        .create_location("Downloads".into())
+        .unwrap();
+    downloads.set_path(dir.clone()).unwrap();
+
+    let element = downloads.create_element("file.txt".into()).unwrap();
+    let mut settings = Settings::default();
+    settings.add(
+        session_settings::ON_COMPLETE_MOVE,
+        Setting::new("0", Vec::<String>::new(), ""),
+    );
+    settings.add(
+        session_settings::ON_COMPLETE_COMMAND,
+        Setting::new(
+            format!("touch {}", dir.join("ran").display()),
+            Vec::<String>::new(),
+            "",
+        ),
+    );
+    settings.add(
+        session_settings::ON_COMPLETE_EVENT,
+        Setting::new("Ingest", Vec::<String>::new(), ""),
+    );
+    element.set_settings(settings).unwrap();
+    element.set_module(Some(module.clone())).unwrap();
+    element.set_url("Hello World".into()).unwrap();
+
+    let observer = default_location.create_element("Observer".into()).unwrap();
+    observer.subscribe(element.uid).unwrap();
+
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+
+    assert!(element.is_completed().unwrap());
+    assert!(!element.is_error().unwrap());
+    assert_eq!(
+        element.get_path().unwrap(),
+        dir.join("done").join("file.txt")
+    );
+    assert!(dir.join("done").join("file.txt").exists());
+    assert!(dir.join("ran").exists());
+    assert!(downloads.is_completed().unwrap());
+
+    let events = observer.events(true).unwrap();
+    assert!(events.iter().any(|event| matches!(
+        event,
+        Event::From(_, event) if matches!(&**event, Event::Custom(name) if name == "Ingest")
+    )));
+
+    // a missing location is not created
+    let locations = default_location.get_locations_len().unwrap();
+    let lost = completed(
+        &downloads,
+        &module,
+        "lost.txt",
+        session_settings::ON_COMPLETE_MOVE,
+        "7/3",
+    );
+    assert!(lost.is_error().unwrap());
+    assert_eq!(default_location.get_locations_len().unwrap(), locations);
+    assert!(dir.join("lost.txt").exists());
+
+    // quotes would be passed to the program as they are
+    let quoted = completed(
+        &downloads,
+        &module,
+        "quoted.txt",
+        session_settings::ON_COMPLETE_COMMAND,
+        &format!("touch '{}'", dir.join("quoted").display()),
+    );
+    assert!(quoted.is_error().unwrap());
+    assert!(!dir.join("quoted").exists());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+/// Runs an element with only the OnComplete `setting`
+fn completed(
+    location: &LocationId,
+    module: &ModuleId,
+    name: &str,
+    setting: &str,
+    value: &str,
+) -> ElementId {
+    let element = location.create_element(name.into()).unwrap();
+    let mut settings = Settings::default();
+    settings.add(setting, Setting::new(value, Vec::<String>::new(), ""));
+    element.set_settings(settings).unwrap();
+    element.set_module(Some(module.clone())).unwrap();
+    element.set_url("Hello World".into()).unwrap();
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+    element
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+use muzzman_lib::prelude::*;
+
+use crate::{
+    tests::test_module::{HookModule, TestModule},
+    LocalSession,
+};
+
+#[test]
+fn main() {
+    let dir = std::env::temp_dir().join(format!("muzzman-partial-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let local_session = LocalSession::new();
+    let module = local_session
+        .add_module(ModuleSource::Box(Box::new(TestModule)))
+        .unwrap();
+    let default_location = local_session.get_default_location().unwrap();
+    default_location.set_path(dir.clone()).unwrap();
+
+    let mut settings = default_location.get_settings().unwrap();
+    settings
+        .get_mut(session_settings::PARTIAL_FILE)
+        .unwrap()
+        .value = true.into();
+    default_location.set_settings(settings).unwrap();
+
+    let element = default_location.create_element("file.txt".into()).unwrap();
+    element.set_module(Some(module.clone())).unwrap();
+    element.set_url("Hello World".into()).unwrap();
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+
+    assert!(element.is_completed().unwrap());
+    assert!(!dir.join("file.txt.part").exists());
+    assert_eq!(
+        std::fs::read_to_string(dir.join("file.txt")).unwrap(),
+        "Hello World"
+    );
+    assert!(!element
+        .get_data()
+        .unwrap()
+        .contains_key(element_data::PARTIAL_LENGTH));
+
+    // the file is allocated with the expected size, the zeros after the data are cut
+    let element = default_location.create_element("big.txt".into()).unwrap();
+    element.set_module(Some(module)).unwrap();
+    element
+        .set_data(HashMap::from([(element_data::SIZE.into(), Atom::U(100))]))
+        .unwrap();
+    element.set_url("Hello".into()).unwrap();
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+
+    assert!(element.is_completed().unwrap());
+    assert_eq!(
+        std::fs::read_to_string(dir.join("big.txt")).unwrap(),
+        "Hello"
+    );
+
+    // written at offsets, the file is cut after the last written byte
+    let segments = local_session
+        .add_module(ModuleSource::Box(Box::new(
+            HookModule::new("Segments", u64::MAX - 13).poll_element(|element, _, _| {
+                let mut element = element.write().unwrap();
+                element.stream.write_at(5, b" World")?;
+                element.stream.write_at(0, b"Hello")?;
+                element.is_completed = true;
+                Ok(())
+            }),
+        )))
+        .unwrap();
+    let element = default_location
+        .create_element("segments.txt".into())
+        .unwrap();
+    element.set_module(Some(segments.clone())).unwrap();
+    element
+        .set_data(HashMap::from([(element_data::SIZE.into(), Atom::U(100))]))
+        .unwrap();
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+
+    assert!(element.is_completed().unwrap());
+    assert_eq!(
+        std::fs::read_to_string(dir.join("segments.txt")).unwrap(),
+        "Hello World"
+    );
+
+    // without partial files the element path is written directly
+    let mut settings = default_location.get_settings().unwrap();
+    settings
+        .get_mut(session_settings::PARTIAL_FILE)
+        .unwrap()
+        .value = false.into();
+    default_location.set_settings(settings).unwrap();
+
+    let element = default_location
+        .create_element("direct.txt".into())
+        .unwrap();
+    element.set_module(Some(segments)).unwrap();
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+
+    assert!(element.is_completed().unwrap());
+    assert!(!dir.join("direct.txt.part").exists());
+    assert_eq!(
+        std::fs::read_to_string(dir.join("direct.txt")).unwrap(),
+        "Hello World"
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,119 @@
+use muzzman_lib::prelude::*;
+
+use crate::{
+    tests::{library, system_library},
+    LocalSession,
+};
+
+/// The module hosts started by this process
+fn module_hosts() -> Vec<u32> {
+    let mut pids = Vec::new();
+    for entry in std::fs::read_dir("/proc").unwrap().flatten() {
+        let Ok(stat) = std::fs::read_to_string(entry.path().join("stat")) else {
+            continue;
+        };
+        // pid (comm) state ppid ...
+        let Some((comm, rest)) = stat.split_once(") ") else {
+            continue;
+        };
+        let ppid = rest.split(' ').nth(1).unwrap_or_default();
+        if comm.ends_with("(muzzman-module-") && ppid == std::process::id().to_string() {
+            pids.push(entry.file_name().to_string_lossy().parse().unwrap());
+        }
+    }
+    pids
+}
+
+fn completed_element(location: &LocationId, module: &ModuleId) -> ElementId {
+    let element = location.create_element("Process".into()).unwrap();
+    element.set_module(Some(module.clone())).unwrap();
+    assert!(element.get_settings().unwrap().get("Method").is_some());
+    // the http module only marks the element as completed
+    element.set_status(3).unwrap();
+    element
+}
+
+#[test]
+fn main() {
+    let http = library("muzzman_module_http");
+    // only this test starts module hosts
+    std::env::set_var(MODULE_HOST_TIMEOUT_ENV, "2");
+    let local_session = LocalSession::new();
+    let module = local_session
+        .add_module(ModuleSource::Process(http))
+        .unwrap();
+
+    let info = module.info().unwrap();
+    assert_eq!(info.name, "HTTP");
+    assert_eq!(info.id, 1);
+    assert_eq!(
+        info.protocols,
+        vec!["http".to_string(), "https".to_string()]
+    );
+    assert!(module
+        .get_element_settings()
+        .unwrap()
+        .get("Method")
+        .is_some());
+
+    let default_location = local_session.get_default_location().unwrap();
+
+    let element = completed_element(&default_location, &module);
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+    assert!(element.is_completed().unwrap());
+    assert_eq!(element.get_statuses().unwrap().len(), 4);
+
+    // attaching the element talks with the module host, so before killing it
+    let element = completed_element(&default_location, &module);
+    let hosts = module_hosts();
+    assert_eq!(hosts.len(), 1);
+    std::process::Command::new("kill")
+        .arg("-9")
+        .arg(hosts[0].to_string())
+        .status()
+        .unwrap();
+
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+    assert!(element.is_error().unwrap());
+    assert!(element
+        .get_status_str()
+        .unwrap()
+        .contains("Module host crashed"));
+
+    // the module host is started again
+    let element = completed_element(&default_location, &module);
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+    assert!(element.is_completed().unwrap());
+
+    // a module host that does not answer is killed and started again
+    let element = completed_element(&default_location, &module);
+    let stopped = module_hosts();
+    assert_eq!(stopped.len(), 1);
+    std::process::Command::new("kill")
+        .arg("-STOP")
+        .arg(stopped[0].to_string())
+        .status()
+        .unwrap();
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+    assert!(element.is_error().unwrap());
+    assert!(element
+        .get_status_str()
+        .unwrap()
+        .contains("Module host timed out"));
+    let hosts = module_hosts();
+    assert_eq!(hosts.len(), 1);
+    assert_ne!(hosts, stopped);
+
+    let element = completed_element(&default_location, &module);
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+    assert!(element.is_completed().unwrap());
+
+    assert!(local_session
+        .add_module(ModuleSource::Process(system_library().into()))
+        .is_err());
+}
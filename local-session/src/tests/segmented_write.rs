@@ -0,0 +1,46 @@
+use std::io::Read;
+
+use muzzman_lib::prelude::*;
+
+#[test]
+fn main() {
+    let path = std::env::temp_dir().join(format!("muzzman-segmented-{}", std::process::id()));
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .read(true)
+        .write(true)
+        .open(&path)
+        .unwrap();
+    file.set_len(12).unwrap();
+
+    let mut stream = Stream::Segmented(SegmentedFile::new(file), 0);
+    let segmented = stream.segmented().unwrap();
+
+    let threads = [(8, b"9abc"), (0, b"1234")]
+        .into_iter()
+        .map(|(offset, data)| {
+            let segmented = segmented.clone();
+            std::thread::spawn(move || segmented.write_at(offset, data).unwrap())
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let ranges = stream.written_ranges().unwrap();
+    assert_eq!(ranges.len(), 8);
+    assert_eq!(ranges.missing(12), vec![4..8]);
+    assert_eq!(ranges.progress(12), 8.0 / 12.0);
+
+    stream.write_at(4, b"5678").unwrap();
+    let ranges = stream.written_ranges().unwrap();
+    assert!(ranges.is_complete(12));
+    assert_eq!(ranges.iter().cloned().collect::<Vec<_>>(), vec![0..12]);
+
+    let mut content = String::new();
+    stream.read_to_string(&mut content).unwrap();
+    assert_eq!(content, "123456789abc");
+
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+
+use muzzman_lib::prelude::*;
+
+use crate::{tests::test_module::TestModule, LocalSession};
+
+#[test]
+fn main() {
+    let local_session = LocalSession::new();
+    let module = local_session
+        .add_module(ModuleSource::Box(Box::new(TestModule)))
+        .unwrap();
+    let default_location = local_session.get_default_location().unwrap();
+    default_location.set_path(std::env::temp_dir()).unwrap();
+    let location = default_location.create_location("Quota".into()).unwrap();
+
+    let mut settings = Settings::default();
+    settings.add(
+        session_settings::QUOTA,
+        Setting::new(10u64, Vec::<u64>::new(), ""),
+    );
+    location.set_settings(settings).unwrap();
+
+    let element = location.create_element("big".into()).unwrap();
+    element.set_module(Some(module)).unwrap();
+    element
+        .set_data(HashMap::from([(element_data::SIZE.into(), Atom::U(11))]))
+        .unwrap();
+    let Err(SessionError::ElementSetEnabled(error)) = element.set_enabled(true) else {
+        panic!("Quota was not enforced")
+    };
+    assert!(matches!(*error, SessionError::QuotaExceeded(10, 11)));
+
+    element
+        .set_data(HashMap::from([(
+            element_data::SIZE.into(),
+            Atom::U(u64::MAX),
+        )]))
+        .unwrap();
+    location.set_settings(Settings::default()).unwrap();
+    let Err(SessionError::ElementSetEnabled(error)) = element.set_enabled(true) else {
+        panic!("Free space was not checked")
+    };
+    assert!(matches!(*error, SessionError::NotEnoughSpace(u64::MAX, _)));
+    assert!(!element.get_enabled().unwrap());
+}
@@ -0,0 +1,48 @@
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+use muzzman_lib::prelude::*;
+
+use crate::LocalSession;
+
+#[test]
+fn main() {
+    let mut stream = Stream::Tee(vec![
+        Stream::Memory(Cursor::default()),
+        Stream::Memory(Cursor::default()),
+    ]);
+    stream.write_all(b"Hello World").unwrap();
+    stream.flush().unwrap();
+
+    let clone = stream.try_clone().unwrap();
+    assert_eq!(stream.seek(SeekFrom::Start(6)).unwrap(), 6);
+
+    let mut buffer = String::new();
+    stream.read_to_string(&mut buffer).unwrap();
+    assert_eq!(buffer, "World");
+
+    let Stream::Tee(streams) = clone else {
+        panic!("try_clone changed the Stream type")
+    };
+    let Stream::Memory(cursor) = &streams[1] else {
+        panic!("try_clone changed the Stream type")
+    };
+    assert_eq!(cursor.get_ref(), b"Hello World");
+
+    let mut null = Stream::Null(0);
+    null.write_all(b"Hello World").unwrap();
+    assert!(matches!(null, Stream::Null(11)));
+    assert!(null.seek(SeekFrom::Start(0)).is_err());
+
+    // an element is cloned without its stream
+    let local_session = LocalSession::new();
+    let element = local_session
+        .get_default_location()
+        .unwrap()
+        .create_element("Clone".into())
+        .unwrap();
+    let element = local_session.element(element.uid).unwrap().element;
+    element.write().unwrap().stream = Stream::Memory(Cursor::default());
+    let clone = element.read().unwrap().clone();
+    assert_eq!(clone.name, "Clone");
+    assert!(matches!(clone.stream, Stream::None));
+}
@@ -0,0 +1,457 @@
+use std::{
+    io::Write,
+    panic::UnwindSafe,
+    sync::{Arc, RwLock},
+};
+
+use muzzman_lib::{prelude::*, Storage};
+
+/// Writes the element url to the element stream and completes
+pub struct TestModule;
+
+impl TModule for TestModule {
+    fn name(&self) -> &str {
+        "Test"
+    }
+
+    fn desc(&self) -> &str {
+        "Writes the url in the element stream"
+    }
+
+    fn id(&self) -> u64 {
+        u64::MAX
+    }
+
+    fn version(&self) -> u64 {
+        1
+    }
+
+    fn supported_versions(&self) -> &'static [u64] {
+        &[1]
+    }
+
+    fn poll_element(
+        &self,
+        _ctx: &mut std::task::Context<'_>,
+        element: Arc<RwLock<Element>>,
+        _storage: &mut Storage,
+        _context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        let mut element = element.write().unwrap();
+        let data = element.url.clone().into_bytes();
+        element.stream.write_all(&data)?;
+        element.total_download += data.len();
+        element.progress = 1.0;
+        element.is_completed = true;
+        Ok(())
+    }
+
+    fn poll_location(
+        &self,
+        _ctx: &mut std::task::Context<'_>,
+        _location: Arc<RwLock<Location>>,
+        _storage: &mut Storage,
+        _context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        Ok(())
+    }
+
+    fn element_on_event(
+        &self,
+        _element: Arc<RwLock<Element>>,
+        _event: Event,
+        _storage: &mut Storage,
+        _context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        Ok(())
+    }
+
+    fn location_on_event(
+        &self,
+        _location: Arc<RwLock<Location>>,
+        _event: Event,
+        _storage: &mut Storage,
+        _context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        Ok(())
+    }
+
+    fn default_element_settings(&self) -> Settings {
+        Settings::default()
+    }
+
+    fn default_location_settings(&self) -> Settings {
+        Settings::default()
+    }
+
+    fn supports_protocols(&self) -> &[&'static str] {
+        &["test"]
+    }
+
+    fn supports_extensions(&self) -> &[&'static str] {
+        &[]
+    }
+}
+
+type Hook<F> = Option<Box<F>>;
+type ElementHook = dyn Fn(Arc<RwLock<Element>>, &mut Storage) + Send + Sync + UnwindSafe;
+type LocationHook = dyn Fn(Arc<RwLock<Location>>, &mut Storage) + Send + Sync + UnwindSafe;
+type PollElement = dyn Fn(Arc<RwLock<Element>>, &mut Storage, &mut ModuleContext) -> SessionResult<()>
+    + Send
+    + Sync
+    + UnwindSafe;
+type ElementAction = dyn Fn(Arc<RwLock<Element>>, &str, Vec<Atom>, &mut Storage, &mut ModuleContext) -> SessionResult<()>
+    + Send
+    + Sync
+    + UnwindSafe;
+type OnLoad = dyn Fn(Session) -> SessionResult<()> + Send + Sync + UnwindSafe;
+type OnUnload = dyn Fn() + Send + Sync + UnwindSafe;
+type ElementAttached =
+    dyn Fn(Arc<RwLock<Element>>, &mut Storage) -> SessionResult<()> + Send + Sync + UnwindSafe;
+type LocationAttached =
+    dyn Fn(Arc<RwLock<Location>>, &mut Storage) -> SessionResult<()> + Send + Sync + UnwindSafe;
+
+/// A module for the tests that does what its hooks do, the callbacks without a hook do nothing
+pub struct HookModule {
+    name: &'static str,
+    id: u64,
+    version: u64,
+    protocols: &'static [&'static str],
+    extensions: &'static [&'static str],
+    element_settings: Settings,
+    location_settings: Settings,
+    permissions: Permissions,
+    element_actions: Vec<Action>,
+    poll_element: Hook<PollElement>,
+    element_action: Hook<ElementAction>,
+    on_load: Hook<OnLoad>,
+    on_unload: Hook<OnUnload>,
+    on_element_attached: Hook<ElementAttached>,
+    on_element_detached: Hook<ElementHook>,
+    on_element_destroyed: Hook<ElementHook>,
+    on_location_attached: Hook<LocationAttached>,
+    on_location_detached: Hook<LocationHook>,
+    on_location_destroyed: Hook<LocationHook>,
+}
+
+impl HookModule {
+    pub fn new(name: &'static str, id: u64) -> Self {
+        Self {
+            name,
+            id,
+            version: 1,
+            protocols: &[],
+            extensions: &[],
+            element_settings: Settings::default(),
+            location_settings: Settings::default(),
+            permissions: Permissions::default(),
+            element_actions: Vec::new(),
+            poll_element: None,
+            element_action: None,
+            on_load: None,
+            on_unload: None,
+            on_element_attached: None,
+            on_element_detached: None,
+            on_element_destroyed: None,
+            on_location_attached: None,
+            on_location_detached: None,
+            on_location_destroyed: None,
+        }
+    }
+
+    pub fn version(mut self, version: u64) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn protocols(mut self, protocols: &'static [&'static str]) -> Self {
+        self.protocols = protocols;
+        self
+    }
+
+    pub fn extensions(mut self, extensions: &'static [&'static str]) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
+    pub fn element_setting(mut self, name: &str, setting: Setting) -> Self {
+        self.element_settings.add(name, setting);
+        self
+    }
+
+    pub fn location_setting(mut self, name: &str, setting: Setting) -> Self {
+        self.location_settings.add(name, setting);
+        self
+    }
+
+    pub fn permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    pub fn element_actions(mut self, actions: Vec<Action>) -> Self {
+        self.element_actions = actions;
+        self
+    }
+
+    pub fn poll_element(
+        mut self,
+        f: impl Fn(Arc<RwLock<Element>>, &mut Storage, &mut ModuleContext) -> SessionResult<()>
+            + Send
+            + Sync
+            + UnwindSafe
+            + 'static,
+    ) -> Self {
+        self.poll_element = Some(Box::new(f));
+        self
+    }
+
+    pub fn element_action(
+        mut self,
+        f: impl Fn(
+                Arc<RwLock<Element>>,
+                &str,
+                Vec<Atom>,
+                &mut Storage,
+                &mut ModuleContext,
+            ) -> SessionResult<()>
+            + Send
+            + Sync
+            + UnwindSafe
+            + 'static,
+    ) -> Self {
+        self.element_action = Some(Box::new(f));
+        self
+    }
+
+    pub fn on_load(
+        mut self,
+        f: impl Fn(Session) -> SessionResult<()> + Send + Sync + UnwindSafe + 'static,
+    ) -> Self {
+        self.on_load = Some(Box::new(f));
+        self
+    }
+
+    pub fn on_unload(mut self, f: impl Fn() + Send + Sync + UnwindSafe + 'static) -> Self {
+        self.on_unload = Some(Box::new(f));
+        self
+    }
+
+    pub fn on_element_attached(
+        mut self,
+        f: impl Fn(Arc<RwLock<Element>>, &mut Storage) -> SessionResult<()>
+            + Send
+            + Sync
+            + UnwindSafe
+            + 'static,
+    ) -> Self {
+        self.on_element_attached = Some(Box::new(f));
+        self
+    }
+
+    pub fn on_element_detached(
+        mut self,
+        f: impl Fn(Arc<RwLock<Element>>, &mut Storage) + Send + Sync + UnwindSafe + 'static,
+    ) -> Self {
+        self.on_element_detached = Some(Box::new(f));
+        self
+    }
+
+    pub fn on_element_destroyed(
+        mut self,
+        f: impl Fn(Arc<RwLock<Element>>, &mut Storage) + Send + Sync + UnwindSafe + 'static,
+    ) -> Self {
+        self.on_element_destroyed = Some(Box::new(f));
+        self
+    }
+
+    pub fn on_location_attached(
+        mut self,
+        f: impl Fn(Arc<RwLock<Location>>, &mut Storage) -> SessionResult<()>
+            + Send
+            + Sync
+            + UnwindSafe
+            + 'static,
+    ) -> Self {
+        self.on_location_attached = Some(Box::new(f));
+        self
+    }
+
+    pub fn on_location_detached(
+        mut self,
+        f: impl Fn(Arc<RwLock<Location>>, &mut Storage) + Send + Sync + UnwindSafe + 'static,
+    ) -> Self {
+        self.on_location_detached = Some(Box::new(f));
+        self
+    }
+
+    pub fn on_location_destroyed(
+        mut self,
+        f: impl Fn(Arc<RwLock<Location>>, &mut Storage) + Send + Sync + UnwindSafe + 'static,
+    ) -> Self {
+        self.on_location_destroyed = Some(Box::new(f));
+        self
+    }
+}
+
+impl TModule for HookModule {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn desc(&self) -> &str {
+        "A module for the tests"
+    }
+
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn supported_versions(&self) -> &'static [u64] {
+        &[1]
+    }
+
+    fn poll_element(
+        &self,
+        _ctx: &mut std::task::Context<'_>,
+        element: Arc<RwLock<Element>>,
+        storage: &mut Storage,
+        context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        match &self.poll_element {
+            Some(poll_element) => poll_element(element, storage, context),
+            None => Ok(()),
+        }
+    }
+
+    fn poll_location(
+        &self,
+        _ctx: &mut std::task::Context<'_>,
+        _location: Arc<RwLock<Location>>,
+        _storage: &mut Storage,
+        _context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        Ok(())
+    }
+
+    fn element_on_event(
+        &self,
+        _element: Arc<RwLock<Element>>,
+        _event: Event,
+        _storage: &mut Storage,
+        _context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        Ok(())
+    }
+
+    fn location_on_event(
+        &self,
+        _location: Arc<RwLock<Location>>,
+        _event: Event,
+        _storage: &mut Storage,
+        _context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        Ok(())
+    }
+
+    fn default_element_settings(&self) -> Settings {
+        self.element_settings.clone()
+    }
+
+    fn default_location_settings(&self) -> Settings {
+        self.location_settings.clone()
+    }
+
+    fn supports_protocols(&self) -> &[&'static str] {
+        self.protocols
+    }
+
+    fn supports_extensions(&self) -> &[&'static str] {
+        self.extensions
+    }
+
+    fn permissions(&self) -> Permissions {
+        self.permissions.clone()
+    }
+
+    fn element_actions(&self) -> Vec<Action> {
+        self.element_actions.clone()
+    }
+
+    fn element_action(
+        &self,
+        element: Arc<RwLock<Element>>,
+        name: &str,
+        args: Vec<Atom>,
+        storage: &mut Storage,
+        context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        match &self.element_action {
+            Some(element_action) => element_action(element, name, args, storage, context),
+            None => Err(SessionError::NoAction(name.to_string())),
+        }
+    }
+
+    fn on_load(&self, session: Session) -> SessionResult<()> {
+        match &self.on_load {
+            Some(on_load) => on_load(session),
+            None => Ok(()),
+        }
+    }
+
+    fn on_unload(&self) {
+        if let Some(on_unload) = &self.on_unload {
+            on_unload()
+        }
+    }
+
+    fn on_element_attached(
+        &self,
+        element: Arc<RwLock<Element>>,
+        storage: &mut Storage,
+    ) -> SessionResult<()> {
+        match &self.on_element_attached {
+            Some(on_element_attached) => on_element_attached(element, storage),
+            None => Ok(()),
+        }
+    }
+
+    fn on_element_detached(&self, element: Arc<RwLock<Element>>, storage: &mut Storage) {
+        if let Some(on_element_detached) = &self.on_element_detached {
+            on_element_detached(element, storage)
+        }
+    }
+
+    fn on_element_destroyed(&self, element: Arc<RwLock<Element>>, storage: &mut Storage) {
+        if let Some(on_element_destroyed) = &self.on_element_destroyed {
+            on_element_destroyed(element, storage)
+        }
+    }
+
+    fn on_location_attached(
+        &self,
+        location: Arc<RwLock<Location>>,
+        storage: &mut Storage,
+    ) -> SessionResult<()> {
+        match &self.on_location_attached {
+            Some(on_location_attached) => on_location_attached(location, storage),
+            None => Ok(()),
+        }
+    }
+
+    fn on_location_detached(&self, location: Arc<RwLock<Location>>, storage: &mut Storage) {
+        if let Some(on_location_detached) = &self.on_location_detached {
+            on_location_detached(location, storage)
+        }
+    }
+
+    fn on_location_destroyed(&self, location: Arc<RwLock<Location>>, storage: &mut Storage) {
+        if let Some(on_location_destroyed) = &self.on_location_destroyed {
+            on_location_destroyed(location, storage)
+        }
+    }
+}
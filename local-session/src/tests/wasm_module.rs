@@ -0,0 +1,163 @@
+use muzzman_lib::prelude::*;
+
+use crate::{wasm_module::WasmModule, LocalSession};
+
+const WAT: &str = r#"
+(module
+  (import "muzzman" "url" (func $url (param i32 i32) (result i32)))
+  (import "muzzman" "write" (func $write (param i32 i32) (result i32)))
+  (import "muzzman" "set_progress" (func $set_progress (param f32)))
+  (import "muzzman" "set_completed" (func $set_completed))
+  (import "muzzman" "add_setting" (func $add_setting (param i32 i32 i32 i32 i32 i32)))
+  (import "muzzman" "emit" (func $emit (param i32 i32 i32) (result i32)))
+  (import "muzzman" "set_status" (func $set_status (param i32 i32)))
+  (import "muzzman" "storage_set" (func $storage_set (param i32 i32 i32 i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "Wasm")
+  (data (i32.const 8) "Test wasm module")
+  (data (i32.const 32) "wasm")
+  (data (i32.const 40) "\01\00\00\00\00\00\00\00")
+  (data (i32.const 48) "Mode")
+  (data (i32.const 56) "\04fast")
+  (data (i32.const 64) "The mode")
+  (data (i32.const 72) "Done")
+  (func (export "name") (result i64) (i64.const 4))
+  (func (export "desc") (result i64) (i64.const 34359738384))
+  (func (export "id") (result i64) (i64.const 42))
+  (func (export "version") (result i64) (i64.const 1))
+  (func (export "supported_versions") (result i64) (i64.const 171798691841))
+  (func (export "protocols") (result i64) (i64.const 137438953476))
+  (func (export "extensions") (result i64) (i64.const 0))
+  (func (export "default_element_settings")
+    (call $add_setting (i32.const 48) (i32.const 4) (i32.const 56) (i32.const 5) (i32.const 64) (i32.const 8)))
+  (func (export "default_location_settings"))
+  (func (export "poll_element") (result i32)
+    (local $len i32)
+    (local $i i32)
+    (local.set $len (call $url (i32.const 1024) (i32.const 512)))
+    ;; "wasm://many" and "wasm://kv" add statuses or storage keys until the limit
+    (if (i32.eq (i32.load8_u (i32.const 1031)) (i32.const 109))
+      (then (loop $more
+        (i32.store8 (i32.const 2048) (i32.and (local.get $i) (i32.const 127)))
+        (i32.store8 (i32.const 2049) (i32.shr_u (local.get $i) (i32.const 7)))
+        (call $set_status (i32.const 2048) (i32.const 2))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $more))))
+    (if (i32.eq (i32.load8_u (i32.const 1031)) (i32.const 107))
+      (then (loop $more
+        (i32.store8 (i32.const 2048) (i32.and (local.get $i) (i32.const 127)))
+        (i32.store8 (i32.const 2049) (i32.shr_u (local.get $i) (i32.const 7)))
+        (call $storage_set (i32.const 2048) (i32.const 2) (i32.const 0) (i32.const 60000))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $more))))
+    ;; "wasm://loop" never ends
+    (if (i32.eq (i32.load8_u (i32.const 1031)) (i32.const 108))
+      (then (loop $forever (br $forever))))
+    ;; "wasm://big" writes 4 GiB
+    (if (i32.eq (i32.load8_u (i32.const 1031)) (i32.const 98))
+      (then (return (call $write (i32.const 0) (i32.const -1)))))
+    (if (i32.ne (call $write (i32.const 1024) (local.get $len)) (i32.const 0))
+      (then (return (i32.const -1))))
+    (call $set_progress (f32.const 1))
+    (call $set_completed)
+    (drop (call $emit (i32.const 4) (i32.const 72) (i32.const 4)))
+    (i32.const 0))
+  (func (export "poll_location") (result i32) (i32.const 0))
+  (func (export "element_on_event") (param i32 i64 i64 i32 i32) (result i32) (i32.const 0))
+  (func (export "location_on_event") (param i32 i64 i64 i32 i32) (result i32) (i32.const 0)))
+"#;
+
+#[test]
+fn main() {
+    let dir = std::env::temp_dir().join(format!("muzzman-wasm-module-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let local_session = LocalSession::new();
+    assert!(local_session
+        .add_module(ModuleSource::Wasm(b"not wasm".to_vec()))
+        .is_err());
+
+    let module = local_session
+        .add_module(ModuleSource::Wasm(WAT.as_bytes().to_vec()))
+        .unwrap();
+
+    let info = module.info().unwrap();
+    assert_eq!(info.name, "Wasm");
+    assert_eq!(info.desc, "Test wasm module");
+    assert_eq!(info.id, 42);
+    assert_eq!(info.protocols, vec!["wasm".to_string()]);
+    assert!(info.extensions.is_empty());
+    let settings = module.get_element_settings().unwrap();
+    assert_eq!(settings.get("Mode").unwrap().value, Atom::from("fast"));
+
+    let default_location = local_session.get_default_location().unwrap();
+    default_location.set_path(dir.clone()).unwrap();
+    let mut location_settings = default_location.get_settings().unwrap();
+    location_settings
+        .get_mut(session_settings::PARTIAL_FILE)
+        .unwrap()
+        .value = true.into();
+    default_location.set_settings(location_settings).unwrap();
+
+    let observer = default_location.create_element("Observer".into()).unwrap();
+    let element = default_location
+        .create_element_from_url("wasm://host/file.txt".into())
+        .unwrap();
+    observer.subscribe(element.uid).unwrap();
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+    assert!(element.is_completed().unwrap());
+    assert!(observer.events(true).unwrap().iter().any(|event| matches!(
+        event,
+        Event::From(uid, event)
+            if *uid == element.uid && matches!(&**event, Event::Custom(name) if name == "Done")
+    )));
+    assert_eq!(
+        std::fs::read_to_string(dir.join("file.txt")).unwrap(),
+        "wasm://host/file.txt"
+    );
+
+    // runs out of fuel
+    let element = default_location
+        .create_element_from_url("wasm://loop".into())
+        .unwrap();
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+    assert!(element.is_error().unwrap());
+
+    // the host does not allocate a buffer bigger than the module memory
+    let element = default_location
+        .create_element_from_url("wasm://big".into())
+        .unwrap();
+    element.set_enabled(true).unwrap();
+    element.wait().unwrap();
+    assert!(element.is_error().unwrap());
+
+    // the statuses and the storage that the module adds are limited
+    for (url, error) in [
+        ("wasm://many", "too many statuses"),
+        ("wasm://kv", "storage is too big"),
+    ] {
+        let element = default_location
+            .create_element_from_url(url.into())
+            .unwrap();
+        element.set_enabled(true).unwrap();
+        element.wait().unwrap();
+        assert!(element.is_error().unwrap());
+        assert!(element.get_status_str().unwrap().contains(error));
+    }
+
+    // loading the module again uses the same static values
+    let first = WasmModule::new(WAT.as_bytes()).unwrap();
+    let second = WasmModule::new(WAT.as_bytes()).unwrap();
+    assert!(std::ptr::eq(
+        first.supported_versions(),
+        second.supported_versions()
+    ));
+    assert!(std::ptr::eq(
+        first.supports_protocols()[0],
+        second.supports_protocols()[0]
+    ));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,879 @@
+//! Modules compiled to WebAssembly, they can only use the host functions from the "muzzman" import module
+//! and every call is limited by WASM_FUEL and WASM_MEMORY, so they can be untrusted
+//!
+//! A string or a list is returned by the module as an i64, `ptr << 32 | len`
+//!
+//! The module exports:
+//! `memory`,
+//! `name() -> i64`, `desc() -> i64`, `id() -> i64`, `version() -> i64`,
+//! `supported_versions() -> i64` a list of u64 that should contain WASM_ABI_VERSION,
+//! `protocols() -> i64` and `extensions() -> i64` separated by ",",
+//! `default_element_settings()` and `default_location_settings()` that call `add_setting`,
+//! `poll_element() -> i32` and `poll_location() -> i32` that return 0 or -1 for an error,
+//! a wasm module cannot be woken, it is polled again by the runner after its poll interval
+//! `element_on_event(kind: i32, uid: i64, from: i64, ptr: i32, len: i32) -> i32`
+//! and `location_on_event` with the same signature, `from` is -1 if the event is not from other element or location,
+//! `alloc(len: i32) -> i32` used for the event data, the module should free it
+//!
+//! Kind is 0 NewData, 1 ProgressChanged, 2 Completed, 3 Error, 4 Custom
+//! The module emits events from the element or location with `emit(kind: i32, ptr: i32, len: i32) -> i32`,
+//! the data is used by NewData and Custom, the event is emitted by the session after the call returns
+//!
+//! An atom is encoded as the kind byte: 0 B, 1 I, 2 U, 3 F, 4 S
+//! followed by one byte for B, 8 bytes little endian for I, U and F or the utf8 string for S
+//!
+//! The host functions that write in the module memory write at most `cap` bytes and return the full length
+//! The host copies at most WASM_MAX_COPY bytes from or to the module memory in a host function
+//! The settings, storage, data and statuses that the module adds are limited in total,
+//! the call traps when a limit is exceeded
+
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    sync::{Arc, Mutex, RwLock},
+};
+
+use muzzman_lib::{prelude::*, Storage};
+use wasmi::{
+    Caller, Config, Engine, Error, Extern, Instance, Linker, Memory, Module, Store, StoreLimits,
+    StoreLimitsBuilder, TypedFunc,
+};
+
+use crate::module::{intern_str, intern_versions};
+
+/// Changes every time the interface between the session and a wasm module changes,
+/// wasm modules use the host functions and not the rust types, so it is not the same as ABI_VERSION
+pub(crate) const WASM_ABI_VERSION: u64 = 1;
+/// How many instructions a module can run in a call
+const WASM_FUEL: u64 = 100_000_000;
+/// Max bytes of memory that a module can have
+const WASM_MEMORY: usize = 256 * 1024 * 1024;
+/// Max bytes of a buffer that the host allocates for a module
+const WASM_MAX_COPY: usize = 16 * 1024 * 1024;
+/// Max bytes of the settings and variants that a module adds for its defaults
+const WASM_MAX_SETTINGS: usize = 1024 * 1024;
+/// Max bytes of the keys and values in the storage of an element or location
+const WASM_MAX_STORAGE: usize = 64 * 1024 * 1024;
+/// Max bytes of the names and values in the data of an element or location
+const WASM_MAX_DATA: usize = 16 * 1024 * 1024;
+/// Max statuses of an element or location
+const WASM_MAX_STATUSES: usize = 1024;
+
+enum Target {
+    Element(Arc<RwLock<Element>>),
+    Location(Arc<RwLock<Location>>),
+}
+
+struct NewSetting {
+    name: String,
+    default: Atom,
+    variants: Vec<Atom>,
+    desc: String,
+}
+
+#[derive(Default)]
+struct HostState {
+    target: Option<Target>,
+    /// The element or location storage while a call is made
+    storage: Storage,
+    settings: Vec<NewSetting>,
+    /// Bytes of `settings`
+    settings_size: usize,
+    /// The ModuleContext of the callback while a call is made
+    context: ModuleContext,
+    error: Option<String>,
+    limits: StoreLimits,
+}
+
+/// Where the module storage_get and storage_set are stored in the element or location Storage
+#[derive(Default)]
+struct WasmStorage {
+    values: HashMap<String, Vec<u8>>,
+    /// Bytes of the keys and values
+    size: usize,
+}
+
+pub struct WasmModule {
+    name: String,
+    desc: String,
+    id: u64,
+    version: u64,
+    supported_versions: &'static [u64],
+    protocols: Vec<&'static str>,
+    extensions: Vec<&'static str>,
+    instance: Instance,
+    store: Mutex<Store<HostState>>,
+}
+
+impl WasmModule {
+    pub fn new(wasm: &[u8]) -> Result<Self, RawLibraryError> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module =
+            Module::new(&engine, wasm).map_err(|e| RawLibraryError::InvalidWasm(e.to_string()))?;
+
+        let mut store = Store::new(
+            &engine,
+            HostState {
+                limits: StoreLimitsBuilder::new().memory_size(WASM_MEMORY).build(),
+                ..Default::default()
+            },
+        );
+        store.limiter(|state| &mut state.limits);
+        store
+            .set_fuel(WASM_FUEL)
+            .map_err(|e| RawLibraryError::InvalidWasm(e.to_string()))?;
+
+        let linker = linker(&engine).map_err(|e| RawLibraryError::InvalidWasm(e.to_string()))?;
+        let instance = linker
+            .instantiate_and_start(&mut store, &module)
+            .map_err(|e| RawLibraryError::InvalidWasm(e.to_string()))?;
+
+        let Some(memory) = instance.get_memory(&store, "memory") else {
+            return Err(RawLibraryError::DontHaveSymbolMemory);
+        };
+
+        let call = |store: &mut Store<HostState>, name: &str, error: RawLibraryError| {
+            instance
+                .get_typed_func::<(), i64>(&*store, name)
+                .map_err(|_| error.clone())?
+                .call(&mut *store, ())
+                .map_err(|e| RawLibraryError::InvalidWasm(e.to_string()))
+        };
+        let read = |store: &Store<HostState>, (ptr, len): (usize, usize)| {
+            if len > WASM_MAX_COPY || ptr + len > memory.data_size(store) {
+                return Err(RawLibraryError::InvalidWasm(
+                    "The module returned a buffer outside of its memory".into(),
+                ));
+            }
+            let mut buf = vec![0; len];
+            memory
+                .read(store, ptr, &mut buf)
+                .map_err(|e| RawLibraryError::InvalidWasm(e.to_string()))?;
+            Ok::<_, RawLibraryError>(buf)
+        };
+        let list = |str: String| -> Vec<&'static str> {
+            str.split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(intern_str)
+                .collect()
+        };
+
+        let packed = call(&mut store, "name", RawLibraryError::DontHaveSymbolName)?;
+        let name = String::from_utf8_lossy(&read(&store, unpack(packed))?).to_string();
+        let packed = call(&mut store, "desc", RawLibraryError::DontHaveSymbolDesc)?;
+        let desc = String::from_utf8_lossy(&read(&store, unpack(packed))?).to_string();
+        let id = call(&mut store, "id", RawLibraryError::DontHaveSymbolId)? as u64;
+        let version = call(
+            &mut store,
+            "version",
+            RawLibraryError::DontHaveSymbolVersion,
+        )? as u64;
+
+        let packed = call(
+            &mut store,
+            "supported_versions",
+            RawLibraryError::DontHaveSymbolSupportedVersions,
+        )?;
+        let (ptr, len) = unpack(packed);
+        let bytes = read(&store, (ptr, len.saturating_mul(8)))?;
+        let supported_versions: Vec<u64> = bytes
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        if !supported_versions.contains(&WASM_ABI_VERSION) {
+            return Err(RawLibraryError::UnsupportedVersion(WASM_ABI_VERSION));
+        }
+
+        let packed = call(
+            &mut store,
+            "protocols",
+            RawLibraryError::DontHaveSymbolSupportsProtocols,
+        )?;
+        let protocols = list(String::from_utf8_lossy(&read(&store, unpack(packed))?).to_string());
+        let packed = call(
+            &mut store,
+            "extensions",
+            RawLibraryError::DontHaveSymbolSupportsExtensions,
+        )?;
+        let extensions = list(String::from_utf8_lossy(&read(&store, unpack(packed))?).to_string());
+
+        let required = [
+            (
+                "default_element_settings",
+                RawLibraryError::DontHaveSymbolDefaultElementSettings,
+            ),
+            (
+                "default_location_settings",
+                RawLibraryError::DontHaveSymbolDefaultLocationSettings,
+            ),
+            ("poll_element", RawLibraryError::DontHaveSymbolPollElement),
+            ("poll_location", RawLibraryError::DontHaveSymbolPollLocation),
+            (
+                "element_on_event",
+                RawLibraryError::DontHaveSymbolElementOnEvent,
+            ),
+            (
+                "location_on_event",
+                RawLibraryError::DontHaveSymbolLocationOnEvent,
+            ),
+        ];
+        for (name, error) in required {
+            if instance.get_func(&store, name).is_none() {
+                return Err(error);
+            }
+        }
+
+        Ok(Self {
+            name,
+            desc,
+            id,
+            version,
+            supported_versions: intern_versions(supported_versions),
+            protocols,
+            extensions,
+            instance,
+            store: Mutex::new(store),
+        })
+    }
+
+    /// Calls the module function with `target`, `storage` and `context` available to the host functions
+    fn call<Params: wasmi::WasmParams, Results: wasmi::WasmResults>(
+        &self,
+        target: Option<Target>,
+        storage: Option<&mut Storage>,
+        context: Option<&mut ModuleContext>,
+        name: &str,
+        params: Params,
+    ) -> SessionResult<Results> {
+        let mut store = self.store.lock().unwrap();
+        let _ = store.set_fuel(WASM_FUEL);
+        {
+            let state = store.data_mut();
+            state.target = target;
+            state.error = None;
+        }
+        let mut storage = storage;
+        if let Some(storage) = storage.as_deref_mut() {
+            std::mem::swap(storage, &mut store.data_mut().storage);
+        }
+        let mut context = context;
+        if let Some(context) = context.as_deref_mut() {
+            std::mem::swap(context, &mut store.data_mut().context);
+        }
+
+        let func: Result<TypedFunc<Params, Results>, Error> =
+            self.instance.get_typed_func(&*store, name);
+        let res = func.and_then(|func| func.call(&mut *store, params));
+
+        if let Some(storage) = storage {
+            std::mem::swap(storage, &mut store.data_mut().storage);
+        }
+        if let Some(context) = context {
+            std::mem::swap(context, &mut store.data_mut().context);
+        }
+        // the operations of a call without a context are dropped
+        store.data_mut().context.take();
+        let state = store.data_mut();
+        state.target = None;
+        let error = state.error.take();
+        res.map_err(|e| SessionError::Custom(error.unwrap_or_else(|| e.to_string())))
+    }
+
+    fn poll(
+        &self,
+        target: Target,
+        storage: &mut Storage,
+        context: &mut ModuleContext,
+        name: &str,
+    ) -> SessionResult<()> {
+        let res: i32 = self.call(Some(target), Some(storage), Some(context), name, ())?;
+        self.result(res)
+    }
+
+    fn on_event(
+        &self,
+        target: Target,
+        event: Event,
+        storage: &mut Storage,
+        context: &mut ModuleContext,
+        name: &str,
+    ) -> SessionResult<()> {
+        let mut from = -1;
+        let mut event = event;
+        while let Event::From(uid, inner) = event {
+            if from == -1 {
+                from = uid as i64;
+            }
+            event = *inner;
+        }
+        let (kind, uid, data) = match event {
+            Event::NewData(data) => (0, 0, data),
+            Event::ProgressChanged(uid) => (1, uid, Vec::new()),
+            Event::Completed(uid) => (2, uid, Vec::new()),
+            Event::Error(uid) => (3, uid, Vec::new()),
+            Event::Custom(data) => (4, 0, data.into_bytes()),
+            Event::From(..) => unreachable!(),
+        };
+
+        let (ptr, len) = if data.is_empty() {
+            (0, 0)
+        } else {
+            let ptr: i32 = self.call(None, None, None, "alloc", data.len() as i32)?;
+            let store = &mut *self.store.lock().unwrap();
+            let Some(memory) = self.instance.get_memory(&*store, "memory") else {
+                return Err(RawLibraryError::DontHaveSymbolMemory.into());
+            };
+            memory
+                .write(store, ptr as u32 as usize, &data)
+                .map_err(|e| SessionError::Custom(e.to_string()))?;
+            (ptr, data.len() as i32)
+        };
+
+        let res: i32 = self.call(
+            Some(target),
+            Some(storage),
+            Some(context),
+            name,
+            (kind, uid as i64, from, ptr, len),
+        )?;
+        self.result(res)
+    }
+
+    fn result(&self, res: i32) -> SessionResult<()> {
+        if res == 0 {
+            Ok(())
+        } else {
+            Err(SessionError::Custom(format!(
+                "Module {} returned {res}",
+                self.name
+            )))
+        }
+    }
+
+    fn settings(&self, name: &str) -> Settings {
+        let _: SessionResult<()> = self.call(None, None, None, name, ());
+        let new_settings = {
+            let mut store = self.store.lock().unwrap();
+            let state = store.data_mut();
+            state.settings_size = 0;
+            std::mem::take(&mut state.settings)
+        };
+        let mut settings = Settings::default();
+        for setting in new_settings {
+            settings.add(
+                setting.name,
+                Setting::new(setting.default, setting.variants, setting.desc),
+            );
+        }
+        settings
+    }
+}
+
+impl TModule for WasmModule {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn desc(&self) -> &str {
+        &self.desc
+    }
+
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn supported_versions(&self) -> &'static [u64] {
+        self.supported_versions
+    }
+
+    fn poll_element(
+        &self,
+        _ctx: &mut std::task::Context<'_>,
+        element: Arc<RwLock<Element>>,
+        storage: &mut Storage,
+        context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        self.poll(Target::Element(element), storage, context, "poll_element")
+    }
+
+    fn poll_location(
+        &self,
+        _ctx: &mut std::task::Context<'_>,
+        location: Arc<RwLock<Location>>,
+        storage: &mut Storage,
+        context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        self.poll(
+            Target::Location(location),
+            storage,
+            context,
+            "poll_location",
+        )
+    }
+
+    fn element_on_event(
+        &self,
+        element: Arc<RwLock<Element>>,
+        event: Event,
+        storage: &mut Storage,
+        context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        self.on_event(
+            Target::Element(element),
+            event,
+            storage,
+            context,
+            "element_on_event",
+        )
+    }
+
+    fn location_on_event(
+        &self,
+        location: Arc<RwLock<Location>>,
+        event: Event,
+        storage: &mut Storage,
+        context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        self.on_event(
+            Target::Location(location),
+            event,
+            storage,
+            context,
+            "location_on_event",
+        )
+    }
+
+    fn default_element_settings(&self) -> Settings {
+        self.settings("default_element_settings")
+    }
+
+    fn default_location_settings(&self) -> Settings {
+        self.settings("default_location_settings")
+    }
+
+    fn supports_protocols(&self) -> &[&'static str] {
+        &self.protocols
+    }
+
+    fn supports_extensions(&self) -> &[&'static str] {
+        &self.extensions
+    }
+}
+
+fn unpack(packed: i64) -> (usize, usize) {
+    (
+        (packed as u64 >> 32) as usize,
+        (packed as u64 as u32) as usize,
+    )
+}
+
+fn memory(caller: &Caller<'_, HostState>) -> Result<Memory, Error> {
+    caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| Error::new("The module does not export memory"))
+}
+
+/// The buffer is allocated by the host, so it is checked before that the module has it in its memory
+fn check(caller: &Caller<'_, HostState>, ptr: i32, len: usize) -> Result<(), Error> {
+    if len > WASM_MAX_COPY || ptr as u32 as usize + len > memory(caller)?.data_size(caller) {
+        return Err(Error::new("The buffer is outside of the module memory"));
+    }
+    Ok(())
+}
+
+fn read(caller: &Caller<'_, HostState>, ptr: i32, len: i32) -> Result<Vec<u8>, Error> {
+    let len = len as u32 as usize;
+    check(caller, ptr, len)?;
+    let mut buf = vec![0; len];
+    memory(caller)?
+        .read(caller, ptr as u32 as usize, &mut buf)
+        .map_err(|e| Error::new(e.to_string()))?;
+    Ok(buf)
+}
+
+fn read_str(caller: &Caller<'_, HostState>, ptr: i32, len: i32) -> Result<String, Error> {
+    Ok(String::from_utf8_lossy(&read(caller, ptr, len)?).to_string())
+}
+
+/// Writes at most `cap` bytes and returns the len of `bytes`
+fn write_out(
+    caller: &mut Caller<'_, HostState>,
+    ptr: i32,
+    cap: i32,
+    bytes: &[u8],
+) -> Result<i32, Error> {
+    let len = bytes.len().min(cap as u32 as usize);
+    memory(caller)?
+        .write(caller, ptr as u32 as usize, &bytes[..len])
+        .map_err(|e| Error::new(e.to_string()))?;
+    Ok(bytes.len() as i32)
+}
+
+fn encode_atom(atom: &Atom) -> Vec<u8> {
+    match atom {
+        Atom::B(v) => vec![0, *v as u8],
+        Atom::I(v) => [&[1][..], &v.to_le_bytes()].concat(),
+        Atom::U(v) => [&[2][..], &v.to_le_bytes()].concat(),
+        Atom::F(v) => [&[3][..], &v.to_le_bytes()].concat(),
+        Atom::S(v) => [&[4][..], v.as_bytes()].concat(),
+    }
+}
+
+fn decode_atom(bytes: &[u8]) -> Result<Atom, Error> {
+    let invalid = || Error::new("Invalid atom");
+    let (kind, value) = bytes.split_first().ok_or_else(invalid)?;
+    let number = || -> Result<[u8; 8], Error> { value.try_into().map_err(|_| invalid()) };
+    Ok(match kind {
+        0 => Atom::B(*value.first().ok_or_else(invalid)? != 0),
+        1 => Atom::I(i64::from_le_bytes(number()?)),
+        2 => Atom::U(u64::from_le_bytes(number()?)),
+        3 => Atom::F(f64::from_le_bytes(number()?)),
+        4 => Atom::S(String::from_utf8_lossy(value).to_string()),
+        _ => return Err(invalid()),
+    })
+}
+
+/// Runs `$body` with `$t` as the locked element or location, or returns `$none` if there is no target
+macro_rules! with_target {
+    ($caller:expr, $none:expr, $t:ident => $body:expr) => {
+        match &$caller.data().target {
+            Some(Target::Element(element)) => {
+                #[allow(unused_mut)]
+                let mut $t = element.write().unwrap();
+                $body
+            }
+            Some(Target::Location(location)) => {
+                #[allow(unused_mut)]
+                let mut $t = location.write().unwrap();
+                $body
+            }
+            None => $none,
+        }
+    };
+}
+
+/// Counts the bytes of the settings that the module added in this call
+fn add_settings_size(caller: &mut Caller<'_, HostState>, size: usize) -> Result<(), Error> {
+    let state = caller.data_mut();
+    state.settings_size += size;
+    if state.settings_size > WASM_MAX_SETTINGS {
+        return Err(Error::new("The settings are too big"));
+    }
+    Ok(())
+}
+
+fn element(caller: &Caller<'_, HostState>) -> Option<Arc<RwLock<Element>>> {
+    match &caller.data().target {
+        Some(Target::Element(element)) => Some(element.clone()),
+        _ => None,
+    }
+}
+
+fn linker(engine: &Engine) -> Result<Linker<HostState>, Error> {
+    let mut linker = Linker::<HostState>::new(engine);
+
+    linker.func_wrap(
+        "muzzman",
+        "url",
+        |mut caller: Caller<'_, HostState>, ptr: i32, cap: i32| {
+            let url = element(&caller)
+                .map(|element| element.read().unwrap().url.clone())
+                .unwrap_or_default();
+            write_out(&mut caller, ptr, cap, url.as_bytes())
+        },
+    )?;
+
+    linker.func_wrap(
+        "muzzman",
+        "path",
+        |mut caller: Caller<'_, HostState>, ptr: i32, cap: i32| {
+            let path =
+                with_target!(caller, String::new(), t => t.path.to_string_lossy().to_string());
+            write_out(&mut caller, ptr, cap, path.as_bytes())
+        },
+    )?;
+
+    linker.func_wrap(
+        "muzzman",
+        "setting",
+        |mut caller: Caller<'_, HostState>, name_ptr: i32, name_len: i32, ptr: i32, cap: i32| {
+            let name = read_str(&caller, name_ptr, name_len)?;
+            let atom =
+                with_target!(caller, None, t => t.settings.get(&name).map(|s| s.value.clone()));
+            match atom {
+                Some(atom) => write_out(&mut caller, ptr, cap, &encode_atom(&atom)),
+                None => Ok(-1),
+            }
+        },
+    )?;
+
+    linker.func_wrap(
+        "muzzman",
+        "data",
+        |mut caller: Caller<'_, HostState>, name_ptr: i32, name_len: i32, ptr: i32, cap: i32| {
+            let name = read_str(&caller, name_ptr, name_len)?;
+            let atom = with_target!(caller, None, t => t.data.get(&name).cloned());
+            match atom {
+                Some(atom) => write_out(&mut caller, ptr, cap, &encode_atom(&atom)),
+                None => Ok(-1),
+            }
+        },
+    )?;
+
+    linker.func_wrap(
+        "muzzman",
+        "set_data",
+        |caller: Caller<'_, HostState>, name_ptr: i32, name_len: i32, ptr: i32, len: i32| {
+            let name = read_str(&caller, name_ptr, name_len)?;
+            let atom = decode_atom(&read(&caller, ptr, len)?)?;
+            with_target!(caller, Ok(()), t => {
+                let size = t
+                    .data
+                    .iter()
+                    .filter(|(key, _)| **key != name)
+                    .map(|(key, value)| key.len() + encode_atom(value).len())
+                    .sum::<usize>();
+                if size + name.len() + encode_atom(&atom).len() > WASM_MAX_DATA {
+                    return Err(Error::new("The data is too big"));
+                }
+                t.data.insert(name, atom);
+                Ok(())
+            })
+        },
+    )?;
+
+    linker.func_wrap(
+        "muzzman",
+        "add_setting",
+        |mut caller: Caller<'_, HostState>,
+         name_ptr: i32,
+         name_len: i32,
+         default_ptr: i32,
+         default_len: i32,
+         desc_ptr: i32,
+         desc_len: i32| {
+            let setting = NewSetting {
+                name: read_str(&caller, name_ptr, name_len)?,
+                default: decode_atom(&read(&caller, default_ptr, default_len)?)?,
+                variants: Vec::new(),
+                desc: read_str(&caller, desc_ptr, desc_len)?,
+            };
+            add_settings_size(
+                &mut caller,
+                setting.name.len() + default_len as u32 as usize + setting.desc.len(),
+            )?;
+            caller.data_mut().settings.push(setting);
+            Ok(())
+        },
+    )?;
+
+    linker.func_wrap(
+        "muzzman",
+        "add_setting_variant",
+        |mut caller: Caller<'_, HostState>, name_ptr: i32, name_len: i32, ptr: i32, len: i32| {
+            let name = read_str(&caller, name_ptr, name_len)?;
+            let atom = decode_atom(&read(&caller, ptr, len)?)?;
+            add_settings_size(&mut caller, len as u32 as usize)?;
+            let setting = caller
+                .data_mut()
+                .settings
+                .iter_mut()
+                .rfind(|setting| setting.name == name)
+                .ok_or_else(|| Error::new(format!("There is no setting {name}")))?;
+            setting.variants.push(atom);
+            Ok(())
+        },
+    )?;
+
+    linker.func_wrap(
+        "muzzman",
+        "storage_get",
+        |mut caller: Caller<'_, HostState>, key_ptr: i32, key_len: i32, ptr: i32, cap: i32| {
+            let key = read_str(&caller, key_ptr, key_len)?;
+            let value = caller
+                .data()
+                .storage
+                .iter()
+                .find_map(|data| data.downcast_ref::<WasmStorage>())
+                .and_then(|storage| storage.values.get(&key).cloned());
+            match value {
+                Some(value) => write_out(&mut caller, ptr, cap, &value),
+                None => Ok(-1),
+            }
+        },
+    )?;
+
+    linker.func_wrap(
+        "muzzman",
+        "storage_set",
+        |mut caller: Caller<'_, HostState>, key_ptr: i32, key_len: i32, ptr: i32, len: i32| {
+            let key = read_str(&caller, key_ptr, key_len)?;
+            let value = read(&caller, ptr, len)?;
+            let storage = &mut caller.data_mut().storage;
+            if !storage.iter().any(|data| data.is::<WasmStorage>()) {
+                storage.push(WasmStorage::default());
+            }
+            let Some(storage) = storage
+                .iter_mut()
+                .find_map(|data| data.downcast_mut::<WasmStorage>())
+            else {
+                return Ok(());
+            };
+            let old = storage
+                .values
+                .get(&key)
+                .map(|old| key.len() + old.len())
+                .unwrap_or(0);
+            let size = storage.size - old + key.len() + value.len();
+            if size > WASM_MAX_STORAGE {
+                return Err(Error::new("The module storage is too big"));
+            }
+            storage.size = size;
+            storage.values.insert(key, value);
+            Ok(())
+        },
+    )?;
+
+    linker.func_wrap(
+        "muzzman",
+        "set_status",
+        |caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+            let status = read_str(&caller, ptr, len)?;
+            with_target!(caller, Ok(()), t => {
+                // a status that was set before is used again
+                if let Some(index) = t.statuses.iter().position(|s| *s == status) {
+                    t.status = index;
+                    return Ok(());
+                }
+                if t.statuses.len() >= WASM_MAX_STATUSES {
+                    return Err(Error::new("There are too many statuses"));
+                }
+                t.status = t.statuses.len();
+                t.statuses.push(status);
+                Ok(())
+            })
+        },
+    )?;
+
+    linker.func_wrap(
+        "muzzman",
+        "set_progress",
+        |caller: Caller<'_, HostState>, progress: f32| {
+            with_target!(caller, (), t => t.progress = progress);
+        },
+    )?;
+
+    linker.func_wrap(
+        "muzzman",
+        "set_completed",
+        |caller: Caller<'_, HostState>| {
+            with_target!(caller, (), t => t.is_completed = true);
+        },
+    )?;
+
+    linker.func_wrap(
+        "muzzman",
+        "set_error",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+            let error = read_str(&caller, ptr, len)?;
+            caller.data_mut().error = Some(error);
+            Ok(())
+        },
+    )?;
+
+    linker.func_wrap(
+        "muzzman",
+        "write",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+            let Some(element) = element(&caller) else {
+                caller.data_mut().error = Some("A location does not have a stream".into());
+                return Ok(-1);
+            };
+            let data = read(&caller, ptr, len)?;
+            let mut element = element.write().unwrap();
+            if let Err(error) = element.stream.write_all(&data) {
+                drop(element);
+                caller.data_mut().error = Some(error.to_string());
+                return Ok(-1);
+            }
+            element.total_download += data.len();
+            element.download_speed_counter += data.len();
+            Ok(0)
+        },
+    )?;
+
+    linker.func_wrap(
+        "muzzman",
+        "read",
+        |mut caller: Caller<'_, HostState>, ptr: i32, cap: i32| {
+            let Some(element) = element(&caller) else {
+                caller.data_mut().error = Some("A location does not have a stream".into());
+                return Ok(-1);
+            };
+            let cap = (cap as u32 as usize).min(WASM_MAX_COPY);
+            check(&caller, ptr, cap)?;
+            let mut buf = vec![0; cap];
+            let res = element.write().unwrap().stream.read(&mut buf);
+            match res {
+                Ok(read) => {
+                    write_out(&mut caller, ptr, cap as i32, &buf[..read])?;
+                    Ok(read as i32)
+                }
+                Err(error) => {
+                    caller.data_mut().error = Some(error.to_string());
+                    Ok(-1)
+                }
+            }
+        },
+    )?;
+
+    linker.func_wrap(
+        "muzzman",
+        "emit",
+        |mut caller: Caller<'_, HostState>, kind: i32, ptr: i32, len: i32| {
+            let Some(uid) = with_target!(caller, None, t => Some(t.id.uid)) else {
+                caller.data_mut().error =
+                    Some("There is no element or location to emit from".into());
+                return Ok(-1);
+            };
+            let data = read(&caller, ptr, len)?;
+            let event = match kind {
+                0 => Event::NewData(data),
+                1 => Event::ProgressChanged(uid),
+                2 => Event::Completed(uid),
+                3 => Event::Error(uid),
+                4 => Event::Custom(String::from_utf8_lossy(&data).to_string()),
+                _ => return Err(Error::new(format!("Invalid event kind {kind}"))),
+            };
+            caller.data_mut().context.emit(uid, event);
+            Ok(0)
+        },
+    )?;
+
+    linker.func_wrap(
+        "muzzman",
+        "log",
+        |caller: Caller<'_, HostState>, level: i32, ptr: i32, len: i32| {
+            let message = read_str(&caller, ptr, len)?;
+            let level = match level {
+                1 => log::Level::Error,
+                2 => log::Level::Warn,
+                3 => log::Level::Info,
+                4 => log::Level::Debug,
+                _ => log::Level::Trace,
+            };
+            log::log!(level, "{message}");
+            Ok(())
+        },
+    )?;
+
+    Ok(linker)
+}
@@ -6,10 +6,13 @@ pub fn fields(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     fields::fields(input)
 }
 
+/// Exports the module so a session can load the library as a dynamic module
+/// A unit struct is created with its name, anything else needs `#[module_link(constructor = ...)]`
+/// The library also exports `MUZZMAN_MANIFEST`, a `RawModuleManifest` that can be read without calling the module
 #[proc_macro_attribute]
 pub fn module_link(
-    _meta: proc_macro::TokenStream,
+    meta: proc_macro::TokenStream,
     input: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-    module_link::module_link(_meta, input)
+    module_link::module_link(meta, input)
 }
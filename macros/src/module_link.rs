@@ -1,109 +1,286 @@
-use proc_macro2::{Ident, Span, TokenStream, TokenTree};
-use quote::quote;
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
+use syn::{
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    Expr, Fields, Ident, Item, Token,
+};
+
+/// `#[module_link]` or `#[module_link(constructor = Module::new())]`
+struct Args {
+    constructor: Option<Expr>,
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut args = Args { constructor: None };
+        for arg in Punctuated::<Arg, Token![,]>::parse_terminated(input)? {
+            if arg.name == "constructor" {
+                if args.constructor.is_some() {
+                    return Err(syn::Error::new_spanned(
+                        arg.name,
+                        "`constructor` is already set",
+                    ));
+                }
+                args.constructor = Some(arg.value);
+            } else {
+                return Err(syn::Error::new_spanned(
+                    &arg.name,
+                    format!("unknown argument `{}`, expected `constructor`", arg.name),
+                ));
+            }
+        }
+        Ok(args)
+    }
+}
+
+struct Arg {
+    name: Ident,
+    value: Expr,
+}
+
+impl Parse for Arg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        input.parse::<Token![=]>()?;
+        Ok(Self {
+            name,
+            value: input.parse()?,
+        })
+    }
+}
 
 pub fn module_link(
-    _meta: proc_macro::TokenStream,
+    meta: proc_macro::TokenStream,
     input: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-    let input: TokenStream = input.into();
+    match expand(meta.into(), input.into()) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
 
-    let mut name = None;
+fn expand(meta: TokenStream, input: TokenStream) -> syn::Result<TokenStream> {
+    let args = syn::parse2::<Args>(meta)?;
+    let item = syn::parse2::<Item>(input)?;
 
-    {
-        let mut struct_finded = false;
+    let (name, generics, unit) = match &item {
+        Item::Struct(item) => (
+            &item.ident,
+            &item.generics,
+            matches!(item.fields, Fields::Unit),
+        ),
+        Item::Enum(item) => (&item.ident, &item.generics, false),
+        _ => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "`module_link` can only be used on a struct or an enum",
+            ))
+        }
+    };
 
-        for token in input.clone() {
-            if let TokenTree::Ident(test) = token {
-                if struct_finded {
-                    name = Some(test);
-                    break;
-                }
-                if test == Ident::new("struct", Span::call_site()) {
-                    struct_finded = true
-                }
-            }
+    let constructor = match args.constructor {
+        Some(constructor) => constructor.into_token_stream(),
+        None if unit && generics.params.is_empty() => name.into_token_stream(),
+        None => {
+            return Err(syn::Error::new_spanned(
+                name,
+                format!(
+                    "`{name}` is not a unit struct, use `#[module_link(constructor = ...)]` to create it"
+                ),
+            ))
         }
-    }
+    };
+    let module_type = name.to_string();
 
-    let name = name.expect("is not struct");
+    let prelude = quote!(::muzzman_lib::prelude);
 
-    quote! {
-        #input
+    Ok(quote! {
+        #item
 
-        static MODULE: #name = #name;
+        /// Created on the first call
+        fn __muzzman_module() -> &'static dyn #prelude::TModule {
+            static MODULE: ::std::sync::OnceLock<::std::boxed::Box<dyn #prelude::TModule>> =
+                ::std::sync::OnceLock::new();
+            MODULE.get_or_init(|| ::std::boxed::Box::new(#constructor)).as_ref()
+        }
+
+        #[no_mangle]
+        static MUZZMAN_MANIFEST: #prelude::RawModuleManifest = #prelude::RawModuleManifest {
+            abi_version: #prelude::ABI_VERSION,
+            build_id: #prelude::BUILD_ID.as_ptr() as *const ::std::ffi::c_char,
+            package_name: ::core::concat!(::core::env!("CARGO_PKG_NAME"), "\0").as_ptr() as *const ::std::ffi::c_char,
+            package_version: ::core::concat!(::core::env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const ::std::ffi::c_char,
+            module_type: ::core::concat!(#module_type, "\0").as_ptr() as *const ::std::ffi::c_char,
+            debug: ::core::cfg!(debug_assertions),
+        };
+
+        #[no_mangle]
+        extern "C" fn muzzman_abi_version() -> u64 {
+            #prelude::ABI_VERSION
+        }
+
+        #[no_mangle]
+        extern "C" fn muzzman_build_id() -> *const ::std::ffi::c_char {
+            #prelude::BUILD_ID.as_ptr() as *const ::std::ffi::c_char
+        }
 
         #[no_mangle]
         fn name() -> &'static str {
-            MODULE.name()
+            __muzzman_module().name()
         }
 
         #[no_mangle]
         fn desc() -> &'static str {
-            MODULE.desc()
+            __muzzman_module().desc()
         }
 
         #[no_mangle]
         fn id() -> u64 {
-            MODULE.id()
+            __muzzman_module().id()
         }
 
         #[no_mangle]
         fn version() -> u64 {
-            MODULE.version()
+            __muzzman_module().version()
         }
 
         #[no_mangle]
         fn supported_versions() -> &'static [u64]{
-            MODULE.supported_versions()
+            __muzzman_module().supported_versions()
         }
 
         #[no_mangle]
-        fn default_element_settings() -> Settings {
-            MODULE.default_element_settings()
+        fn default_element_settings() -> #prelude::Settings {
+            __muzzman_module().default_element_settings()
         }
 
         #[no_mangle]
-        fn default_location_settings() -> Settings {
-            MODULE.default_location_settings()
+        fn default_location_settings() -> #prelude::Settings {
+            __muzzman_module().default_location_settings()
         }
 
         #[no_mangle]
-        fn poll_element(ctx: &mut std::task::Context, element: Arc<RwLock<Element>>, storage: &mut Storage) -> Result<(), SessionError> {
-            MODULE.poll_element(ctx, element, storage)
+        fn poll_element(ctx: &mut ::std::task::Context, element: ::std::sync::Arc<::std::sync::RwLock<#prelude::Element>>, storage: &mut ::muzzman_lib::Storage, context: &mut #prelude::ModuleContext) -> #prelude::SessionResult<()> {
+            __muzzman_module().poll_element(ctx, element, storage, context)
         }
 
         #[no_mangle]
-        fn poll_location(ctx: &mut std::task::Context, location: Arc<RwLock<Location>>, storage: &mut Storage) -> Result<(), SessionError> {
-            MODULE.poll_location(ctx, location, storage)
+        fn poll_location(ctx: &mut ::std::task::Context, location: ::std::sync::Arc<::std::sync::RwLock<#prelude::Location>>, storage: &mut ::muzzman_lib::Storage, context: &mut #prelude::ModuleContext) -> #prelude::SessionResult<()> {
+            __muzzman_module().poll_location(ctx, location, storage, context)
         }
 
         #[no_mangle]
         fn element_on_event(
-            element: std::sync::Arc<std::sync::RwLock<Element>>,
-            event: Event,
-            storage: &mut Storage,
-        ) -> SessionResult<()> {
-            MODULE.element_on_event(element, event, storage)
+            element: ::std::sync::Arc<::std::sync::RwLock<#prelude::Element>>,
+            event: #prelude::Event,
+            storage: &mut ::muzzman_lib::Storage,
+            context: &mut #prelude::ModuleContext,
+        ) -> #prelude::SessionResult<()> {
+            __muzzman_module().element_on_event(element, event, storage, context)
         }
 
         #[no_mangle]
         fn location_on_event(
-            location: std::sync::Arc<std::sync::RwLock<Location>>,
-            event: Event,
-            storage: &mut Storage,
-        ) -> SessionResult<()> {
-            MODULE.location_on_event(location, event, storage)
+            location: ::std::sync::Arc<::std::sync::RwLock<#prelude::Location>>,
+            event: #prelude::Event,
+            storage: &mut ::muzzman_lib::Storage,
+            context: &mut #prelude::ModuleContext,
+        ) -> #prelude::SessionResult<()> {
+            __muzzman_module().location_on_event(location, event, storage, context)
         }
 
         #[no_mangle]
         fn supports_protocols() -> &'static [&'static str] {
-            MODULE.supports_protocols()
+            __muzzman_module().supports_protocols()
         }
 
         #[no_mangle]
         fn supports_extensions() -> &'static [&'static str] {
-            MODULE.supports_extensions()
+            __muzzman_module().supports_extensions()
         }
-    }
-    .into()
+
+        #[no_mangle]
+        fn permissions() -> #prelude::Permissions {
+            __muzzman_module().permissions()
+        }
+
+        #[no_mangle]
+        fn element_actions() -> ::std::vec::Vec<#prelude::Action> {
+            __muzzman_module().element_actions()
+        }
+
+        #[no_mangle]
+        fn location_actions() -> ::std::vec::Vec<#prelude::Action> {
+            __muzzman_module().location_actions()
+        }
+
+        #[no_mangle]
+        fn element_action(
+            element: ::std::sync::Arc<::std::sync::RwLock<#prelude::Element>>,
+            name: &str,
+            args: ::std::vec::Vec<#prelude::Atom>,
+            storage: &mut ::muzzman_lib::Storage,
+            context: &mut #prelude::ModuleContext,
+        ) -> #prelude::SessionResult<()> {
+            __muzzman_module().element_action(element, name, args, storage, context)
+        }
+
+        #[no_mangle]
+        fn location_action(
+            location: ::std::sync::Arc<::std::sync::RwLock<#prelude::Location>>,
+            name: &str,
+            args: ::std::vec::Vec<#prelude::Atom>,
+            storage: &mut ::muzzman_lib::Storage,
+            context: &mut #prelude::ModuleContext,
+        ) -> #prelude::SessionResult<()> {
+            __muzzman_module().location_action(location, name, args, storage, context)
+        }
+
+        #[no_mangle]
+        fn on_load(session: #prelude::Session) -> #prelude::SessionResult<()> {
+            __muzzman_module().on_load(session)
+        }
+
+        #[no_mangle]
+        fn on_unload() {
+            __muzzman_module().on_unload()
+        }
+
+        #[no_mangle]
+        fn on_element_attached(
+            element: ::std::sync::Arc<::std::sync::RwLock<#prelude::Element>>,
+            storage: &mut ::muzzman_lib::Storage,
+        ) -> #prelude::SessionResult<()> {
+            __muzzman_module().on_element_attached(element, storage)
+        }
+
+        #[no_mangle]
+        fn on_element_detached(element: ::std::sync::Arc<::std::sync::RwLock<#prelude::Element>>, storage: &mut ::muzzman_lib::Storage) {
+            __muzzman_module().on_element_detached(element, storage)
+        }
+
+        #[no_mangle]
+        fn on_element_destroyed(element: ::std::sync::Arc<::std::sync::RwLock<#prelude::Element>>, storage: &mut ::muzzman_lib::Storage) {
+            __muzzman_module().on_element_destroyed(element, storage)
+        }
+
+        #[no_mangle]
+        fn on_location_attached(
+            location: ::std::sync::Arc<::std::sync::RwLock<#prelude::Location>>,
+            storage: &mut ::muzzman_lib::Storage,
+        ) -> #prelude::SessionResult<()> {
+            __muzzman_module().on_location_attached(location, storage)
+        }
+
+        #[no_mangle]
+        fn on_location_detached(location: ::std::sync::Arc<::std::sync::RwLock<#prelude::Location>>, storage: &mut ::muzzman_lib::Storage) {
+            __muzzman_module().on_location_detached(location, storage)
+        }
+
+        #[no_mangle]
+        fn on_location_destroyed(location: ::std::sync::Arc<::std::sync::RwLock<#prelude::Location>>, storage: &mut ::muzzman_lib::Storage) {
+            __muzzman_module().on_location_destroyed(location, storage)
+        }
+    })
 }
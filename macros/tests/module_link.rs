@@ -0,0 +1,6 @@
+#[test]
+fn module_link() {
+    let cases = trybuild::TestCases::new();
+    cases.pass("tests/module_link/pass/*.rs");
+    cases.compile_fail("tests/module_link/fail/*.rs");
+}
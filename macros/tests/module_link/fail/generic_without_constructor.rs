@@ -0,0 +1,6 @@
+use muzzman_lib::prelude::module_link;
+
+#[module_link]
+pub struct Module<T>(T);
+
+fn main() {}
@@ -0,0 +1,8 @@
+use muzzman_lib::prelude::module_link;
+
+#[module_link]
+pub struct Module {
+    name: String,
+}
+
+fn main() {}
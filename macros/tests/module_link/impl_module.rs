@@ -0,0 +1,87 @@
+// Included by the cases, implements TModule without importing anything in the scope of the case
+macro_rules! impl_module {
+    ($module:ty) => {
+        const _: () = {
+            use muzzman_lib::{prelude::*, Storage};
+            use std::sync::{Arc, RwLock};
+
+            impl TModule for $module {
+                fn name(&self) -> &str {
+                    "Case"
+                }
+
+                fn desc(&self) -> &str {
+                    "A module_link case"
+                }
+
+                fn id(&self) -> u64 {
+                    1
+                }
+
+                fn version(&self) -> u64 {
+                    1
+                }
+
+                fn supported_versions(&self) -> &'static [u64] {
+                    &[ABI_VERSION]
+                }
+
+                fn poll_element(
+                    &self,
+                    _ctx: &mut std::task::Context<'_>,
+                    _element: Arc<RwLock<Element>>,
+                    _storage: &mut Storage,
+                    _context: &mut ModuleContext,
+                ) -> SessionResult<()> {
+                    Ok(())
+                }
+
+                fn poll_location(
+                    &self,
+                    _ctx: &mut std::task::Context<'_>,
+                    _location: Arc<RwLock<Location>>,
+                    _storage: &mut Storage,
+                    _context: &mut ModuleContext,
+                ) -> SessionResult<()> {
+                    Ok(())
+                }
+
+                fn element_on_event(
+                    &self,
+                    _element: Arc<RwLock<Element>>,
+                    _event: Event,
+                    _storage: &mut Storage,
+                    _context: &mut ModuleContext,
+                ) -> SessionResult<()> {
+                    Ok(())
+                }
+
+                fn location_on_event(
+                    &self,
+                    _location: Arc<RwLock<Location>>,
+                    _event: Event,
+                    _storage: &mut Storage,
+                    _context: &mut ModuleContext,
+                ) -> SessionResult<()> {
+                    Ok(())
+                }
+
+                fn default_element_settings(&self) -> Settings {
+                    Settings::default()
+                }
+
+                fn default_location_settings(&self) -> Settings {
+                    Settings::default()
+                }
+
+                fn supports_protocols(&self) -> &[&'static str] {
+                    &[]
+                }
+
+                fn supports_extensions(&self) -> &[&'static str] {
+                    &[]
+                }
+            }
+        };
+    };
+}
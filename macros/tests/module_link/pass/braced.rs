@@ -0,0 +1,22 @@
+use muzzman_lib::prelude::module_link;
+
+include!("../impl_module.rs");
+
+#[module_link(constructor = Braced::new("braced"))]
+pub struct Braced {
+    /// Documented field
+    name: &'static str,
+}
+
+impl Braced {
+    fn new(name: &'static str) -> Self {
+        Self { name }
+    }
+}
+
+impl_module!(Braced);
+
+fn main() {
+    assert_eq!(Braced::new("other").name, "other");
+    assert_eq!(desc(), "A module_link case");
+}
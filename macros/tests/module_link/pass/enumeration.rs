@@ -0,0 +1,16 @@
+use muzzman_lib::prelude::module_link;
+
+include!("../impl_module.rs");
+
+#[module_link(constructor = Enumeration::Fast)]
+pub enum Enumeration {
+    Fast,
+    Slow,
+}
+
+impl_module!(Enumeration);
+
+fn main() {
+    assert!(matches!(Enumeration::Slow, Enumeration::Slow));
+    assert_eq!(supported_versions(), &[muzzman_lib::prelude::ABI_VERSION]);
+}
@@ -0,0 +1,21 @@
+use muzzman_lib::prelude::module_link;
+
+include!("../impl_module.rs");
+
+#[module_link(constructor = Generic::<u8>::new(),)]
+pub struct Generic<T: Send + Sync> {
+    value: Option<T>,
+}
+
+impl<T: Send + Sync> Generic<T> {
+    fn new() -> Self {
+        Self { value: None }
+    }
+}
+
+impl_module!(Generic<u8>);
+
+fn main() {
+    assert!(Generic::<u16>::new().value.is_none());
+    assert!(supports_protocols().is_empty());
+}
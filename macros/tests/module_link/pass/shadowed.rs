@@ -0,0 +1,30 @@
+// The names used by the generated code mean something else here
+#![allow(dead_code)]
+
+use muzzman_lib::prelude::module_link;
+
+include!("../impl_module.rs");
+
+struct Arc;
+struct RwLock;
+struct Storage;
+struct Settings;
+struct Element;
+struct Location;
+struct Session;
+struct TModule;
+struct RawModuleManifest;
+const ABI_VERSION: () = ();
+const BUILD_ID: () = ();
+type Result = ();
+type Vec = ();
+type Box = ();
+
+#[module_link]
+pub struct Shadowed;
+
+impl_module!(Shadowed);
+
+fn main() {
+    assert_eq!(id(), 1);
+}
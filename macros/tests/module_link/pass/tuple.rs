@@ -0,0 +1,12 @@
+use muzzman_lib::prelude::module_link;
+
+include!("../impl_module.rs");
+
+#[module_link(constructor = Tuple(1, "tuple".to_string()))]
+pub struct Tuple(u64, String);
+
+impl_module!(Tuple);
+
+fn main() {
+    assert_eq!(version(), 1);
+}
@@ -0,0 +1,16 @@
+use muzzman_lib::prelude::module_link;
+
+include!("../impl_module.rs");
+
+/// The doc comment and the attributes stay on the struct
+#[module_link]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Unit;
+
+impl_module!(Unit);
+
+fn main() {
+    assert_eq!(format!("{:?}", Unit), "Unit");
+    assert_eq!(name(), "Case");
+    assert_eq!(MUZZMAN_MANIFEST.abi_version, muzzman_lib::prelude::ABI_VERSION);
+}
@@ -0,0 +1,54 @@
+//! Writes the url in the element stream, only through the C abi
+//! It supports no rust ABI_VERSION, a C module is checked only with C_ABI_VERSION
+
+use std::ffi::c_void;
+
+use muzzman_lib::prelude::*;
+
+static PROTOCOLS: [CSlice; 1] = [CSlice::new("cmodule")];
+
+static VTABLE: CModuleVTable = CModuleVTable {
+    abi_version: C_ABI_VERSION,
+    name: CSlice::new("CModule"),
+    desc: CSlice::new("Writes the url in the element stream"),
+    id: u64::MAX - 10,
+    version: 1,
+    supported_versions: std::ptr::null(),
+    supported_versions_len: 0,
+    protocols: PROTOCOLS.as_ptr(),
+    protocols_len: PROTOCOLS.len(),
+    extensions: std::ptr::null(),
+    extensions_len: 0,
+    default_element_settings: default_settings,
+    default_location_settings: default_settings,
+    poll_element,
+    poll_location,
+    element_on_event: on_event,
+    location_on_event: on_event,
+};
+
+#[no_mangle]
+pub extern "C" fn muzzman_module() -> *const CModuleVTable {
+    &VTABLE
+}
+
+extern "C" fn default_settings(_host: *const CHost, _settings: *mut c_void) {}
+
+extern "C" fn poll_element(host: *const CHost, handle: *mut c_void) -> i32 {
+    let host = unsafe { &*host };
+    let url = unsafe { (host.url)(handle).to_string_lossy() };
+    if (host.write)(handle, CSlice::new(&url)) != C_OK {
+        return C_ERROR;
+    }
+    (host.set_progress)(handle, 1.0);
+    (host.set_completed)(handle);
+    C_OK
+}
+
+extern "C" fn poll_location(_host: *const CHost, _handle: *mut c_void) -> i32 {
+    C_OK
+}
+
+extern "C" fn on_event(_host: *const CHost, _handle: *mut c_void, _event: *const CEvent) -> i32 {
+    C_OK
+}
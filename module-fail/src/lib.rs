@@ -0,0 +1,89 @@
+use muzzman_lib::prelude::*;
+use muzzman_lib::Storage;
+use std::sync::{Arc, RwLock};
+
+/// Loads but cannot be added to a session because `on_load` always fails
+#[module_link]
+pub struct ModuleFail;
+
+impl TModule for ModuleFail {
+    fn name(&self) -> &str {
+        "Fail"
+    }
+
+    fn desc(&self) -> &str {
+        "Fails when it is added to a session"
+    }
+
+    fn id(&self) -> u64 {
+        u64::MAX
+    }
+
+    fn version(&self) -> u64 {
+        1
+    }
+
+    fn supported_versions(&self) -> &'static [u64] {
+        &[ABI_VERSION]
+    }
+
+    fn poll_element(
+        &self,
+        _ctx: &mut std::task::Context<'_>,
+        _element: Arc<RwLock<Element>>,
+        _storage: &mut Storage,
+        _context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        Ok(())
+    }
+
+    fn poll_location(
+        &self,
+        _ctx: &mut std::task::Context<'_>,
+        _location: Arc<RwLock<Location>>,
+        _storage: &mut Storage,
+        _context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        Ok(())
+    }
+
+    fn element_on_event(
+        &self,
+        _element: Arc<RwLock<Element>>,
+        _event: Event,
+        _storage: &mut Storage,
+        _context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        Ok(())
+    }
+
+    fn location_on_event(
+        &self,
+        _location: Arc<RwLock<Location>>,
+        _event: Event,
+        _storage: &mut Storage,
+        _context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        Ok(())
+    }
+
+    fn default_element_settings(&self) -> Settings {
+        Settings::default()
+    }
+
+    fn default_location_settings(&self) -> Settings {
+        Settings::default()
+    }
+
+    fn supports_protocols(&self) -> &[&'static str] {
+        &[]
+    }
+
+    fn supports_extensions(&self) -> &[&'static str] {
+        &[]
+    }
+
+    fn on_load(&self, _session: Session) -> SessionResult<()> {
+        Err(SessionError::Custom("Cannot be loaded".into()))
+    }
+}
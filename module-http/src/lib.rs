@@ -1,12 +1,31 @@
 use futures::FutureExt;
-use hyper::http::request;
+use hyper::body::HttpBody;
+use hyper::client::ResponseFuture;
 use hyper::Body;
 use hyper::Method;
 use hyper::Request;
 use muzzman_lib::prelude::*;
 use muzzman_lib::Storage;
+use std::io::Write;
+use std::sync::Mutex;
 pub use std::sync::{Arc, RwLock};
 
+/// hyper needs the tokio of this library, the runtime of the session is from an other copy of tokio
+static RUNTIME: Mutex<Option<tokio::runtime::Runtime>> = Mutex::new(None);
+
+fn runtime() -> SessionResult<tokio::runtime::Handle> {
+    let mut runtime = RUNTIME.lock().unwrap();
+    if runtime.is_none() {
+        *runtime = Some(
+            tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(1)
+                .enable_all()
+                .build()?,
+        );
+    }
+    Ok(runtime.as_ref().unwrap().handle().clone())
+}
+
 #[module_link]
 pub struct ModuleHttp;
 
@@ -28,7 +47,7 @@ impl TModule for ModuleHttp {
     }
 
     fn supported_versions(&self) -> &'static [u64] {
-        &[1]
+        &[ABI_VERSION]
     }
 
     fn poll_element(
@@ -36,6 +55,7 @@ impl TModule for ModuleHttp {
         ctx: &mut std::task::Context<'_>,
         element: std::sync::Arc<std::sync::RwLock<Element>>,
         storage: &mut Storage,
+        context: &mut ModuleContext,
     ) -> SessionResult<()> {
         let status = element.read().unwrap().status;
         element.write().unwrap().statuses = ["Connecting", "Downloading", "Uploading", "Completed"]
@@ -47,32 +67,74 @@ impl TModule for ModuleHttp {
             .unwrap()
             .settings
             .get("Method")
-            .unwrap()
+            .ok_or(SessionError::InvalidSettings(vec!["Method".into()]))?
             .value
             .to_string();
         let uri = element.read().unwrap().url.clone();
+        let runtime = runtime()?;
+        let _guard = runtime.enter();
         match status {
             0 => {
-                let mut client = hyper::Client::new();
-                let mut request = client.request(
-                    Request::builder()
-                        .method(Method::from_bytes(method.as_bytes()).unwrap())
-                        .uri(uri)
-                        .body(Body::empty())
-                        .unwrap(),
-                );
-                match request.poll_unpin(ctx) {
-                    std::task::Poll::Ready(res) => {
-                        println!("Res: {res:?}");
+                // Connecting
+                if storage.get::<Mutex<ResponseFuture>>(0).is_none() {
+                    let request =
+                        Request::builder()
+                            .method(Method::from_bytes(method.as_bytes()).map_err(|_| {
+                                SessionError::InvalidSettings(vec!["Method".into()])
+                            })?)
+                            .uri(uri)
+                            .body(Body::empty())
+                            .map_err(|e| SessionError::Custom(e.to_string()))?;
+                    storage.push(Mutex::new(hyper::Client::new().request(request)));
+                }
+                let request = storage.get_mut::<Mutex<ResponseFuture>>(0).unwrap();
+                match request.get_mut().unwrap().poll_unpin(ctx) {
+                    std::task::Poll::Ready(Ok(response)) => {
+                        storage.pop();
+                        let size = response
+                            .headers()
+                            .get(hyper::header::CONTENT_LENGTH)
+                            .and_then(|length| length.to_str().ok())
+                            .and_then(|length| length.parse::<u64>().ok());
+                        storage.push(response.into_body());
+                        let mut element = element.write().unwrap();
+                        if let Some(size) = size {
+                            element
+                                .data
+                                .insert(element_data::SIZE.into(), Atom::U(size));
+                        }
+                        element.status = 1;
                     }
-                    std::task::Poll::Pending => {
-                        println!("Pending");
+                    std::task::Poll::Ready(Err(error)) => {
+                        return Err(SessionError::Custom(error.to_string()))
                     }
+                    std::task::Poll::Pending => {}
                 }
-                // Connecting
             }
             1 => {
                 // Downloading
+                let Some(body) = storage.get_mut::<Body>(0) else {
+                    // the storage was cleared, the request is made again
+                    element.write().unwrap().status = 0;
+                    return Ok(());
+                };
+                match std::pin::Pin::new(body).poll_data(ctx) {
+                    std::task::Poll::Ready(Some(Ok(chunk))) => {
+                        let mut element = element.write().unwrap();
+                        element.stream.write_all(&chunk)?;
+                        element.download_speed_counter += chunk.len();
+                        element.total_download += chunk.len();
+                        context.write(element.id.uid, chunk.to_vec());
+                    }
+                    std::task::Poll::Ready(Some(Err(error))) => {
+                        return Err(SessionError::Custom(error.to_string()))
+                    }
+                    std::task::Poll::Ready(None) => {
+                        storage.pop();
+                        element.write().unwrap().status = 3;
+                    }
+                    std::task::Poll::Pending => {}
+                }
             }
             2 => {
                 // Uploading
@@ -80,7 +142,7 @@ impl TModule for ModuleHttp {
             3 => {
                 // Completed
                 let id = element.read().unwrap().id.clone();
-                id.set_enabled(false);
+                let _ = id.set_enabled(false);
                 element.write().unwrap().is_completed = true;
             }
             _ => return Err(SessionError::Custom("Invalid status!".into())),
@@ -90,9 +152,10 @@ impl TModule for ModuleHttp {
 
     fn poll_location(
         &self,
-        ctx: &mut std::task::Context<'_>,
-        location: std::sync::Arc<std::sync::RwLock<Location>>,
-        storage: &mut Storage,
+        _ctx: &mut std::task::Context<'_>,
+        _location: std::sync::Arc<std::sync::RwLock<Location>>,
+        _storage: &mut Storage,
+        _context: &mut ModuleContext,
     ) -> SessionResult<()> {
         Err(SessionError::Custom(
             "HTTP is not implemented for an Location".into(),
@@ -101,18 +164,20 @@ impl TModule for ModuleHttp {
 
     fn element_on_event(
         &self,
-        element: std::sync::Arc<std::sync::RwLock<Element>>,
-        event: Event,
-        storage: &mut Storage,
+        _element: std::sync::Arc<std::sync::RwLock<Element>>,
+        _event: Event,
+        _storage: &mut Storage,
+        _context: &mut ModuleContext,
     ) -> SessionResult<()> {
         Ok(())
     }
 
     fn location_on_event(
         &self,
-        location: std::sync::Arc<std::sync::RwLock<Location>>,
-        event: Event,
-        storage: &mut Storage,
+        _location: std::sync::Arc<std::sync::RwLock<Location>>,
+        _event: Event,
+        _storage: &mut Storage,
+        _context: &mut ModuleContext,
     ) -> SessionResult<()> {
         Ok(())
     }
@@ -142,8 +207,7 @@ impl TModule for ModuleHttp {
     }
 
     fn default_location_settings(&self) -> Settings {
-        let mut settings = Settings::default();
-        settings
+        Settings::default()
     }
 
     fn supports_protocols(&self) -> &[&'static str] {
@@ -153,4 +217,15 @@ impl TModule for ModuleHttp {
     fn supports_extensions(&self) -> &[&'static str] {
         &[]
     }
+
+    fn on_unload(&self) {
+        // waits for the threads of the runtime, they cannot outlive the library
+        // dropping the runtime uses thread locals of tokio, so it is done on a thread that ends before the library is unloaded
+        if let Some(runtime) = RUNTIME.lock().unwrap().take() {
+            let _ = std::thread::spawn(move || drop(runtime)).join();
+        }
+    }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,42 @@
+use std::{
+    io::{Read, Write},
+    net::TcpListener,
+    time::Duration,
+};
+
+use muzzman_lib::prelude::*;
+use muzzman_module_test::*;
+
+use crate::ModuleHttp;
+
+#[test]
+fn main() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0; 1024];
+        let _ = stream.read(&mut request).unwrap();
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello")
+            .unwrap();
+    });
+
+    let mut harness = ModuleHarness::new(ModuleHttp).unwrap();
+    harness.set_wake_timeout(Duration::from_millis(100));
+    let element = harness
+        .element("Hello", &format!("http://{addr}/"))
+        .unwrap();
+    harness.run_element(&element, 100).unwrap();
+    server.join().unwrap();
+
+    assert_completed(&element);
+    assert_status(&element, "Completed");
+    assert_eq!(written(&element.read().unwrap()), b"hello");
+    assert_called(harness.session(), "element_set_enabled");
+    let uid = element.read().unwrap().id.uid;
+    assert_eq!(
+        harness.take_ops(),
+        vec![ContextOp::Write(uid, b"hello".to_vec())]
+    );
+}
@@ -0,0 +1,19 @@
+use muzzman_lib::prelude::*;
+
+use crate::MUZZMAN_MANIFEST;
+
+#[test]
+fn main() {
+    let manifest = unsafe { ModuleManifest::from_raw(&MUZZMAN_MANIFEST) };
+    assert_eq!(
+        manifest,
+        ModuleManifest {
+            abi_version: ABI_VERSION,
+            build_id: BUILD_ID.trim_end_matches('\0').to_string(),
+            package_name: "muzzman-module-http".into(),
+            package_version: "0.1.0".into(),
+            module_type: "ModuleHttp".into(),
+            debug: cfg!(debug_assertions),
+        }
+    );
+}
@@ -0,0 +1,64 @@
+use std::sync::RwLock;
+
+use muzzman_lib::prelude::*;
+
+use crate::FakeSession;
+
+/// The current status of the element is `status`
+#[track_caller]
+pub fn assert_status(element: &RwLock<Element>, status: &str) {
+    let element = element.read().unwrap();
+    let current = element.statuses.get(element.status);
+    assert_eq!(
+        current.map(String::as_str),
+        Some(status),
+        "the status of {:?} is {} in {:?}",
+        element.name,
+        element.status,
+        element.statuses
+    );
+}
+
+#[track_caller]
+pub fn assert_completed(element: &RwLock<Element>) {
+    let element = element.read().unwrap();
+    assert!(
+        element.is_completed && !element.is_error,
+        "{:?} is not completed, statuses: {:?}",
+        element.name,
+        element.statuses
+    );
+}
+
+/// The element is errored and the current status contains `error`
+#[track_caller]
+pub fn assert_error(element: &RwLock<Element>, error: &str) {
+    let element = element.read().unwrap();
+    let current = element.statuses.get(element.status);
+    assert!(
+        element.is_error && current.is_some_and(|status| status.contains(error)),
+        "{:?} does not have the error {error:?}, statuses: {:?}",
+        element.name,
+        element.statuses
+    );
+}
+
+/// `uid` emitted `event` with TSessionCommon::emit
+#[track_caller]
+pub fn assert_emitted(session: &FakeSession, uid: UID, event: &Event) {
+    let emitted = session.emitted();
+    assert!(
+        emitted.iter().any(|(from, e)| *from == uid && e == event),
+        "{uid} did not emit {event:?}, emitted: {emitted:?}"
+    );
+}
+
+/// The module called the session method `method`
+#[track_caller]
+pub fn assert_called(session: &FakeSession, method: &str) {
+    assert!(
+        !session.calls_to(method).is_empty(),
+        "{method} was not called, calls: {:?}",
+        session.calls()
+    );
+}
@@ -0,0 +1,253 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    task::Context,
+    time::Duration,
+};
+
+use muzzman_lib::{prelude::*, Storage};
+
+use crate::{FakeSession, ManualWaker};
+
+/// Calls a module like the session does, the polls happen only when the test asks for them
+///
+/// The module is loaded with the FakeSession when the harness is created and unloaded when it is dropped
+pub struct ModuleHarness {
+    module: Box<dyn TModule>,
+    session: FakeSession,
+    waker: Arc<ManualWaker>,
+    /// How long `run_element` waits for a wake between polls
+    wake_timeout: Duration,
+    element_storages: HashMap<UID, Storage>,
+    location_storages: HashMap<UID, Storage>,
+    ops: Vec<ContextOp>,
+}
+
+impl ModuleHarness {
+    pub fn new(module: impl TModule + 'static) -> SessionResult<Self> {
+        let session = FakeSession::new();
+        module.on_load(session.session())?;
+        Ok(Self {
+            module: Box::new(module),
+            session,
+            waker: Default::default(),
+            wake_timeout: Duration::ZERO,
+            element_storages: HashMap::new(),
+            location_storages: HashMap::new(),
+            ops: Vec::new(),
+        })
+    }
+
+    pub fn module(&self) -> &dyn TModule {
+        self.module.as_ref()
+    }
+
+    pub fn session(&self) -> &FakeSession {
+        &self.session
+    }
+
+    pub fn waker(&self) -> &ManualWaker {
+        &self.waker
+    }
+
+    /// For modules that wait for IO, by default `run_element` polls again without waiting
+    pub fn set_wake_timeout(&mut self, timeout: Duration) {
+        self.wake_timeout = timeout;
+    }
+
+    /// The operations that the module added to the ModuleContext in the calls that did not fail
+    pub fn take_ops(&mut self) -> Vec<ContextOp> {
+        std::mem::take(&mut self.ops)
+    }
+
+    /// An element in the default location that uses the module, it has the module settings
+    pub fn element(&mut self, name: &str, url: &str) -> SessionResult<Arc<RwLock<Element>>> {
+        let element = self
+            .session
+            .create_element(&self.session.default_location(), name);
+        {
+            let mut element = element.write().unwrap();
+            element.url = url.to_string();
+            element
+                .settings
+                .merge_module(&self.module.default_element_settings());
+        }
+        let uid = element.read().unwrap().id.uid;
+        let storage = self.element_storages.entry(uid).or_default();
+        self.module.on_element_attached(element.clone(), storage)?;
+        Ok(element)
+    }
+
+    /// A location in the default location that uses the module, it has the module settings
+    pub fn location(&mut self, name: &str) -> SessionResult<Arc<RwLock<Location>>> {
+        let location = self
+            .session
+            .create_location(&self.session.default_location(), name);
+        location
+            .write()
+            .unwrap()
+            .settings
+            .merge_module(&self.module.default_location_settings());
+        let uid = location.read().unwrap().id.uid;
+        let storage = self.location_storages.entry(uid).or_default();
+        self.module
+            .on_location_attached(location.clone(), storage)?;
+        Ok(location)
+    }
+
+    /// Detaches the element from the module, the storage is cleared
+    pub fn detach_element(&mut self, element: &Arc<RwLock<Element>>) {
+        let uid = element.read().unwrap().id.uid;
+        let mut storage = self.element_storages.remove(&uid).unwrap_or_default();
+        self.module
+            .on_element_detached(element.clone(), &mut storage);
+    }
+
+    pub fn poll_element(&mut self, element: &Arc<RwLock<Element>>) -> SessionResult<()> {
+        let uid = element.read().unwrap().id.uid;
+        let waker = self.waker.waker();
+        let mut ctx = Context::from_waker(&waker);
+        let storage = self.element_storages.entry(uid).or_default();
+        let mut context = ModuleContext::default();
+        self.module
+            .poll_element(&mut ctx, element.clone(), storage, &mut context)?;
+        self.ops.extend(context.take());
+        Ok(())
+    }
+
+    pub fn poll_location(&mut self, location: &Arc<RwLock<Location>>) -> SessionResult<()> {
+        let uid = location.read().unwrap().id.uid;
+        let waker = self.waker.waker();
+        let mut ctx = Context::from_waker(&waker);
+        let storage = self.location_storages.entry(uid).or_default();
+        let mut context = ModuleContext::default();
+        self.module
+            .poll_location(&mut ctx, location.clone(), storage, &mut context)?;
+        self.ops.extend(context.take());
+        Ok(())
+    }
+
+    /// Polls the element until it is completed or errored, returns how many times it was polled
+    /// If the module fails the element gets the error like in the session runner
+    pub fn run_element(
+        &mut self,
+        element: &Arc<RwLock<Element>>,
+        max_polls: usize,
+    ) -> SessionResult<usize> {
+        element.write().unwrap().enabled = true;
+        for polls in 1..=max_polls {
+            let wakes = self.waker.wakes();
+            if let Err(error) = self.poll_element(element) {
+                let mut element = element.write().unwrap();
+                element.is_error = true;
+                element.status = element.statuses.len();
+                element.statuses.push(format!("{error:?}"));
+                element.enabled = false;
+                return Err(error);
+            }
+            {
+                let element = element.read().unwrap();
+                if element.is_completed || element.is_error {
+                    return Ok(polls);
+                }
+            }
+            if !self.wake_timeout.is_zero() {
+                self.waker.wait(wakes, self.wake_timeout);
+            }
+        }
+        Err(SessionError::Custom(format!(
+            "The element is not completed after {max_polls} polls"
+        )))
+    }
+
+    pub fn element_event(
+        &mut self,
+        element: &Arc<RwLock<Element>>,
+        event: Event,
+    ) -> SessionResult<()> {
+        let uid = element.read().unwrap().id.uid;
+        let storage = self.element_storages.entry(uid).or_default();
+        let mut context = ModuleContext::default();
+        self.module
+            .element_on_event(element.clone(), event, storage, &mut context)?;
+        self.ops.extend(context.take());
+        Ok(())
+    }
+
+    pub fn location_event(
+        &mut self,
+        location: &Arc<RwLock<Location>>,
+        event: Event,
+    ) -> SessionResult<()> {
+        let uid = location.read().unwrap().id.uid;
+        let storage = self.location_storages.entry(uid).or_default();
+        let mut context = ModuleContext::default();
+        self.module
+            .location_on_event(location.clone(), event, storage, &mut context)?;
+        self.ops.extend(context.take());
+        Ok(())
+    }
+
+    /// Checks the action and the arguments like the session
+    pub fn element_action(
+        &mut self,
+        element: &Arc<RwLock<Element>>,
+        name: &str,
+        args: Vec<Atom>,
+    ) -> SessionResult<()> {
+        let Some(action) = self
+            .module
+            .element_actions()
+            .into_iter()
+            .find(|action| action.name == name)
+        else {
+            return Err(SessionError::NoAction(name.to_string()));
+        };
+        if !action.validate(&args) {
+            return Err(SessionError::InvalidActionArgs(name.to_string()));
+        }
+        let uid = element.read().unwrap().id.uid;
+        let storage = self.element_storages.entry(uid).or_default();
+        let mut context = ModuleContext::default();
+        self.module
+            .element_action(element.clone(), name, args, storage, &mut context)?;
+        self.ops.extend(context.take());
+        Ok(())
+    }
+
+    /// Checks the action and the arguments like the session
+    pub fn location_action(
+        &mut self,
+        location: &Arc<RwLock<Location>>,
+        name: &str,
+        args: Vec<Atom>,
+    ) -> SessionResult<()> {
+        let Some(action) = self
+            .module
+            .location_actions()
+            .into_iter()
+            .find(|action| action.name == name)
+        else {
+            return Err(SessionError::NoAction(name.to_string()));
+        };
+        if !action.validate(&args) {
+            return Err(SessionError::InvalidActionArgs(name.to_string()));
+        }
+        let uid = location.read().unwrap().id.uid;
+        let storage = self.location_storages.entry(uid).or_default();
+        let mut context = ModuleContext::default();
+        self.module
+            .location_action(location.clone(), name, args, storage, &mut context)?;
+        self.ops.extend(context.take());
+        Ok(())
+    }
+}
+
+impl Drop for ModuleHarness {
+    fn drop(&mut self) {
+        // the values in the storages can be from the module
+        self.element_storages.clear();
+        self.location_storages.clear();
+        self.module.on_unload();
+    }
+}
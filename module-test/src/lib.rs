@@ -0,0 +1,19 @@
+//! Tests a `TModule` without a LocalSession
+//!
+//! `ModuleHarness` calls the module like the session does, with a `FakeSession` that records
+//! the session calls of the module, a `ManualWaker` and elements that write in memory
+
+mod assert;
+mod harness;
+mod session;
+mod values;
+mod waker;
+
+#[cfg(test)]
+mod tests;
+
+pub use assert::*;
+pub use harness::ModuleHarness;
+pub use session::{Call, FakeSession};
+pub use values::*;
+pub use waker::ManualWaker;
@@ -0,0 +1,950 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    path::PathBuf,
+    sync::{Arc, Mutex, RwLock, Weak},
+};
+
+use muzzman_lib::prelude::*;
+
+use crate::values::{new_element, new_location};
+
+/// A session call of the module, `args` are formatted with Debug and have the uids instead of the ids
+#[derive(Clone, Debug, PartialEq)]
+pub struct Call {
+    pub method: &'static str,
+    pub args: String,
+}
+
+#[derive(Default)]
+struct State {
+    next_uid: UID,
+    elements: HashMap<UID, Arc<RwLock<Element>>>,
+    locations: HashMap<UID, Arc<RwLock<Location>>>,
+    events: HashMap<UID, Vec<Event>>,
+    emitted: Vec<(UID, Event)>,
+    calls: Vec<Call>,
+}
+
+/// Keeps the elements and locations that the module can use from the session,
+/// the modules are not in the session and every call of a module is recorded
+///
+/// Enabling an element or setting a module only changes the field, nothing runs
+pub struct FakeSession {
+    state: Arc<Mutex<State>>,
+    default_location: LocationId,
+}
+
+impl Default for FakeSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FakeSession {
+    pub fn new() -> Self {
+        let state = Arc::new(Mutex::new(State::default()));
+        let session = Handle {
+            state: Arc::downgrade(&state),
+        };
+        let default_location = session.add_location(None, "Default Location".into());
+        Self {
+            state,
+            default_location,
+        }
+    }
+
+    /// What the module gets as the session of its elements and locations
+    pub fn session(&self) -> Session {
+        Session::from(Box::new(Handle {
+            state: Arc::downgrade(&self.state),
+        }) as Box<dyn TSession>)
+    }
+
+    pub fn default_location(&self) -> LocationId {
+        self.default_location.clone()
+    }
+
+    pub fn create_element(&self, location: &LocationId, name: &str) -> Arc<RwLock<Element>> {
+        let id = self.handle().add_element(location, name.into());
+        self.element(id.uid).unwrap()
+    }
+
+    pub fn create_location(&self, location: &LocationId, name: &str) -> Arc<RwLock<Location>> {
+        let id = self
+            .handle()
+            .add_location(Some(location.clone()), name.into());
+        self.location(id.uid).unwrap()
+    }
+
+    pub fn element(&self, uid: UID) -> Option<Arc<RwLock<Element>>> {
+        self.state.lock().unwrap().elements.get(&uid).cloned()
+    }
+
+    pub fn location(&self, uid: UID) -> Option<Arc<RwLock<Location>>> {
+        self.state.lock().unwrap().locations.get(&uid).cloned()
+    }
+
+    /// Every session call in the order that they were made
+    pub fn calls(&self) -> Vec<Call> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    pub fn calls_to(&self, method: &str) -> Vec<Call> {
+        self.calls()
+            .into_iter()
+            .filter(|call| call.method == method)
+            .collect()
+    }
+
+    pub fn clear_calls(&self) {
+        self.state.lock().unwrap().calls.clear()
+    }
+
+    /// The events from TSessionCommon::emit with the uid that emitted them
+    pub fn emitted(&self) -> Vec<(UID, Event)> {
+        self.state.lock().unwrap().emitted.clone()
+    }
+
+    fn handle(&self) -> Handle {
+        Handle {
+            state: Arc::downgrade(&self.state),
+        }
+    }
+}
+
+/// The session of the elements and locations, weak so the elements can keep it
+#[derive(Clone)]
+struct Handle {
+    state: Weak<Mutex<State>>,
+}
+
+enum Target {
+    Element(Arc<RwLock<Element>>),
+    Location(Arc<RwLock<Location>>),
+}
+
+/// Runs `$body` with `$value` as the element or the location
+macro_rules! with_target {
+    ($target:expr, $value:ident => $body:expr) => {
+        match $target {
+            Target::Element(element) => {
+                let $value = &mut *element.write().unwrap();
+                $body
+            }
+            Target::Location(location) => {
+                let $value = &mut *location.write().unwrap();
+                $body
+            }
+        }
+    };
+}
+
+impl Handle {
+    fn state(&self) -> SessionResult<Arc<Mutex<State>>> {
+        self.state.upgrade().ok_or(SessionError::NoSession)
+    }
+
+    fn record(&self, method: &'static str, args: impl Debug) -> SessionResult<Arc<Mutex<State>>> {
+        let state = self.state()?;
+        state.lock().unwrap().calls.push(Call {
+            method,
+            args: format!("{args:?}"),
+        });
+        Ok(state)
+    }
+
+    fn element(
+        &self,
+        method: &'static str,
+        uid: UID,
+        args: impl Debug,
+    ) -> SessionResult<Arc<RwLock<Element>>> {
+        let state = self.record(method, (uid, args))?;
+        let element = state.lock().unwrap().elements.get(&uid).cloned();
+        element.ok_or(SessionError::UIDIsNotAElement)
+    }
+
+    fn location(
+        &self,
+        method: &'static str,
+        uid: UID,
+        args: impl Debug,
+    ) -> SessionResult<Arc<RwLock<Location>>> {
+        let state = self.record(method, (uid, args))?;
+        let location = state.lock().unwrap().locations.get(&uid).cloned();
+        location.ok_or(SessionError::UIDIsNotALocation)
+    }
+
+    fn target(&self, method: &'static str, uid: UID, args: impl Debug) -> SessionResult<Target> {
+        let state = self.record(method, (uid, args))?;
+        let state = state.lock().unwrap();
+        if let Some(element) = state.elements.get(&uid) {
+            Ok(Target::Element(element.clone()))
+        } else if let Some(location) = state.locations.get(&uid) {
+            Ok(Target::Location(location.clone()))
+        } else {
+            Err(SessionError::IsNotAnElementOrLocation)
+        }
+    }
+
+    fn unsupported<T>(&self, method: &'static str) -> SessionResult<T> {
+        self.record(method, ())?;
+        Err(SessionError::Custom(format!(
+            "FakeSession does not support {method}"
+        )))
+    }
+
+    fn session(&self) -> Session {
+        Session::from(Box::new(self.clone()) as Box<dyn TSession>)
+    }
+
+    fn add_element(&self, location: &LocationId, name: String) -> ElementId {
+        let state = self.state().unwrap();
+        let mut state = state.lock().unwrap();
+        let id = ElementId {
+            uid: state.next_uid,
+            session: Some(self.session()),
+        };
+        state.next_uid += 1;
+        let mut element = new_element(name, "");
+        element.id = id.clone();
+        element.parent = location.clone();
+        if let Some(location) = state.locations.get(&location.uid) {
+            let mut location = location.write().unwrap();
+            element.path = location.path.join(&element.name);
+            location.elements.push(id.clone());
+        }
+        state
+            .elements
+            .insert(id.uid, Arc::new(RwLock::new(element)));
+        id
+    }
+
+    fn add_location(&self, parent: Option<LocationId>, name: String) -> LocationId {
+        let state = self.state().unwrap();
+        let mut state = state.lock().unwrap();
+        let id = LocationId {
+            uid: state.next_uid,
+            session: Some(self.session()),
+        };
+        state.next_uid += 1;
+        let mut location = new_location(name);
+        location.id = id.clone();
+        if let Some(parent) = parent.as_ref().and_then(|p| state.locations.get(&p.uid)) {
+            let mut parent = parent.write().unwrap();
+            location.path = parent.path.join(&location.name);
+            parent.locations.push(id.clone());
+        }
+        location.parent = parent;
+        state
+            .locations
+            .insert(id.uid, Arc::new(RwLock::new(location)));
+        id
+    }
+}
+
+impl TSession for Handle {
+    fn version(&self) -> SessionResult<u64> {
+        self.record("version", ())?;
+        Ok(1)
+    }
+
+    fn version_str(&self) -> SessionResult<String> {
+        self.record("version_str", ())?;
+        Ok("FakeSession".into())
+    }
+
+    fn weak_box(&self) -> Box<dyn TSession> {
+        Box::new(self.clone())
+    }
+}
+
+impl TSessionCommon for Handle {
+    fn get_name(&self, uid: UID) -> SessionResult<String> {
+        Ok(with_target!(self.target("get_name", uid, ())?, value => value.name.clone()))
+    }
+
+    fn set_name(&self, uid: UID, name: String) -> SessionResult<()> {
+        with_target!(self.target("set_name", uid, &name)?, value => value.name = name);
+        Ok(())
+    }
+
+    fn get_desc(&self, uid: UID) -> SessionResult<String> {
+        Ok(with_target!(self.target("get_desc", uid, ())?, value => value.desc.clone()))
+    }
+
+    fn set_desc(&self, uid: UID, desc: String) -> SessionResult<()> {
+        with_target!(self.target("set_desc", uid, &desc)?, value => value.desc = desc);
+        Ok(())
+    }
+
+    fn emit(&self, uid: UID, event: Event) -> SessionResult<()> {
+        let state = self.record("emit", (uid, &event))?;
+        state.lock().unwrap().emitted.push((uid, event));
+        Ok(())
+    }
+
+    fn notify(&self, uid: UID, to: UID, event: Event) -> SessionResult<()> {
+        let state = self.record("notify", (uid, to, &event))?;
+        let mut state = state.lock().unwrap();
+        let events = state.events.entry(to).or_default();
+        events.push(Event::From(uid, Box::new(event)));
+        Ok(())
+    }
+
+    fn subscribe(&self, uid: UID, to: UID) -> SessionResult<()> {
+        self.record("subscribe", (uid, to))?;
+        Ok(())
+    }
+
+    fn unsubscribe(&self, uid: UID, from: UID) -> SessionResult<()> {
+        self.record("unsubscribe", (uid, from))?;
+        Ok(())
+    }
+
+    fn events(&self, uid: UID, consume: bool) -> SessionResult<Vec<Event>> {
+        let state = self.record("events", (uid, consume))?;
+        let mut state = state.lock().unwrap();
+        let events = state.events.entry(uid).or_default();
+        if consume {
+            Ok(std::mem::take(events))
+        } else {
+            Ok(events.clone())
+        }
+    }
+
+    fn push_event(&self, uid: UID, event: Event) -> SessionResult<()> {
+        let state = self.record("push_event", (uid, &event))?;
+        state
+            .lock()
+            .unwrap()
+            .events
+            .entry(uid)
+            .or_default()
+            .push(event);
+        Ok(())
+    }
+
+    fn get_buffer_size(&self, uid: UID) -> SessionResult<usize> {
+        Ok(with_target!(self.target("get_buffer_size", uid, ())?, value => value.buffer_size))
+    }
+
+    fn set_buffer_size(&self, uid: UID, size: usize) -> SessionResult<()> {
+        with_target!(self.target("set_buffer_size", uid, size)?, value => value.buffer_size = size);
+        Ok(())
+    }
+
+    fn remaining(&self, uid: UID) -> SessionResult<usize> {
+        Ok(with_target!(self.target("remaining", uid, ())?, value => value.buffer.len()))
+    }
+
+    fn read(&self, uid: UID, len: usize) -> SessionResult<Vec<u8>> {
+        Ok(with_target!(self.target("read", uid, len)?, value => {
+            let len = len.min(value.buffer.len());
+            value.buffer.drain(..len).collect()
+        }))
+    }
+
+    fn write(&self, uid: UID, data: &[u8]) -> SessionResult<usize> {
+        Ok(with_target!(self.target("write", uid, data)?, value => {
+            let len = data
+                .len()
+                .min(value.buffer_size.saturating_sub(value.buffer.len()));
+            value.buffer.extend_from_slice(&data[..len]);
+            len
+        }))
+    }
+}
+
+impl TSessionElement for Handle {
+    fn create_element(&self, location: LocationId, name: String) -> SessionResult<ElementId> {
+        self.location("create_element", location.uid, &name)?;
+        Ok(self.add_element(&location, name))
+    }
+
+    fn create_element_from_url(
+        &self,
+        location: LocationId,
+        url: String,
+    ) -> SessionResult<ElementId> {
+        self.location("create_element_from_url", location.uid, &url)?;
+        let element = self.add_element(&location, url.clone());
+        self.state()?.lock().unwrap().elements[&element.uid]
+            .write()
+            .unwrap()
+            .url = url;
+        Ok(element)
+    }
+
+    fn get_element(&self, _path: Vec<usize>) -> SessionResult<ElementId> {
+        self.unsupported("get_element")
+    }
+
+    fn move_element(&self, _element: ElementId, _location: LocationId) -> SessionResult<()> {
+        self.unsupported("move_element")
+    }
+
+    fn element_path(&self, _element: ElementId) -> SessionResult<Vec<usize>> {
+        self.unsupported("element_path")
+    }
+
+    fn element_get_parent(&self, element: ElementId) -> SessionResult<LocationId> {
+        let element = self.element("element_get_parent", element.uid, ())?;
+        let parent = element.read().unwrap().parent.clone();
+        Ok(parent)
+    }
+
+    fn element_get_enabled(&self, element: ElementId) -> SessionResult<bool> {
+        let element = self.element("element_get_enabled", element.uid, ())?;
+        let enabled = element.read().unwrap().enabled;
+        Ok(enabled)
+    }
+
+    fn element_set_enabled(&self, element: ElementId, enabled: bool) -> SessionResult<()> {
+        let element = self.element("element_set_enabled", element.uid, enabled)?;
+        element.write().unwrap().enabled = enabled;
+        Ok(())
+    }
+
+    fn element_get_path(&self, element: ElementId) -> SessionResult<PathBuf> {
+        let element = self.element("element_get_path", element.uid, ())?;
+        let path = element.read().unwrap().path.clone();
+        Ok(path)
+    }
+
+    fn element_set_path(&self, element: ElementId, path: PathBuf) -> SessionResult<()> {
+        let element = self.element("element_set_path", element.uid, &path)?;
+        element.write().unwrap().path = path;
+        Ok(())
+    }
+
+    fn element_is_completed(&self, element: ElementId) -> SessionResult<bool> {
+        let element = self.element("element_is_completed", element.uid, ())?;
+        let is_completed = element.read().unwrap().is_completed;
+        Ok(is_completed)
+    }
+
+    fn element_is_error(&self, element: ElementId) -> SessionResult<bool> {
+        let element = self.element("element_is_error", element.uid, ())?;
+        let is_error = element.read().unwrap().is_error;
+        Ok(is_error)
+    }
+
+    fn element_get_statuses(&self, element: ElementId) -> SessionResult<Vec<String>> {
+        let element = self.element("element_get_statuses", element.uid, ())?;
+        let statuses = element.read().unwrap().statuses.clone();
+        Ok(statuses)
+    }
+
+    fn element_set_statuses(&self, element: ElementId, statuses: Vec<String>) -> SessionResult<()> {
+        let element = self.element("element_set_statuses", element.uid, &statuses)?;
+        element.write().unwrap().statuses = statuses;
+        Ok(())
+    }
+
+    fn element_get_status(&self, element: ElementId) -> SessionResult<usize> {
+        let element = self.element("element_get_status", element.uid, ())?;
+        let status = element.read().unwrap().status;
+        Ok(status)
+    }
+
+    fn element_set_status(&self, element: ElementId, status: usize) -> SessionResult<()> {
+        let element = self.element("element_set_status", element.uid, status)?;
+        element.write().unwrap().status = status;
+        Ok(())
+    }
+
+    fn element_get_status_str(&self, element: ElementId) -> SessionResult<String> {
+        let element = self.element("element_get_status_str", element.uid, ())?;
+        let element = element.read().unwrap();
+        element
+            .statuses
+            .get(element.status)
+            .cloned()
+            .ok_or(SessionError::InvalidStatus)
+    }
+
+    fn element_get_url(&self, element: ElementId) -> SessionResult<String> {
+        let element = self.element("element_get_url", element.uid, ())?;
+        let url = element.read().unwrap().url.clone();
+        Ok(url)
+    }
+
+    fn element_set_url(&self, element: ElementId, url: String) -> SessionResult<()> {
+        let element = self.element("element_set_url", element.uid, &url)?;
+        element.write().unwrap().url = url;
+        Ok(())
+    }
+
+    fn element_get_progress(&self, element: ElementId) -> SessionResult<f32> {
+        let element = self.element("element_get_progress", element.uid, ())?;
+        let progress = element.read().unwrap().progress;
+        Ok(progress)
+    }
+
+    fn element_get_download_speed(&self, element: ElementId) -> SessionResult<usize> {
+        let element = self.element("element_get_download_speed", element.uid, ())?;
+        let speed = element.read().unwrap().download_speed;
+        Ok(speed)
+    }
+
+    fn element_get_upload_speed(&self, element: ElementId) -> SessionResult<usize> {
+        let element = self.element("element_get_upload_speed", element.uid, ())?;
+        let speed = element.read().unwrap().upload_speed;
+        Ok(speed)
+    }
+
+    fn element_get_download_total(&self, element: ElementId) -> SessionResult<usize> {
+        let element = self.element("element_get_download_total", element.uid, ())?;
+        let total = element.read().unwrap().total_download;
+        Ok(total)
+    }
+
+    fn element_get_upload_total(&self, element: ElementId) -> SessionResult<usize> {
+        let element = self.element("element_get_upload_total", element.uid, ())?;
+        let total = element.read().unwrap().total_upload;
+        Ok(total)
+    }
+
+    fn element_get_data(&self, element: ElementId) -> SessionResult<HashMap<String, Atom>> {
+        let element = self.element("element_get_data", element.uid, ())?;
+        let data = element.read().unwrap().data.clone();
+        Ok(data)
+    }
+
+    fn element_set_data(
+        &self,
+        element: ElementId,
+        data: HashMap<String, Atom>,
+    ) -> SessionResult<()> {
+        let element = self.element("element_set_data", element.uid, &data)?;
+        element.write().unwrap().data = data;
+        Ok(())
+    }
+
+    fn element_get_settings(&self, element: ElementId) -> SessionResult<Settings> {
+        let element = self.element("element_get_settings", element.uid, ())?;
+        let settings = element.read().unwrap().settings.clone();
+        Ok(settings)
+    }
+
+    fn element_set_settings(&self, element: ElementId, settings: Settings) -> SessionResult<()> {
+        let element = self.element("element_set_settings", element.uid, &settings)?;
+        element.write().unwrap().settings = settings;
+        Ok(())
+    }
+
+    fn element_get_module(&self, element: ElementId) -> SessionResult<Option<ModuleId>> {
+        let element = self.element("element_get_module", element.uid, ())?;
+        let module = element.read().unwrap().module.clone();
+        Ok(module)
+    }
+
+    fn element_set_module(
+        &self,
+        element: ElementId,
+        module_id: Option<ModuleId>,
+    ) -> SessionResult<()> {
+        let uid = module_id.as_ref().map(|module| module.uid);
+        let element = self.element("element_set_module", element.uid, uid)?;
+        element.write().unwrap().module = module_id;
+        Ok(())
+    }
+
+    fn element_get_proxy(&self, element: ElementId) -> SessionResult<Option<ModuleId>> {
+        let element = self.element("element_get_proxy", element.uid, ())?;
+        let proxy = element.read().unwrap().proxy.clone();
+        Ok(proxy)
+    }
+
+    fn element_set_proxy(&self, element: ElementId, proxy: Option<ModuleId>) -> SessionResult<()> {
+        let uid = proxy.as_ref().map(|module| module.uid);
+        let element = self.element("element_set_proxy", element.uid, uid)?;
+        element.write().unwrap().proxy = proxy;
+        Ok(())
+    }
+
+    fn element_get_actions(&self, _element: ElementId) -> SessionResult<Vec<Action>> {
+        self.unsupported("element_get_actions")
+    }
+
+    fn element_run_action(
+        &self,
+        _element: ElementId,
+        _name: String,
+        _args: Vec<Atom>,
+    ) -> SessionResult<()> {
+        self.unsupported("element_run_action")
+    }
+
+    fn element_wait(&self, element: ElementId) -> SessionResult<()> {
+        self.element("element_wait", element.uid, ())?;
+        Ok(())
+    }
+
+    fn destroy_element(&self, element: ElementId) -> SessionResult<()> {
+        let removed = self.element("destroy_element", element.uid, ())?;
+        let state = self.state()?;
+        let mut state = state.lock().unwrap();
+        state.elements.remove(&element.uid);
+        let parent = removed.read().unwrap().parent.uid;
+        if let Some(parent) = state.locations.get(&parent) {
+            parent
+                .write()
+                .unwrap()
+                .elements
+                .retain(|id| id.uid != element.uid);
+        }
+        Ok(())
+    }
+}
+
+impl TSessionLocation for Handle {
+    fn create_location(&self, location: LocationId, name: String) -> SessionResult<LocationId> {
+        self.location("create_location", location.uid, &name)?;
+        Ok(self.add_location(Some(location), name))
+    }
+
+    fn get_location(&self, _path: Vec<usize>) -> SessionResult<LocationId> {
+        self.unsupported("get_location")
+    }
+
+    fn get_default_location(&self) -> SessionResult<LocationId> {
+        let state = self.record("get_default_location", ())?;
+        let state = state.lock().unwrap();
+        let location = state.locations.values().find_map(|location| {
+            let location = location.read().unwrap();
+            location.parent.is_none().then(|| location.id.clone())
+        });
+        location.ok_or(SessionError::UIDWasDestroyed)
+    }
+
+    fn location_get_parent(&self, location: LocationId) -> SessionResult<LocationId> {
+        let location = self.location("location_get_parent", location.uid, ())?;
+        let parent = location.read().unwrap().parent.clone();
+        parent.ok_or(SessionError::IsRoot)
+    }
+
+    fn location_get_locations_len(&self, location: LocationId) -> SessionResult<usize> {
+        let location = self.location("location_get_locations_len", location.uid, ())?;
+        let len = location.read().unwrap().locations.len();
+        Ok(len)
+    }
+
+    fn location_get_locations(
+        &self,
+        location: LocationId,
+        start: usize,
+        end: usize,
+    ) -> SessionResult<Vec<LocationId>> {
+        let location = self.location("location_get_locations", location.uid, (start, end))?;
+        let location = location.read().unwrap();
+        location
+            .locations
+            .get(start..=end)
+            .map(<[LocationId]>::to_vec)
+            .ok_or(SessionError::ThereAreLessLocations)
+    }
+
+    fn location_get_elements_len(&self, location: LocationId) -> SessionResult<usize> {
+        let location = self.location("location_get_elements_len", location.uid, ())?;
+        let len = location.read().unwrap().elements.len();
+        Ok(len)
+    }
+
+    fn location_get_elements(
+        &self,
+        location: LocationId,
+        start: usize,
+        end: usize,
+    ) -> SessionResult<Vec<ElementId>> {
+        let location = self.location("location_get_elements", location.uid, (start, end))?;
+        let location = location.read().unwrap();
+        location
+            .elements
+            .get(start..=end)
+            .map(<[ElementId]>::to_vec)
+            .ok_or(SessionError::ThereAreLessElements)
+    }
+
+    fn location_get_enabled(&self, location: LocationId) -> SessionResult<bool> {
+        let location = self.location("location_get_enabled", location.uid, ())?;
+        let enabled = location.read().unwrap().enabled;
+        Ok(enabled)
+    }
+
+    fn location_set_enabled(&self, location: LocationId, enabled: bool) -> SessionResult<()> {
+        let location = self.location("location_set_enabled", location.uid, enabled)?;
+        location.write().unwrap().enabled = enabled;
+        Ok(())
+    }
+
+    fn location_get_path(&self, location: LocationId) -> SessionResult<PathBuf> {
+        let location = self.location("location_get_path", location.uid, ())?;
+        let path = location.read().unwrap().path.clone();
+        Ok(path)
+    }
+
+    fn location_set_path(&self, location: LocationId, path: PathBuf) -> SessionResult<()> {
+        let location = self.location("location_set_path", location.uid, &path)?;
+        location.write().unwrap().path = path;
+        Ok(())
+    }
+
+    fn location_is_completed(&self, location: LocationId) -> SessionResult<bool> {
+        let location = self.location("location_is_completed", location.uid, ())?;
+        let is_completed = location.read().unwrap().is_completed;
+        Ok(is_completed)
+    }
+
+    fn location_is_error(&self, location: LocationId) -> SessionResult<bool> {
+        let location = self.location("location_is_error", location.uid, ())?;
+        let is_error = location.read().unwrap().is_error;
+        Ok(is_error)
+    }
+
+    fn location_get_statuses(&self, location: LocationId) -> SessionResult<Vec<String>> {
+        let location = self.location("location_get_statuses", location.uid, ())?;
+        let statuses = location.read().unwrap().statuses.clone();
+        Ok(statuses)
+    }
+
+    fn location_set_statuses(
+        &self,
+        location: LocationId,
+        statuses: Vec<String>,
+    ) -> SessionResult<()> {
+        let location = self.location("location_set_statuses", location.uid, &statuses)?;
+        location.write().unwrap().statuses = statuses;
+        Ok(())
+    }
+
+    fn location_get_status(&self, location: LocationId) -> SessionResult<usize> {
+        let location = self.location("location_get_status", location.uid, ())?;
+        let status = location.read().unwrap().status;
+        Ok(status)
+    }
+
+    fn location_set_status(&self, location: LocationId, status: usize) -> SessionResult<()> {
+        let location = self.location("location_set_status", location.uid, status)?;
+        location.write().unwrap().status = status;
+        Ok(())
+    }
+
+    fn location_get_status_str(&self, location: LocationId) -> SessionResult<String> {
+        let location = self.location("location_get_status_str", location.uid, ())?;
+        let location = location.read().unwrap();
+        location
+            .statuses
+            .get(location.status)
+            .cloned()
+            .ok_or(SessionError::InvalidStatus)
+    }
+
+    fn location_get_progress(&self, location: LocationId) -> SessionResult<f32> {
+        let location = self.location("location_get_progress", location.uid, ())?;
+        let progress = location.read().unwrap().progress;
+        Ok(progress)
+    }
+
+    fn location_get_download_speed(&self, location: LocationId) -> SessionResult<usize> {
+        let location = self.location("location_get_download_speed", location.uid, ())?;
+        let speed = location.read().unwrap().download_speed;
+        Ok(speed)
+    }
+
+    fn location_get_upload_speed(&self, location: LocationId) -> SessionResult<usize> {
+        let location = self.location("location_get_upload_speed", location.uid, ())?;
+        let speed = location.read().unwrap().upload_speed;
+        Ok(speed)
+    }
+
+    fn location_get_download_total(&self, location: LocationId) -> SessionResult<usize> {
+        let location = self.location("location_get_download_total", location.uid, ())?;
+        let total = location.read().unwrap().total_download;
+        Ok(total)
+    }
+
+    fn location_get_upload_total(&self, location: LocationId) -> SessionResult<usize> {
+        let location = self.location("location_get_upload_total", location.uid, ())?;
+        let total = location.read().unwrap().total_upload;
+        Ok(total)
+    }
+
+    fn location_get_data(&self, location: LocationId) -> SessionResult<HashMap<String, Atom>> {
+        let location = self.location("location_get_data", location.uid, ())?;
+        let data = location.read().unwrap().data.clone();
+        Ok(data)
+    }
+
+    fn location_set_data(
+        &self,
+        location: LocationId,
+        data: HashMap<String, Atom>,
+    ) -> SessionResult<()> {
+        let location = self.location("location_set_data", location.uid, &data)?;
+        location.write().unwrap().data = data;
+        Ok(())
+    }
+
+    fn location_get_settings(&self, location: LocationId) -> SessionResult<Settings> {
+        let location = self.location("location_get_settings", location.uid, ())?;
+        let settings = location.read().unwrap().settings.clone();
+        Ok(settings)
+    }
+
+    fn location_set_settings(&self, location: LocationId, settings: Settings) -> SessionResult<()> {
+        let location = self.location("location_set_settings", location.uid, &settings)?;
+        location.write().unwrap().settings = settings;
+        Ok(())
+    }
+
+    fn location_get_module(&self, location: LocationId) -> SessionResult<Option<ModuleId>> {
+        let location = self.location("location_get_module", location.uid, ())?;
+        let module = location.read().unwrap().module.clone();
+        Ok(module)
+    }
+
+    fn location_set_module(
+        &self,
+        location: LocationId,
+        module_id: Option<ModuleId>,
+    ) -> SessionResult<()> {
+        let uid = module_id.as_ref().map(|module| module.uid);
+        let location = self.location("location_set_module", location.uid, uid)?;
+        location.write().unwrap().module = module_id;
+        Ok(())
+    }
+
+    fn location_get_actions(&self, _location: LocationId) -> SessionResult<Vec<Action>> {
+        self.unsupported("location_get_actions")
+    }
+
+    fn location_run_action(
+        &self,
+        _location: LocationId,
+        _name: String,
+        _args: Vec<Atom>,
+    ) -> SessionResult<()> {
+        self.unsupported("location_run_action")
+    }
+
+    fn move_location(
+        &self,
+        _location: LocationId,
+        _location_location: LocationId,
+    ) -> SessionResult<()> {
+        self.unsupported("move_location")
+    }
+
+    fn location_path(&self, _location: LocationId) -> SessionResult<Vec<usize>> {
+        self.unsupported("location_path")
+    }
+
+    fn location_wait(&self, location: LocationId) -> SessionResult<()> {
+        self.location("location_wait", location.uid, ())?;
+        Ok(())
+    }
+
+    fn destroy_location(&self, _location: LocationId) -> SessionResult<()> {
+        self.unsupported("destroy_location")
+    }
+}
+
+impl TSessionModule for Handle {
+    fn add_module(&self, _source: ModuleSource) -> SessionResult<ModuleId> {
+        self.unsupported("add_module")
+    }
+
+    fn load_modules(&self, _dirs: Vec<PathBuf>) -> SessionResult<Vec<ModuleLoadReport>> {
+        self.unsupported("load_modules")
+    }
+
+    fn get_module(&self, _path: usize) -> SessionResult<ModuleId> {
+        self.unsupported("get_module")
+    }
+
+    /// The tested module is not in the session
+    fn get_modules(&self) -> SessionResult<Vec<ModuleId>> {
+        self.record("get_modules", ())?;
+        Ok(Vec::new())
+    }
+
+    fn get_modules_info(&self) -> SessionResult<Vec<ModuleInfo>> {
+        self.record("get_modules_info", ())?;
+        Ok(Vec::new())
+    }
+
+    fn find_module_by_id(&self, _id: u64) -> SessionResult<ModuleId> {
+        self.unsupported("find_module_by_id")
+    }
+
+    fn find_module_by_name(&self, _name: String) -> SessionResult<ModuleId> {
+        self.unsupported("find_module_by_name")
+    }
+
+    fn module_info(&self, _module: ModuleId) -> SessionResult<ModuleInfo> {
+        self.unsupported("module_info")
+    }
+
+    fn module_get_element_settings(&self, _module: ModuleId) -> SessionResult<Settings> {
+        self.unsupported("module_get_element_settings")
+    }
+
+    fn module_set_element_settings(
+        &self,
+        _module: ModuleId,
+        _settings: Settings,
+    ) -> SessionResult<()> {
+        self.unsupported("module_set_element_settings")
+    }
+
+    fn module_get_location_settings(&self, _module: ModuleId) -> SessionResult<Settings> {
+        self.unsupported("module_get_location_settings")
+    }
+
+    fn module_set_location_settings(
+        &self,
+        _module: ModuleId,
+        _settings: Settings,
+    ) -> SessionResult<()> {
+        self.unsupported("module_set_location_settings")
+    }
+
+    fn module_supports_protocols(&self, _module: ModuleId) -> SessionResult<Vec<String>> {
+        self.unsupported("module_supports_protocols")
+    }
+
+    fn module_supports_extensions(&self, _module: ModuleId) -> SessionResult<Vec<String>> {
+        self.unsupported("module_supports_extensions")
+    }
+
+    fn module_path(&self, _module: ModuleId) -> SessionResult<usize> {
+        self.unsupported("module_path")
+    }
+
+    fn module_id(&self, _module: ModuleId) -> SessionResult<u64> {
+        self.unsupported("module_id")
+    }
+
+    fn module_get_permissions(&self, _module: ModuleId) -> SessionResult<Permissions> {
+        self.unsupported("module_get_permissions")
+    }
+
+    fn module_set_permissions(
+        &self,
+        _module: ModuleId,
+        _permissions: Permissions,
+    ) -> SessionResult<()> {
+        self.unsupported("module_set_permissions")
+    }
+
+    fn module_reload(&self, _module: ModuleId, _source: ModuleSource) -> SessionResult<()> {
+        self.unsupported("module_reload")
+    }
+
+    fn destroy_module(&self, _module: ModuleId) -> SessionResult<()> {
+        self.unsupported("destroy_module")
+    }
+}
@@ -0,0 +1,56 @@
+use muzzman_lib::prelude::*;
+
+use crate::*;
+
+#[test]
+fn main() {
+    let fake = FakeSession::new();
+    let session = fake.session();
+    let default_location = session.get_default_location().unwrap();
+    assert_eq!(default_location, fake.default_location());
+
+    let location = default_location.create_location("Sub".into()).unwrap();
+    let element = location.create_element("File".into()).unwrap();
+    assert_eq!(element.get_parent().unwrap(), location);
+    assert_eq!(location.get_elements(0, 0).unwrap(), vec![element.clone()]);
+    assert!(location.get_elements(0, 1).is_err());
+
+    element.set_url("chunk://file".into()).unwrap();
+    element.set_enabled(true).unwrap();
+    let stored = fake.element(element.uid).unwrap();
+    assert_eq!(stored.read().unwrap().url, "chunk://file");
+    assert!(stored.read().unwrap().enabled);
+
+    assert_eq!(element.write(b"data").unwrap(), 4);
+    assert_eq!(element.read(2).unwrap(), b"da");
+    assert_eq!(element.remaining().unwrap(), 2);
+
+    element
+        .notify(location.uid, Event::Custom("Hi".into()))
+        .unwrap();
+    assert!(matches!(
+        &location.events(true).unwrap()[..],
+        [Event::From(uid, event)] if *uid == element.uid && **event == Event::Custom("Hi".into())
+    ));
+    assert!(location.events(false).unwrap().is_empty());
+
+    assert_eq!(
+        fake.calls_to("element_set_enabled"),
+        vec![Call {
+            method: "element_set_enabled",
+            args: format!("({}, true)", element.uid),
+        }]
+    );
+    assert!(session.get_modules().unwrap().is_empty());
+    assert!(matches!(
+        session.find_module_by_name("HTTP".into()),
+        Err(SessionError::Custom(_))
+    ));
+
+    element.clone().destroy().unwrap();
+    assert_eq!(location.get_elements_len().unwrap(), 0);
+    assert!(element.get_name().is_err());
+
+    fake.clear_calls();
+    assert!(fake.calls().is_empty());
+}
@@ -0,0 +1,184 @@
+use std::{
+    io::Write,
+    sync::{Arc, RwLock},
+};
+
+use muzzman_lib::{prelude::*, Storage};
+
+use crate::*;
+
+/// Writes "Chunks" chunks, one every poll, then emits Completed with the session
+struct ChunkModule;
+
+impl TModule for ChunkModule {
+    fn name(&self) -> &str {
+        "Chunk"
+    }
+
+    fn desc(&self) -> &str {
+        "Writes chunks"
+    }
+
+    fn id(&self) -> u64 {
+        1
+    }
+
+    fn version(&self) -> u64 {
+        1
+    }
+
+    fn supported_versions(&self) -> &'static [u64] {
+        &[ABI_VERSION]
+    }
+
+    fn poll_element(
+        &self,
+        ctx: &mut std::task::Context<'_>,
+        element: Arc<RwLock<Element>>,
+        storage: &mut Storage,
+        context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        if storage.get::<u64>(0).is_none() {
+            storage.push(0u64);
+        }
+        let written = storage.get_mut::<u64>(0).unwrap();
+        let (id, done) = {
+            let mut element = element.write().unwrap();
+            if element.url.contains("fail") {
+                return Err(SessionError::Custom("Cannot write".into()));
+            }
+            let chunks = element.settings.get("Chunks").unwrap().value.as_u64();
+            element.statuses = vec!["Writing".into(), "Done".into()];
+            element.stream.write_all(format!("{written};").as_bytes())?;
+            *written += 1;
+            let done = Some(*written) == chunks;
+            if done {
+                element.status = 1;
+                element.is_completed = true;
+            }
+            (element.id.clone(), done)
+        };
+        if done {
+            id.emit(Event::Completed(id.uid))?;
+            context.set_data(id.uid, "Chunks", *written);
+        } else {
+            ctx.waker().wake_by_ref();
+        }
+        Ok(())
+    }
+
+    fn poll_location(
+        &self,
+        _ctx: &mut std::task::Context<'_>,
+        _location: Arc<RwLock<Location>>,
+        _storage: &mut Storage,
+        _context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        Ok(())
+    }
+
+    fn element_on_event(
+        &self,
+        element: Arc<RwLock<Element>>,
+        event: Event,
+        _storage: &mut Storage,
+        _context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        if let Event::Custom(name) = event {
+            element.write().unwrap().desc = name;
+        }
+        Ok(())
+    }
+
+    fn location_on_event(
+        &self,
+        _location: Arc<RwLock<Location>>,
+        _event: Event,
+        _storage: &mut Storage,
+        _context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        Ok(())
+    }
+
+    fn default_element_settings(&self) -> Settings {
+        let mut settings = Settings::default();
+        settings.add("Chunks", Setting::new(3u64, Vec::<u64>::new(), ""));
+        settings
+    }
+
+    fn default_location_settings(&self) -> Settings {
+        Settings::default()
+    }
+
+    fn supports_protocols(&self) -> &[&'static str] {
+        &["chunk"]
+    }
+
+    fn supports_extensions(&self) -> &[&'static str] {
+        &[]
+    }
+
+    fn element_actions(&self) -> Vec<Action> {
+        vec![Action::new(
+            "Rename",
+            "Changes the name",
+            vec![("Name".into(), "".into())],
+        )]
+    }
+
+    fn element_action(
+        &self,
+        element: Arc<RwLock<Element>>,
+        _name: &str,
+        args: Vec<Atom>,
+        _storage: &mut Storage,
+        _context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        let id = element.read().unwrap().id.clone();
+        id.set_name(args[0].to_string())
+    }
+}
+
+#[test]
+fn main() {
+    let mut harness = ModuleHarness::new(ChunkModule).unwrap();
+    let element = harness.element("Chunks", "chunk://three").unwrap();
+    assert!(element.read().unwrap().settings.get("Chunks").is_some());
+
+    let uid = element.read().unwrap().id.uid;
+    assert_eq!(harness.run_element(&element, 10).unwrap(), 3);
+    assert_completed(&element);
+    assert_status(&element, "Done");
+    assert_eq!(written(&element.read().unwrap()), b"0;1;2;");
+    assert_eq!(harness.waker().wakes(), 2);
+    assert_emitted(harness.session(), uid, &Event::Completed(uid));
+    assert_eq!(
+        harness.take_ops(),
+        vec![ContextOp::SetData(uid, "Chunks".into(), Atom::U(3))]
+    );
+
+    harness
+        .element_event(&element, Event::Custom("Seen".into()))
+        .unwrap();
+    assert_eq!(element.read().unwrap().desc, "Seen");
+
+    assert!(matches!(
+        harness.element_action(&element, "Rename", Vec::new()),
+        Err(SessionError::InvalidActionArgs(_))
+    ));
+    harness
+        .element_action(&element, "Rename", vec!["Renamed".into()])
+        .unwrap();
+    assert_eq!(element.read().unwrap().name, "Renamed");
+    assert_called(harness.session(), "set_name");
+
+    let failed = harness.element("Fail", "chunk://fail").unwrap();
+    assert!(harness.run_element(&failed, 10).is_err());
+    assert_error(&failed, "Cannot write");
+
+    // the storage is cleared, the module starts again
+    harness.detach_element(&element);
+    element.write().unwrap().is_completed = false;
+    harness.poll_element(&element).unwrap();
+    assert_eq!(written(&element.read().unwrap()), b"0;1;2;0;");
+}
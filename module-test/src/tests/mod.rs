@@ -0,0 +1,2 @@
+mod fake_session;
+mod harness;
@@ -0,0 +1,85 @@
+use std::io::Cursor;
+
+use muzzman_lib::prelude::*;
+
+/// An element that is not in a session, it writes in memory
+pub fn new_element(name: impl Into<String>, url: impl Into<String>) -> Element {
+    Element {
+        name: name.into(),
+        desc: Default::default(),
+        data: Default::default(),
+        settings: Default::default(),
+        path: Default::default(),
+        module: None,
+        proxy: None,
+        id: ElementId {
+            uid: 0,
+            session: None,
+        },
+        url: url.into(),
+        parent: LocationId {
+            uid: 0,
+            session: None,
+        },
+        stream: memory_stream(),
+        buffer: Vec::new(),
+        buffer_size: DEFAULT_BUFFER_SIZE,
+        status: 0,
+        statuses: Vec::new(),
+        progress: 0.0,
+        download_speed: 0,
+        upload_speed: 0,
+        download_speed_counter: 0,
+        upload_speed_counter: 0,
+        total_download: 0,
+        total_upload: 0,
+        enabled: false,
+        is_error: false,
+        is_completed: false,
+    }
+}
+
+/// A location that is not in a session
+pub fn new_location(name: impl Into<String>) -> Location {
+    Location {
+        name: name.into(),
+        desc: Default::default(),
+        data: Default::default(),
+        path: Default::default(),
+        settings: Default::default(),
+        module: None,
+        id: LocationId {
+            uid: 0,
+            session: None,
+        },
+        parent: None,
+        locations: Vec::new(),
+        elements: Vec::new(),
+        buffer: Vec::new(),
+        buffer_size: DEFAULT_BUFFER_SIZE,
+        status: 0,
+        statuses: Vec::new(),
+        progress: 0.0,
+        download_speed: 0,
+        upload_speed: 0,
+        download_speed_counter: 0,
+        upload_speed_counter: 0,
+        total_download: 0,
+        total_upload: 0,
+        enabled: false,
+        is_error: false,
+        is_completed: false,
+    }
+}
+
+pub fn memory_stream() -> Stream {
+    Stream::Memory(Cursor::new(Vec::new()))
+}
+
+/// What the module wrote in the element, empty if the stream is not in memory
+pub fn written(element: &Element) -> Vec<u8> {
+    match &element.stream {
+        Stream::Memory(cursor) => cursor.get_ref().clone(),
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,44 @@
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    task::{Wake, Waker},
+    time::Duration,
+};
+
+/// Counts the wakes of the module, the harness decides when to poll again
+#[derive(Default)]
+pub struct ManualWaker {
+    wakes: Mutex<usize>,
+    woken: Condvar,
+}
+
+impl ManualWaker {
+    pub fn waker(self: &Arc<Self>) -> Waker {
+        Waker::from(self.clone())
+    }
+
+    /// How many times the module woke the waker
+    pub fn wakes(&self) -> usize {
+        *self.wakes.lock().unwrap()
+    }
+
+    /// Waits at most `timeout` for a wake after `seen` wakes, returns the wakes
+    pub fn wait(&self, seen: usize, timeout: Duration) -> usize {
+        let wakes = self.wakes.lock().unwrap();
+        let (wakes, _) = self
+            .woken
+            .wait_timeout_while(wakes, timeout, |wakes| *wakes == seen)
+            .unwrap();
+        *wakes
+    }
+}
+
+impl Wake for ManualWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        *self.wakes.lock().unwrap() += 1;
+        self.woken.notify_all();
+    }
+}
@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::Atom;
+
+/// A module specific operation that a client can run on an element or a location
+/// like "Refresh link", "Verify" or "Open in browser"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Action {
+    pub name: String,
+    pub desc: String,
+    /// The name and the default value of every argument
+    /// the value given for an argument should have the same Atom type as the default
+    pub args: Vec<(String, Atom)>,
+}
+
+impl Action {
+    pub fn new(
+        name: impl Into<String>,
+        desc: impl Into<String>,
+        args: Vec<(String, Atom)>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            desc: desc.into(),
+            args,
+        }
+    }
+
+    /// If `args` have the same count and types as the action arguments
+    pub fn validate(&self, args: &[Atom]) -> bool {
+        self.args.len() == args.len()
+            && self.args.iter().zip(args).all(|((_, default), arg)| {
+                std::mem::discriminant(default) == std::mem::discriminant(arg)
+            })
+    }
+}
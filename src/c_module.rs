@@ -0,0 +1,746 @@
+//! A C abi for modules, so they can be built with any compiler and muzzman-lib version
+//! The module library exports `extern "C" fn muzzman_module() -> *const CModuleVTable`
+//!
+//! A C module can only poll, get events and give its default settings,
+//! there are no entries for the lifecycle hooks, actions, permissions or ModuleContext yet,
+//! so it uses the defaults of TModule: no hooks, no actions and no permissions until the user gives them
+//!
+//! A panic in a host function does not unwind into the module, the call fails after the module returns
+
+use std::{
+    any::Any,
+    ffi::c_void,
+    io::{Read, Write},
+    panic::AssertUnwindSafe,
+    sync::{Arc, RwLock},
+    task::Waker,
+};
+
+use crate::{prelude::*, storage::Storage};
+
+/// Changes every time CModuleVTable or CHost changes
+/// A module is only loaded if it was built with the same version
+pub const C_ABI_VERSION: u64 = 1;
+
+pub const C_OK: i32 = 0;
+pub const C_ERROR: i32 = -1;
+
+/// Borrowed bytes or utf8 string
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct CSlice {
+    pub ptr: *const u8,
+    pub len: usize,
+}
+
+unsafe impl Send for CSlice {}
+unsafe impl Sync for CSlice {}
+
+impl CSlice {
+    pub const EMPTY: Self = Self {
+        ptr: std::ptr::null(),
+        len: 0,
+    };
+
+    pub const fn new(str: &str) -> Self {
+        Self::from_bytes(str.as_bytes())
+    }
+
+    pub const fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            ptr: bytes.as_ptr(),
+            len: bytes.len(),
+        }
+    }
+
+    /// # Safety
+    /// `ptr` should be valid for `len` bytes while 'a
+    pub unsafe fn as_bytes<'a>(&self) -> &'a [u8] {
+        if self.ptr.is_null() {
+            &[]
+        } else {
+            std::slice::from_raw_parts(self.ptr, self.len)
+        }
+    }
+
+    /// Invalid utf8 is replaced
+    ///
+    /// # Safety
+    /// `ptr` should be valid for `len` bytes
+    pub unsafe fn to_string_lossy(&self) -> String {
+        String::from_utf8_lossy(self.as_bytes()).to_string()
+    }
+}
+
+#[repr(C, u8)]
+#[derive(Clone, Copy, Debug)]
+pub enum CAtom {
+    B(bool),
+    I(i64),
+    U(u64),
+    F(f64),
+    S(CSlice),
+}
+
+impl CAtom {
+    /// Borrows the string from `atom`
+    pub fn new(atom: &Atom) -> Self {
+        match atom {
+            Atom::B(v) => Self::B(*v),
+            Atom::I(v) => Self::I(*v),
+            Atom::U(v) => Self::U(*v),
+            Atom::F(v) => Self::F(*v),
+            Atom::S(v) => Self::S(CSlice::new(v)),
+        }
+    }
+
+    /// # Safety
+    /// If is `CAtom::S` the slice should be valid
+    pub unsafe fn to_atom(&self) -> Atom {
+        match self {
+            Self::B(v) => Atom::B(*v),
+            Self::I(v) => Atom::I(*v),
+            Self::U(v) => Atom::U(*v),
+            Self::F(v) => Atom::F(*v),
+            Self::S(v) => Atom::S(v.to_string_lossy()),
+        }
+    }
+}
+
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CEventKind {
+    NewData,
+    ProgressChanged,
+    Completed,
+    Error,
+    Custom,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct CEvent {
+    pub kind: CEventKind,
+    /// For ProgressChanged, Completed and Error
+    pub uid: u64,
+    /// For NewData and Custom
+    pub data: CSlice,
+    /// If the event was received from other element or location
+    pub has_from: bool,
+    pub from: u64,
+}
+
+impl CEvent {
+    /// Borrows the data from `event`
+    pub fn new(event: &Event) -> Self {
+        let event_with = |kind, uid, data| Self {
+            kind,
+            uid,
+            data,
+            has_from: false,
+            from: 0,
+        };
+        match event {
+            Event::NewData(data) => event_with(CEventKind::NewData, 0, CSlice::from_bytes(data)),
+            Event::ProgressChanged(uid) => {
+                event_with(CEventKind::ProgressChanged, *uid, CSlice::EMPTY)
+            }
+            Event::Completed(uid) => event_with(CEventKind::Completed, *uid, CSlice::EMPTY),
+            Event::Error(uid) => event_with(CEventKind::Error, *uid, CSlice::EMPTY),
+            Event::Custom(data) => event_with(CEventKind::Custom, 0, CSlice::new(data)),
+            Event::From(from, event) => {
+                let mut event = Self::new(event);
+                if !event.has_from {
+                    event.has_from = true;
+                    event.from = *from;
+                }
+                event
+            }
+        }
+    }
+}
+
+/// The session functions that a module can call
+/// `handle` is the element or location that the module was called with, and is valid only in that call
+/// A returned CSlice is valid until the next call with the same handle
+#[repr(C)]
+pub struct CHost {
+    pub abi_version: u64,
+
+    /// Adds a setting in `settings` from default_element_settings or default_location_settings
+    pub add_setting: extern "C" fn(
+        settings: *mut c_void,
+        name: CSlice,
+        default: CAtom,
+        variants: *const CAtom,
+        variants_len: usize,
+        desc: CSlice,
+    ),
+
+    /// Empty for a location
+    pub url: extern "C" fn(handle: *mut c_void) -> CSlice,
+    pub path: extern "C" fn(handle: *mut c_void) -> CSlice,
+    /// Returns false if there is no setting with the name
+    pub setting: extern "C" fn(handle: *mut c_void, name: CSlice, out: *mut CAtom) -> bool,
+    /// Returns false if there is no data with the name
+    pub data: extern "C" fn(handle: *mut c_void, name: CSlice, out: *mut CAtom) -> bool,
+    pub set_data: extern "C" fn(handle: *mut c_void, name: CSlice, value: CAtom),
+
+    pub set_status: extern "C" fn(handle: *mut c_void, status: CSlice),
+    pub set_progress: extern "C" fn(handle: *mut c_void, progress: f32),
+    pub set_completed: extern "C" fn(handle: *mut c_void),
+    /// The message for the C_ERROR returned from the current call
+    pub set_error: extern "C" fn(handle: *mut c_void, message: CSlice),
+
+    /// Writes all the data in the element stream and counts it as downloaded
+    /// Returns C_OK or C_ERROR
+    pub write: extern "C" fn(handle: *mut c_void, data: CSlice) -> i32,
+    /// Reads from the element stream
+    /// Returns how many bytes were read or C_ERROR
+    pub read: extern "C" fn(handle: *mut c_void, buf: *mut u8, len: usize) -> i64,
+
+    /// Null if was not set
+    pub user_data: extern "C" fn(handle: *mut c_void) -> *mut c_void,
+    /// The old user data is dropped, `drop` is called when the element or location is dropped
+    pub set_user_data: extern "C" fn(
+        handle: *mut c_void,
+        data: *mut c_void,
+        drop: Option<extern "C" fn(*mut c_void)>,
+    ),
+
+    /// A waker that can be used from any thread after the call
+    /// Should be consumed by wake or drop_waker, null if the call has no waker
+    pub waker: extern "C" fn(handle: *mut c_void) -> *mut c_void,
+    pub wake: extern "C" fn(waker: *mut c_void),
+    pub drop_waker: extern "C" fn(waker: *mut c_void),
+}
+
+pub static C_HOST: CHost = CHost {
+    abi_version: C_ABI_VERSION,
+    add_setting: host_add_setting,
+    url: host_url,
+    path: host_path,
+    setting: host_setting,
+    data: host_data,
+    set_data: host_set_data,
+    set_status: host_set_status,
+    set_progress: host_set_progress,
+    set_completed: host_set_completed,
+    set_error: host_set_error,
+    write: host_write,
+    read: host_read,
+    user_data: host_user_data,
+    set_user_data: host_set_user_data,
+    waker: host_waker,
+    wake: host_wake,
+    drop_waker: host_drop_waker,
+};
+
+/// Exported by the module library from `muzzman_module`
+/// Everything should live as long as the library is loaded
+#[repr(C)]
+pub struct CModuleVTable {
+    /// Should be C_ABI_VERSION
+    pub abi_version: u64,
+
+    pub name: CSlice,
+    pub desc: CSlice,
+    pub id: u64,
+    pub version: u64,
+    pub supported_versions: *const u64,
+    pub supported_versions_len: usize,
+
+    /// Should be like "http, https"
+    pub protocols: *const CSlice,
+    pub protocols_len: usize,
+    /// Should be like "html, exe"
+    pub extensions: *const CSlice,
+    pub extensions_len: usize,
+
+    /// Should add the settings with CHost::add_setting
+    pub default_element_settings: extern "C" fn(host: *const CHost, settings: *mut c_void),
+    pub default_location_settings: extern "C" fn(host: *const CHost, settings: *mut c_void),
+
+    /// Returns C_OK or C_ERROR
+    pub poll_element: extern "C" fn(host: *const CHost, handle: *mut c_void) -> i32,
+    /// Returns C_OK or C_ERROR
+    pub poll_location: extern "C" fn(host: *const CHost, handle: *mut c_void) -> i32,
+
+    /// Returns C_OK or C_ERROR
+    pub element_on_event:
+        extern "C" fn(host: *const CHost, handle: *mut c_void, event: *const CEvent) -> i32,
+    /// Returns C_OK or C_ERROR
+    pub location_on_event:
+        extern "C" fn(host: *const CHost, handle: *mut c_void, event: *const CEvent) -> i32,
+}
+
+unsafe impl Send for CModuleVTable {}
+unsafe impl Sync for CModuleVTable {}
+
+/// Adapts a CModuleVTable to TModule
+pub struct CModule {
+    vtable: &'static CModuleVTable,
+    name: String,
+    desc: String,
+    protocols: Vec<&'static str>,
+    extensions: Vec<&'static str>,
+    /// Keeps the library loaded
+    _owner: Option<Box<dyn Any + Send + Sync + std::panic::UnwindSafe>>,
+}
+
+impl CModule {
+    /// # Safety
+    /// `vtable` and everything that it points to should live as long as `owner`
+    /// or forever if there is no owner
+    pub unsafe fn new(
+        vtable: *const CModuleVTable,
+        owner: Option<Box<dyn Any + Send + Sync + std::panic::UnwindSafe>>,
+    ) -> Result<Self, RawLibraryError> {
+        let Some(vtable) = vtable.as_ref() else {
+            return Err(RawLibraryError::NotFound);
+        };
+        if vtable.abi_version != C_ABI_VERSION {
+            return Err(RawLibraryError::IncompatibleAbi(
+                C_ABI_VERSION,
+                vtable.abi_version,
+            ));
+        }
+
+        let strs = |ptr: *const CSlice, len: usize| -> Vec<&'static str> {
+            let slices: &[CSlice] = if ptr.is_null() {
+                &[]
+            } else {
+                std::slice::from_raw_parts(ptr, len)
+            };
+            slices
+                .iter()
+                .filter_map(|slice| std::str::from_utf8(slice.as_bytes()).ok())
+                .collect()
+        };
+
+        // only C_ABI_VERSION matters, ABI_VERSION is about the rust abi that the module does not use
+        Ok(Self {
+            vtable,
+            name: vtable.name.to_string_lossy(),
+            desc: vtable.desc.to_string_lossy(),
+            protocols: strs(vtable.protocols, vtable.protocols_len),
+            extensions: strs(vtable.extensions, vtable.extensions_len),
+            _owner: owner,
+        })
+    }
+
+    fn settings(&self, default_settings: extern "C" fn(*const CHost, *mut c_void)) -> Settings {
+        let mut settings = Settings::default();
+        default_settings(&C_HOST, &mut settings as *mut Settings as *mut c_void);
+        settings
+    }
+
+    fn call(
+        &self,
+        target: Target,
+        storage: &mut Storage,
+        waker: Option<&Waker>,
+        call: impl FnOnce(*mut c_void) -> i32,
+    ) -> SessionResult<()> {
+        let mut handle = Handle {
+            target,
+            storage,
+            waker,
+            scratch: Vec::new(),
+            error: None,
+            panic: None,
+        };
+        let res = call(&mut handle as *mut Handle as *mut c_void);
+        if let Some(panic) = handle.panic {
+            return Err(SessionError::Custom(format!(
+                "A host function panicked: {panic}"
+            )));
+        }
+        if res == C_OK {
+            Ok(())
+        } else {
+            Err(SessionError::Custom(handle.error.unwrap_or_else(|| {
+                format!("Module {} returned {res}", self.name)
+            })))
+        }
+    }
+}
+
+impl TModule for CModule {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn desc(&self) -> &str {
+        &self.desc
+    }
+
+    fn id(&self) -> u64 {
+        self.vtable.id
+    }
+
+    fn version(&self) -> u64 {
+        self.vtable.version
+    }
+
+    fn supported_versions(&self) -> &'static [u64] {
+        if self.vtable.supported_versions.is_null() {
+            return &[];
+        }
+        unsafe {
+            std::slice::from_raw_parts(
+                self.vtable.supported_versions,
+                self.vtable.supported_versions_len,
+            )
+        }
+    }
+
+    fn poll_element(
+        &self,
+        ctx: &mut std::task::Context<'_>,
+        element: Arc<RwLock<Element>>,
+        storage: &mut Storage,
+        _context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        self.call(
+            Target::Element(element),
+            storage,
+            Some(ctx.waker()),
+            |handle| (self.vtable.poll_element)(&C_HOST, handle),
+        )
+    }
+
+    fn poll_location(
+        &self,
+        ctx: &mut std::task::Context<'_>,
+        location: Arc<RwLock<Location>>,
+        storage: &mut Storage,
+        _context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        self.call(
+            Target::Location(location),
+            storage,
+            Some(ctx.waker()),
+            |handle| (self.vtable.poll_location)(&C_HOST, handle),
+        )
+    }
+
+    fn element_on_event(
+        &self,
+        element: Arc<RwLock<Element>>,
+        event: Event,
+        storage: &mut Storage,
+        _context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        let event = CEvent::new(&event);
+        self.call(Target::Element(element), storage, None, |handle| {
+            (self.vtable.element_on_event)(&C_HOST, handle, &event)
+        })
+    }
+
+    fn location_on_event(
+        &self,
+        location: Arc<RwLock<Location>>,
+        event: Event,
+        storage: &mut Storage,
+        _context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        let event = CEvent::new(&event);
+        self.call(Target::Location(location), storage, None, |handle| {
+            (self.vtable.location_on_event)(&C_HOST, handle, &event)
+        })
+    }
+
+    fn default_element_settings(&self) -> Settings {
+        self.settings(self.vtable.default_element_settings)
+    }
+
+    fn default_location_settings(&self) -> Settings {
+        self.settings(self.vtable.default_location_settings)
+    }
+
+    fn supports_protocols(&self) -> &[&'static str] {
+        &self.protocols
+    }
+
+    fn supports_extensions(&self) -> &[&'static str] {
+        &self.extensions
+    }
+}
+
+enum Target {
+    Element(Arc<RwLock<Element>>),
+    Location(Arc<RwLock<Location>>),
+}
+
+struct Handle<'a> {
+    target: Target,
+    storage: &'a mut Storage,
+    waker: Option<&'a Waker>,
+    /// Backs the returned CSlice
+    scratch: Vec<u8>,
+    error: Option<String>,
+    /// The message of the first host function that panicked in the call
+    panic: Option<String>,
+}
+
+impl Handle<'_> {
+    fn slice(&mut self, bytes: Vec<u8>) -> CSlice {
+        self.scratch = bytes;
+        CSlice::from_bytes(&self.scratch)
+    }
+}
+
+/// Runs `$body` with `$t` as the locked element or location
+macro_rules! with_target {
+    ($handle:expr, $t:ident => $body:expr) => {
+        match &$handle.target {
+            Target::Element(element) => {
+                #[allow(unused_mut)]
+                let mut $t = element.write().unwrap();
+                $body
+            }
+            Target::Location(location) => {
+                #[allow(unused_mut)]
+                let mut $t = location.write().unwrap();
+                $body
+            }
+        }
+    };
+}
+
+fn handle<'a, 'b>(handle: *mut c_void) -> &'a mut Handle<'b> {
+    unsafe { &mut *(handle as *mut Handle<'b>) }
+}
+
+/// A panic cannot unwind into the module
+fn no_unwind(f: impl FnOnce()) {
+    let _ = std::panic::catch_unwind(AssertUnwindSafe(f));
+}
+
+/// Runs `f` with the handle, a panic like from a poisoned lock is saved for CModule::call
+fn with_handle<T>(h: *mut c_void, error: T, f: impl FnOnce(&mut Handle) -> T) -> T {
+    let h = handle(h);
+    match std::panic::catch_unwind(AssertUnwindSafe(|| f(&mut *h))) {
+        Ok(res) => res,
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "Unknown panic".to_string());
+            h.panic.get_or_insert(message);
+            error
+        }
+    }
+}
+
+struct CUserData {
+    data: *mut c_void,
+    drop: Option<extern "C" fn(*mut c_void)>,
+}
+
+unsafe impl Send for CUserData {}
+unsafe impl Sync for CUserData {}
+
+impl Drop for CUserData {
+    fn drop(&mut self) {
+        if let Some(drop) = self.drop {
+            drop(self.data)
+        }
+    }
+}
+
+extern "C" fn host_add_setting(
+    settings: *mut c_void,
+    name: CSlice,
+    default: CAtom,
+    variants: *const CAtom,
+    variants_len: usize,
+    desc: CSlice,
+) {
+    no_unwind(|| {
+        let settings = unsafe { &mut *(settings as *mut Settings) };
+        let variants: &[CAtom] = if variants.is_null() {
+            &[]
+        } else {
+            unsafe { std::slice::from_raw_parts(variants, variants_len) }
+        };
+        unsafe {
+            settings.add(
+                name.to_string_lossy(),
+                Setting::new(
+                    default.to_atom(),
+                    variants.iter().map(|v| v.to_atom()).collect(),
+                    desc.to_string_lossy(),
+                ),
+            );
+        }
+    });
+}
+
+extern "C" fn host_url(h: *mut c_void) -> CSlice {
+    with_handle(h, CSlice::EMPTY, |h| {
+        let url = match &h.target {
+            Target::Element(element) => element.read().unwrap().url.clone(),
+            Target::Location(_) => String::new(),
+        };
+        h.slice(url.into_bytes())
+    })
+}
+
+extern "C" fn host_path(h: *mut c_void) -> CSlice {
+    with_handle(h, CSlice::EMPTY, |h| {
+        let path = with_target!(h, t => t.path.to_string_lossy().to_string());
+        h.slice(path.into_bytes())
+    })
+}
+
+fn atom_out(h: &mut Handle, atom: Option<Atom>, out: *mut CAtom) -> bool {
+    let Some(atom) = atom else {
+        return false;
+    };
+    let atom = match atom {
+        Atom::S(v) => CAtom::S(h.slice(v.into_bytes())),
+        atom => CAtom::new(&atom),
+    };
+    if let Some(out) = unsafe { out.as_mut() } {
+        *out = atom;
+    }
+    true
+}
+
+extern "C" fn host_setting(h: *mut c_void, name: CSlice, out: *mut CAtom) -> bool {
+    with_handle(h, false, |h| {
+        let name = unsafe { name.to_string_lossy() };
+        let atom = with_target!(h, t => t.settings.get(&name).map(|s| s.value.clone()));
+        atom_out(h, atom, out)
+    })
+}
+
+extern "C" fn host_data(h: *mut c_void, name: CSlice, out: *mut CAtom) -> bool {
+    with_handle(h, false, |h| {
+        let name = unsafe { name.to_string_lossy() };
+        let atom = with_target!(h, t => t.data.get(&name).cloned());
+        atom_out(h, atom, out)
+    })
+}
+
+extern "C" fn host_set_data(h: *mut c_void, name: CSlice, value: CAtom) {
+    with_handle(h, (), |h| {
+        let (name, value) = unsafe { (name.to_string_lossy(), value.to_atom()) };
+        with_target!(h, t => t.data.insert(name, value));
+    })
+}
+
+extern "C" fn host_set_status(h: *mut c_void, status: CSlice) {
+    with_handle(h, (), |h| {
+        let status = unsafe { status.to_string_lossy() };
+        with_target!(h, t => {
+            t.status = t.statuses.len();
+            t.statuses.push(status);
+        });
+    })
+}
+
+extern "C" fn host_set_progress(h: *mut c_void, progress: f32) {
+    with_handle(h, (), |h| with_target!(h, t => t.progress = progress))
+}
+
+extern "C" fn host_set_completed(h: *mut c_void) {
+    with_handle(h, (), |h| with_target!(h, t => t.is_completed = true))
+}
+
+extern "C" fn host_set_error(h: *mut c_void, message: CSlice) {
+    with_handle(h, (), |h| {
+        h.error = Some(unsafe { message.to_string_lossy() });
+    })
+}
+
+extern "C" fn host_write(h: *mut c_void, data: CSlice) -> i32 {
+    with_handle(h, C_ERROR, |h| {
+        let Target::Element(element) = &h.target else {
+            h.error = Some("A location does not have a stream".to_string());
+            return C_ERROR;
+        };
+        let data = unsafe { data.as_bytes() };
+        let mut element = element.write().unwrap();
+        if let Err(error) = element.stream.write_all(data) {
+            drop(element);
+            h.error = Some(error.to_string());
+            return C_ERROR;
+        }
+        element.total_download += data.len();
+        element.download_speed_counter += data.len();
+        C_OK
+    })
+}
+
+extern "C" fn host_read(h: *mut c_void, buf: *mut u8, len: usize) -> i64 {
+    with_handle(h, C_ERROR as i64, |h| {
+        let Target::Element(element) = &h.target else {
+            h.error = Some("A location does not have a stream".to_string());
+            return C_ERROR as i64;
+        };
+        if buf.is_null() {
+            return 0;
+        }
+        let buf = unsafe { std::slice::from_raw_parts_mut(buf, len) };
+        let res = element.write().unwrap().stream.read(buf);
+        match res {
+            Ok(read) => read as i64,
+            Err(error) => {
+                h.error = Some(error.to_string());
+                C_ERROR as i64
+            }
+        }
+    })
+}
+
+extern "C" fn host_user_data(h: *mut c_void) -> *mut c_void {
+    with_handle(h, std::ptr::null_mut(), |h| {
+        h.storage
+            .iter()
+            .find_map(|data| data.downcast_ref::<CUserData>())
+            .map_or(std::ptr::null_mut(), |data| data.data)
+    })
+}
+
+extern "C" fn host_set_user_data(
+    h: *mut c_void,
+    data: *mut c_void,
+    drop: Option<extern "C" fn(*mut c_void)>,
+) {
+    with_handle(h, (), |h| {
+        if let Some(index) = h
+            .storage
+            .iter()
+            .position(|data| data.downcast_ref::<CUserData>().is_some())
+        {
+            h.storage.remove(index);
+        }
+        h.storage.push(CUserData { data, drop });
+    })
+}
+
+extern "C" fn host_waker(h: *mut c_void) -> *mut c_void {
+    with_handle(h, std::ptr::null_mut(), |h| {
+        h.waker.map_or(std::ptr::null_mut(), |waker| {
+            Box::into_raw(Box::new(waker.clone())) as *mut c_void
+        })
+    })
+}
+
+extern "C" fn host_wake(waker: *mut c_void) {
+    if !waker.is_null() {
+        let waker = unsafe { Box::from_raw(waker as *mut Waker) };
+        no_unwind(|| waker.wake());
+    }
+}
+
+extern "C" fn host_drop_waker(waker: *mut c_void) {
+    if !waker.is_null() {
+        let waker = unsafe { Box::from_raw(waker as *mut Waker) };
+        no_unwind(|| drop(waker));
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::{Atom, ElementId, Event, LocationId, ModuleId, UID};
+
+/// Given to the module callbacks to use the session from them
+/// The operations are done by the session after the callback returns, when the session does not hold
+/// the locks of the element or location, so they cannot deadlock
+/// They run with the module permissions, if one fails the callback fails with the errors
+#[derive(Debug, Default)]
+pub struct ModuleContext {
+    ops: Vec<ContextOp>,
+}
+
+impl ModuleContext {
+    pub fn create_element(&mut self, location: &LocationId, element: NewElement) {
+        self.ops
+            .push(ContextOp::CreateElement(location.uid, element));
+    }
+
+    pub fn create_location(&mut self, location: &LocationId, new: NewLocation) {
+        self.ops.push(ContextOp::CreateLocation(location.uid, new));
+    }
+
+    pub fn set_enabled(&mut self, element: &ElementId, enabled: bool) {
+        self.ops.push(ContextOp::SetEnabled(element.uid, enabled));
+    }
+
+    pub fn set_url(&mut self, element: &ElementId, url: impl Into<String>) {
+        self.ops.push(ContextOp::SetUrl(element.uid, url.into()));
+    }
+
+    /// Changes the module of the element, None detaches it
+    /// Fails with ElementIsEnabled if the element is enabled
+    pub fn set_module(&mut self, element: &ElementId, module: Option<&ModuleId>) {
+        self.ops.push(ContextOp::SetModule(
+            element.uid,
+            module.map(|module| module.uid),
+        ));
+    }
+
+    /// Gives the element to the proxy module until it completes, None ends the proxy
+    pub fn set_proxy(&mut self, element: &ElementId, proxy: Option<&ModuleId>) {
+        self.ops.push(ContextOp::SetProxy(
+            element.uid,
+            proxy.map(|proxy| proxy.uid),
+        ));
+    }
+
+    /// Changes the value of a setting of an element or location, adds it if it does not exist
+    pub fn set_setting(&mut self, uid: UID, name: impl Into<String>, value: impl Into<Atom>) {
+        self.ops
+            .push(ContextOp::SetSetting(uid, name.into(), value.into()));
+    }
+
+    pub fn set_data(&mut self, uid: UID, name: impl Into<String>, value: impl Into<Atom>) {
+        self.ops
+            .push(ContextOp::SetData(uid, name.into(), value.into()));
+    }
+
+    pub fn emit(&mut self, uid: UID, event: Event) {
+        self.ops.push(ContextOp::Emit(uid, event));
+    }
+
+    /// Writes to the data channel of the element or location, like TSessionCommon::write
+    /// What does not fit in the buffer size is not written
+    pub fn write(&mut self, uid: UID, data: impl Into<Vec<u8>>) {
+        self.ops.push(ContextOp::Write(uid, data.into()));
+    }
+
+    /// The operations in the order that they were added
+    pub fn take(&mut self) -> Vec<ContextOp> {
+        std::mem::take(&mut self.ops)
+    }
+
+    pub fn extend(&mut self, ops: Vec<ContextOp>) {
+        self.ops.extend(ops)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ContextOp {
+    /// In the location
+    CreateElement(UID, NewElement),
+    /// In the location
+    CreateLocation(UID, NewLocation),
+    SetEnabled(UID, bool),
+    SetUrl(UID, String),
+    /// The element and the uid of the module
+    SetModule(UID, Option<UID>),
+    /// The element and the uid of the proxy module
+    SetProxy(UID, Option<UID>),
+    SetSetting(UID, String, Atom),
+    SetData(UID, String, Atom),
+    Emit(UID, Event),
+    Write(UID, Vec<u8>),
+}
+
+/// An element created by ModuleContext::create_element
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NewElement {
+    pub name: String,
+    pub url: String,
+    /// The uid of the module, if None the module is selected from the url
+    pub module: Option<UID>,
+    /// Set after the element gets the module settings
+    pub settings: HashMap<String, Atom>,
+    pub data: HashMap<String, Atom>,
+    /// Enabled after everything else is set
+    pub enabled: bool,
+}
+
+/// A location created by ModuleContext::create_location, with the elements and locations inside
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NewLocation {
+    pub name: String,
+    pub settings: HashMap<String, Atom>,
+    pub elements: Vec<NewElement>,
+    pub locations: Vec<NewLocation>,
+}
@@ -2,7 +2,7 @@ use std::{collections::HashMap, path::PathBuf};
 
 use crate::prelude::*;
 
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub struct Element {
     pub name: String,
     pub desc: String,
@@ -10,6 +10,8 @@ pub struct Element {
     pub settings: Settings,
     pub path: PathBuf,
     pub module: Option<ModuleId>,
+    /// The module that does the transfer for `module`, see TSessionElement::element_set_proxy
+    pub proxy: Option<ModuleId>,
     pub id: ElementId,
 
     pub url: String,
@@ -20,6 +22,8 @@ pub struct Element {
 
     /// From this will be readed when TSessionCommon::read
     pub buffer: Vec<u8>,
+    /// The max len of buffer, TSessionCommon::write will not write more than this
+    pub buffer_size: usize,
 
     pub status: usize,
     pub statuses: Vec<String>,
@@ -41,8 +45,51 @@ pub struct Element {
     pub is_completed: bool,
 }
 
+/// The stream is not cloned, the clone has Stream::None, use Stream::try_clone for it
+impl Clone for Element {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            desc: self.desc.clone(),
+            data: self.data.clone(),
+            settings: self.settings.clone(),
+            path: self.path.clone(),
+            module: self.module.clone(),
+            proxy: self.proxy.clone(),
+            id: self.id.clone(),
+            url: self.url.clone(),
+            parent: self.parent.clone(),
+            stream: Stream::None,
+            buffer: self.buffer.clone(),
+            buffer_size: self.buffer_size,
+            status: self.status,
+            statuses: self.statuses.clone(),
+            progress: self.progress,
+            download_speed: self.download_speed,
+            upload_speed: self.upload_speed,
+            download_speed_counter: self.download_speed_counter,
+            upload_speed_counter: self.upload_speed_counter,
+            total_download: self.total_download,
+            total_upload: self.total_upload,
+            enabled: self.enabled,
+            is_error: self.is_error,
+            is_completed: self.is_completed,
+        }
+    }
+}
+
 impl Element {
     pub fn get_session(&self) -> Option<Session> {
         self.id.session.clone()
     }
 }
+
+/// Element data keys that the session understands
+pub mod element_data {
+    /// Atom::U, the expected size in bytes, should be set by the module when it knows it
+    pub const SIZE: &str = "Size";
+    /// Atom::U, how many bytes of the partial file are valid
+    pub const PARTIAL_LENGTH: &str = "Session.PartialLength";
+    /// Atom::S, the digest in hex computed with Session.ChecksumAlgorithm when the element completed
+    pub const CHECKSUM: &str = "Session.Checksum";
+}
@@ -1,4 +1,4 @@
-use crate::prelude::RawLibraryError;
+use crate::prelude::{RawLibraryError, UID};
 
 #[derive(Clone, Debug)]
 pub enum SessionError {
@@ -18,14 +18,33 @@ pub enum SessionError {
     InvalidStatus,
     InvalidSettings(Vec<String>),
 
+    /// The module does not have an action with this name
+    NoAction(String),
+    /// The arguments are not valid for the action with this name
+    InvalidActionArgs(String),
+    /// An element cannot be proxied to its own module
+    ProxyToItself,
+
     ThereAreLessLocations,
     ThereAreLessElements,
 
     NoPermission,
     IsRoot,
 
+    /// Needed bytes, available bytes on the filesystem
+    NotEnoughSpace(u64, u64),
+    /// Location quota, bytes that would be used
+    QuotaExceeded(u64, u64),
+    /// Expected digest, computed digest
+    ChecksumMismatch(String, String),
+    /// The path is already used by a file or by other element
+    PathCollision(std::path::PathBuf),
+    /// The element has to be disabled first
+    ElementIsEnabled,
+
     Errors(Vec<SessionError>),
     Custom(String),
+    Io(String),
 
     // Common
     GetName(Box<SessionError>),
@@ -53,6 +72,7 @@ pub enum SessionError {
 
     // Element
     CreateElement(Box<SessionError>),
+    CreateElementFromUrl(Box<SessionError>),
     GetElement(Box<SessionError>),
     MoveElement(Box<SessionError>),
     ElementPath(Box<SessionError>),
@@ -94,6 +114,12 @@ pub enum SessionError {
     ElementGetModule(Box<SessionError>),
     ElementSetModule(Box<SessionError>),
 
+    ElementGetProxy(Box<SessionError>),
+    ElementSetProxy(Box<SessionError>),
+
+    ElementGetActions(Box<SessionError>),
+    ElementRunAction(Box<SessionError>),
+
     ElementWait(Box<SessionError>),
 
     DestroyElement(Box<SessionError>),
@@ -143,8 +169,50 @@ pub enum SessionError {
     LocationGetModule(Box<SessionError>),
     LocationSetModule(Box<SessionError>),
 
+    LocationGetActions(Box<SessionError>),
+    LocationRunAction(Box<SessionError>),
+
     MoveLocation(Box<SessionError>),
     LocationPath(Box<SessionError>),
 
     DestroyLocation(Box<SessionError>),
+
+    // Module
+    /// The uid of the module that has the same TModule::id
+    ModuleAlreadyAdded(UID),
+    /// Module id, new module id
+    ModuleIdMismatch(u64, u64),
+
+    AddModule(Box<SessionError>),
+    GetModule(Box<SessionError>),
+    GetModules(Box<SessionError>),
+    GetModulesInfo(Box<SessionError>),
+    FindModuleById(Box<SessionError>),
+    FindModuleByName(Box<SessionError>),
+
+    ModuleInfo(Box<SessionError>),
+
+    ModuleGetElementSettings(Box<SessionError>),
+    ModuleSetElementSettings(Box<SessionError>),
+
+    ModuleGetLocationSettings(Box<SessionError>),
+    ModuleSetLocationSettings(Box<SessionError>),
+
+    ModuleSupportsProtocols(Box<SessionError>),
+    ModuleSupportsExtensions(Box<SessionError>),
+
+    ModulePath(Box<SessionError>),
+    ModuleId(Box<SessionError>),
+    ModuleGetPermissions(Box<SessionError>),
+    ModuleSetPermissions(Box<SessionError>),
+    ModuleReload(Box<SessionError>),
+
+    DestroyModule(Box<SessionError>),
+    LoadModules(Box<SessionError>),
+}
+
+impl From<std::io::Error> for SessionError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value.to_string())
+    }
 }
@@ -63,6 +63,12 @@ pub trait TElementHelper: TCommonHelper {
     fn get_module(&self) -> SessionResult<Option<ModuleId>>;
     fn set_module(&self, module_id: Option<ModuleId>) -> SessionResult<()>;
 
+    fn get_proxy(&self) -> SessionResult<Option<ModuleId>>;
+    fn set_proxy(&self, proxy: Option<ModuleId>) -> SessionResult<()>;
+
+    fn get_actions(&self) -> SessionResult<Vec<Action>>;
+    fn run_action(&self, name: String, args: Vec<Atom>) -> SessionResult<()>;
+
     fn wait(&self) -> SessionResult<()>;
 
     fn destroy(self) -> SessionResult<()>;
@@ -181,6 +187,23 @@ impl TElementHelper for ElementId {
             .element_set_module(self.clone(), module_id)
     }
 
+    fn get_proxy(&self) -> SessionResult<Option<ModuleId>> {
+        self.get_session()?.element_get_proxy(self.clone())
+    }
+
+    fn set_proxy(&self, proxy: Option<ModuleId>) -> SessionResult<()> {
+        self.get_session()?.element_set_proxy(self.clone(), proxy)
+    }
+
+    fn get_actions(&self) -> SessionResult<Vec<Action>> {
+        self.get_session()?.element_get_actions(self.clone())
+    }
+
+    fn run_action(&self, name: String, args: Vec<Atom>) -> SessionResult<()> {
+        self.get_session()?
+            .element_run_action(self.clone(), name, args)
+    }
+
     fn wait(&self) -> SessionResult<()> {
         self.get_session()?.element_wait(self.clone())
     }
@@ -25,6 +25,7 @@ impl LocationId {
 pub trait TLocationHelper: TCommonHelper {
     fn create_location(&self, name: String) -> SessionResult<LocationId>;
     fn create_element(&self, name: String) -> SessionResult<ElementId>;
+    fn create_element_from_url(&self, url: String) -> SessionResult<ElementId>;
 
     fn get_parent(&self) -> SessionResult<LocationId>;
 
@@ -70,6 +71,9 @@ pub trait TLocationHelper: TCommonHelper {
     fn get_module(&self) -> SessionResult<Option<ModuleId>>;
     fn set_module(&self, module_id: Option<ModuleId>) -> SessionResult<()>;
 
+    fn get_actions(&self) -> SessionResult<Vec<Action>>;
+    fn run_action(&self, name: String, args: Vec<Atom>) -> SessionResult<()>;
+
     fn _move(&self, to: LocationId) -> SessionResult<()>;
     fn path(&self) -> SessionResult<Vec<usize>>;
 
@@ -87,6 +91,11 @@ impl TLocationHelper for LocationId {
         self.get_session()?.create_element(self.clone(), name)
     }
 
+    fn create_element_from_url(&self, url: String) -> SessionResult<ElementId> {
+        self.get_session()?
+            .create_element_from_url(self.clone(), url)
+    }
+
     fn get_parent(&self) -> SessionResult<LocationId> {
         self.get_session()?.location_get_parent(self.clone())
     }
@@ -204,6 +213,15 @@ impl TLocationHelper for LocationId {
             .location_set_module(self.clone(), module_id)
     }
 
+    fn get_actions(&self) -> SessionResult<Vec<Action>> {
+        self.get_session()?.location_get_actions(self.clone())
+    }
+
+    fn run_action(&self, name: String, args: Vec<Atom>) -> SessionResult<()> {
+        self.get_session()?
+            .location_run_action(self.clone(), name, args)
+    }
+
     fn wait(&self) -> SessionResult<()> {
         self.get_session()?.location_wait(self.clone())
     }
@@ -35,6 +35,12 @@ pub trait TModuleHelper: TCommonHelper {
     fn path(&self) -> SessionResult<usize>;
 
     fn id(&self) -> SessionResult<u64>;
+    fn info(&self) -> SessionResult<ModuleInfo>;
+
+    fn get_permissions(&self) -> SessionResult<Permissions>;
+    fn set_permissions(&self, permissions: Permissions) -> SessionResult<()>;
+
+    fn reload(&self, source: ModuleSource) -> SessionResult<()>;
     fn destroy(self) -> SessionResult<()>;
 }
 
@@ -75,6 +81,23 @@ impl TModuleHelper for ModuleId {
         self.get_session()?.module_id(self.clone())
     }
 
+    fn info(&self) -> SessionResult<ModuleInfo> {
+        self.get_session()?.module_info(self.clone())
+    }
+
+    fn get_permissions(&self) -> SessionResult<Permissions> {
+        self.get_session()?.module_get_permissions(self.clone())
+    }
+
+    fn set_permissions(&self, permissions: Permissions) -> SessionResult<()> {
+        self.get_session()?
+            .module_set_permissions(self.clone(), permissions)
+    }
+
+    fn reload(&self, source: ModuleSource) -> SessionResult<()> {
+        self.get_session()?.module_reload(self.clone(), source)
+    }
+
     fn destroy(self) -> SessionResult<()> {
         self.get_session()?.destroy_module(self)
     }
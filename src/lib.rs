@@ -1,9 +1,14 @@
+mod action;
+mod c_module;
+mod context;
 mod element;
 mod error;
 mod helper;
 mod location;
 pub mod logger;
 mod module;
+mod permissions;
+mod segmented;
 mod session;
 mod session_common;
 mod session_element;
@@ -19,8 +24,9 @@ pub extern crate muzzman_lib_macros;
 
 pub mod prelude {
     pub use crate::{
-        element::*, error::*, helper::*, location::*, module::*, muzzman_lib_macros::module_link,
-        session::*, session_common::TSessionCommon, session_element::TSessionElement,
+        action::*, c_module::*, context::*, element::*, error::*, helper::*, location::*,
+        module::*, muzzman_lib_macros::module_link, permissions::*, segmented::*, session::*,
+        session_common::TSessionCommon, session_element::TSessionElement,
         session_location::TSessionLocation, session_module::TSessionModule, settings::*, types::*,
     };
 }
@@ -19,6 +19,8 @@ pub struct Location {
 
     /// From this will be readed when TSessionCommon::read
     pub buffer: Vec<u8>,
+    /// The max len of buffer, TSessionCommon::write will not write more than this
+    pub buffer_size: usize,
 
     pub status: usize,
     pub statuses: Vec<String>,
@@ -7,7 +7,7 @@ use std::{
 };
 
 thread_local! {
-    static WHO_IAM: RwLock<Iam> = RwLock::new(Iam::MuzzManLib);
+    static WHO_IAM: RwLock<Iam> = const { RwLock::new(Iam::MuzzManLib) };
 }
 
 #[no_mangle]
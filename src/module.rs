@@ -8,18 +8,82 @@ use serde::{Deserialize, Serialize};
 
 use crate::{prelude::*, storage::Storage};
 
+/// Changes every time the interface between a session and a dynamic module changes
+pub const ABI_VERSION: u64 = 2;
+/// The muzzman-lib version and the compiler that built it, nul terminated
+/// A dynamic module is loaded only if it was built with the same
+pub const BUILD_ID: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " ",
+    env!("MUZZMAN_RUSTC_VERSION"),
+    "\0"
+);
+
+/// Exported by `module_link` as `MUZZMAN_MANIFEST`, it is data so a loader can read it without calling the module
+/// `abi_version` is always first, the other fields are read only if it is the host ABI_VERSION
+#[repr(C)]
+pub struct RawModuleManifest {
+    pub abi_version: u64,
+    /// The strings are nul terminated and live as long as the library is loaded
+    pub build_id: *const std::ffi::c_char,
+    /// The package of the module from Cargo.toml
+    pub package_name: *const std::ffi::c_char,
+    pub package_version: *const std::ffi::c_char,
+    /// The type that implements TModule
+    pub module_type: *const std::ffi::c_char,
+    /// The module was built with debug assertions
+    pub debug: bool,
+}
+
+unsafe impl Sync for RawModuleManifest {}
+
+/// What was known about a dynamic module when it was built
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModuleManifest {
+    pub abi_version: u64,
+    pub build_id: String,
+    pub package_name: String,
+    pub package_version: String,
+    pub module_type: String,
+    pub debug: bool,
+}
+
+impl ModuleManifest {
+    /// # Safety
+    /// The strings of `raw` should be valid nul terminated strings
+    pub unsafe fn from_raw(raw: &RawModuleManifest) -> Self {
+        let string = |ptr: *const std::ffi::c_char| {
+            std::ffi::CStr::from_ptr(ptr).to_string_lossy().to_string()
+        };
+        Self {
+            abi_version: raw.abi_version,
+            build_id: string(raw.build_id),
+            package_name: string(raw.package_name),
+            package_version: string(raw.package_version),
+            module_type: string(raw.module_type),
+            debug: raw.debug,
+        }
+    }
+}
+
 pub trait TModule: std::panic::UnwindSafe + Sync + Send {
     fn name(&self) -> &str;
     fn desc(&self) -> &str;
     fn id(&self) -> u64;
     fn version(&self) -> u64;
+    /// The `ABI_VERSION`s that the module can work with
     fn supported_versions(&self) -> &'static [u64];
 
+    /// The callbacks that get a `context` should not use the session for the element or location
+    /// that they get, the session operations added to `context` are done after the callback returns
+    /// The session does not see what is written to the element stream, the module should also give it
+    /// to ModuleContext::write so it can be read from the data channel while the element runs
     fn poll_element(
         &self,
         ctx: &mut std::task::Context<'_>,
         element: Arc<RwLock<Element>>,
         storage: &mut Storage,
+        context: &mut ModuleContext,
     ) -> SessionResult<()>;
 
     fn poll_location(
@@ -27,6 +91,7 @@ pub trait TModule: std::panic::UnwindSafe + Sync + Send {
         ctx: &mut std::task::Context<'_>,
         location: Arc<RwLock<Location>>,
         storage: &mut Storage,
+        context: &mut ModuleContext,
     ) -> SessionResult<()>;
 
     fn element_on_event(
@@ -34,12 +99,14 @@ pub trait TModule: std::panic::UnwindSafe + Sync + Send {
         element: Arc<RwLock<Element>>,
         event: Event,
         storage: &mut Storage,
+        context: &mut ModuleContext,
     ) -> SessionResult<()>;
     fn location_on_event(
         &self,
         location: Arc<RwLock<Location>>,
         event: Event,
         storage: &mut Storage,
+        context: &mut ModuleContext,
     ) -> SessionResult<()>;
 
     fn default_element_settings(&self) -> Settings;
@@ -49,6 +116,86 @@ pub trait TModule: std::panic::UnwindSafe + Sync + Send {
     fn supports_protocols(&self) -> &[&'static str];
     /// Should be like "html, exe"
     fn supports_extensions(&self) -> &[&'static str];
+
+    /// What the module needs, the session denies the rest when the call comes from the module
+    fn permissions(&self) -> Permissions {
+        Permissions::default()
+    }
+
+    /// The actions that can be run on the elements that use this module
+    fn element_actions(&self) -> Vec<Action> {
+        Vec::new()
+    }
+
+    /// The actions that can be run on the locations that use this module
+    fn location_actions(&self) -> Vec<Action> {
+        Vec::new()
+    }
+
+    /// Runs the element action `name`, the session checked that `args` are valid for the action
+    fn element_action(
+        &self,
+        _element: Arc<RwLock<Element>>,
+        name: &str,
+        _args: Vec<Atom>,
+        _storage: &mut Storage,
+        _context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        Err(SessionError::NoAction(name.to_string()))
+    }
+
+    /// Runs the location action `name`, the session checked that `args` are valid for the action
+    fn location_action(
+        &self,
+        _location: Arc<RwLock<Location>>,
+        name: &str,
+        _args: Vec<Atom>,
+        _storage: &mut Storage,
+        _context: &mut ModuleContext,
+    ) -> SessionResult<()> {
+        Err(SessionError::NoAction(name.to_string()))
+    }
+
+    /// Called after the module is added to the session or reloaded, before any other call
+    /// `session` is the handle of the module, what is called with it is checked with the module permissions
+    /// If it fails the module is removed
+    fn on_load(&self, _session: Session) -> SessionResult<()> {
+        Ok(())
+    }
+
+    /// Called before the module is destroyed, replaced by a reload or dropped with the session
+    /// When destroyed or reloaded every element and location was detached before
+    fn on_unload(&self) {}
+
+    /// Called when the element starts using this module, if it fails the element has no module
+    fn on_element_attached(
+        &self,
+        _element: Arc<RwLock<Element>>,
+        _storage: &mut Storage,
+    ) -> SessionResult<()> {
+        Ok(())
+    }
+
+    /// Called when the element stops using this module, after this the storage is cleared
+    fn on_element_detached(&self, _element: Arc<RwLock<Element>>, _storage: &mut Storage) {}
+
+    /// Called before the element is destroyed
+    fn on_element_destroyed(&self, _element: Arc<RwLock<Element>>, _storage: &mut Storage) {}
+
+    /// Called when the location starts using this module, if it fails the location has no module
+    fn on_location_attached(
+        &self,
+        _location: Arc<RwLock<Location>>,
+        _storage: &mut Storage,
+    ) -> SessionResult<()> {
+        Ok(())
+    }
+
+    /// Called when the location stops using this module, after this the storage is cleared
+    fn on_location_detached(&self, _location: Arc<RwLock<Location>>, _storage: &mut Storage) {}
+
+    /// Called before the location is destroyed
+    fn on_location_destroyed(&self, _location: Arc<RwLock<Location>>, _storage: &mut Storage) {}
 }
 
 pub enum ModuleSource {
@@ -56,6 +203,9 @@ pub enum ModuleSource {
     Wasm(Vec<u8>),
     /// This should be used in general
     Dynamic(PathBuf),
+    /// A dynamic module loaded by a module host process
+    /// if the module crashes only the module host dies and is restarted on the next call
+    Process(PathBuf),
     /// This only should be added when we own the LocalSession
     Box(Box<dyn TModule>),
 }
@@ -65,6 +215,7 @@ impl std::fmt::Debug for ModuleSource {
         match self {
             ModuleSource::Wasm(_) => f.write_str("ModuleSource::Wasm"),
             ModuleSource::Dynamic(_) => f.write_str("ModuleSource::Dynamic"),
+            ModuleSource::Process(_) => f.write_str("ModuleSource::Process"),
             ModuleSource::Box(_) => f.write_str("ModuleSource::Box"),
         }
     }
@@ -73,10 +224,11 @@ impl std::fmt::Debug for ModuleSource {
 pub struct Module {
     pub name: String,
     pub desc: String,
-    pub proxy: u32,
     pub module: Box<dyn TModule>,
     pub element_settings: Settings,
     pub location_settings: Settings,
+    /// Granted to the module, starts as TModule::permissions
+    pub permissions: Permissions,
 }
 
 impl std::fmt::Debug for Module {
@@ -84,13 +236,84 @@ impl std::fmt::Debug for Module {
         f.debug_struct("Module")
             .field("name", &self.name)
             .field("desc", &self.desc)
-            .field("proxy", &self.proxy)
             .field("element_settings", &self.element_settings)
             .field("location_settings", &self.location_settings)
+            .field("permissions", &self.permissions)
             .finish()
     }
 }
 
+/// Describes a loaded module
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Bytes)]
+pub struct ModuleInfo {
+    pub uid: UID,
+    pub name: String,
+    pub desc: String,
+    pub id: u64,
+    pub version: u64,
+    pub supported_versions: Vec<u64>,
+    pub protocols: Vec<String>,
+    pub extensions: Vec<String>,
+}
+
+impl ModuleInfo {
+    pub fn new(uid: UID, module: &Module) -> Self {
+        Self {
+            uid,
+            name: module.name.clone(),
+            desc: module.desc.clone(),
+            id: module.module.id(),
+            version: module.module.version(),
+            supported_versions: module.module.supported_versions().to_vec(),
+            protocols: module
+                .module
+                .supports_protocols()
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+            extensions: module
+                .module
+                .supports_extensions()
+                .iter()
+                .map(|e| e.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// The result of loading a module library found in a directory
+#[derive(Debug, Clone)]
+pub struct ModuleLoadReport {
+    pub path: PathBuf,
+    pub result: Result<ModuleInfo, RawLibraryError>,
+}
+
+/// Environment variable with more directories to search for modules, separated like PATH
+pub const MODULES_PATH_ENV: &str = "MUZZMAN_MODULES_PATH";
+
+/// Environment variable with the path of the module host executable used by ModuleSource::Process
+/// By default `muzzman-module-host` is searched next to the current executable and then in PATH
+pub const MODULE_HOST_ENV: &str = "MUZZMAN_MODULE_HOST";
+
+/// Environment variable with how many seconds the session waits for an answer of the module host
+/// By default 30, after that the module host is killed and started again
+pub const MODULE_HOST_TIMEOUT_ENV: &str = "MUZZMAN_MODULE_HOST_TIMEOUT";
+
+/// The directories where modules are searched by default
+/// `<data dir>/muzzman/modules`, `<config dir>/muzzman/modules` and the MUZZMAN_MODULES_PATH directories
+pub fn default_module_dirs() -> Vec<PathBuf> {
+    let mut module_dirs = Vec::new();
+    #[cfg(any(target_os = "windows", target_os = "linux"))]
+    {
+        module_dirs.extend(dirs::data_dir().map(|dir| dir.join("muzzman").join("modules")));
+        module_dirs.extend(dirs::config_dir().map(|dir| dir.join("muzzman").join("modules")));
+    }
+    if let Some(paths) = std::env::var_os(MODULES_PATH_ENV) {
+        module_dirs.extend(std::env::split_paths(&paths));
+    }
+    module_dirs
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Bytes)]
 pub enum RawLibraryError {
     NotFound,
@@ -107,6 +330,23 @@ pub enum RawLibraryError {
     DontHaveSymbolDefaultLocationSettings,
     DontHaveSymbolSupportsProtocols,
     DontHaveSymbolSupportsExtensions,
+    DontHaveSymbolAbiVersion,
+    DontHaveSymbolBuildId,
+    /// Host abi version, module abi version
+    IncompatibleAbi(u64, u64),
+    /// Host build id, module build id
+    IncompatibleBuild(String, String),
+    /// The host abi version is not in the module supported versions
+    UnsupportedVersion(u64),
+    /// Other library has a module with the same id and a bigger version or is already added
+    Duplicate(u64),
+    InvalidWasm(String),
+    DontHaveSymbolMemory,
+    /// The module host process could not be started or exited while loading the module
+    ModuleHost(String),
+    DontHaveSymbolManifest,
+    /// The module was loaded but the session could not add it, like when TModule::on_load failed
+    AddModule(String),
 }
 
 impl From<RawLibraryError> for SessionError {
@@ -0,0 +1,26 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// What a module is allowed to do with the session
+/// A module declares them with TModule::permissions and the user can change them with TModuleHelper::set_permissions
+/// The default is no permission
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Permissions {
+    /// The element and location paths can be set only inside this directories
+    /// and the session creates, renames and moves the file of an element only there if the module changed its path
+    pub write_paths: Vec<PathBuf>,
+    /// Can set Session.OnComplete.Command and Session.OnComplete.Unpack, they run processes
+    /// Without it they are not run for the elements and locations that use the module
+    pub spawn_processes: bool,
+    /// Can create, move and destroy elements and locations
+    pub create_elements: bool,
+    /// Can use the events of elements and locations that don't use this module
+    pub other_events: bool,
+}
+
+impl Permissions {
+    pub fn can_write(&self, path: &Path) -> bool {
+        self.write_paths.iter().any(|root| path.starts_with(root))
+    }
+}
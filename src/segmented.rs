@@ -0,0 +1,158 @@
+use std::{
+    fs::File,
+    ops::Range,
+    sync::{Arc, RwLock},
+};
+
+/// Sorted and merged byte ranges
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Ranges {
+    ranges: Vec<Range<u64>>,
+}
+
+impl Ranges {
+    pub fn insert(&mut self, range: Range<u64>) {
+        if range.is_empty() {
+            return;
+        }
+
+        let start = self.ranges.partition_point(|r| r.end < range.start);
+        let end = self.ranges.partition_point(|r| r.start <= range.end);
+
+        let mut merged = range;
+        for r in &self.ranges[start..end] {
+            merged.start = merged.start.min(r.start);
+            merged.end = merged.end.max(r.end);
+        }
+        self.ranges.splice(start..end, [merged]);
+    }
+
+    pub fn contains(&self, range: &Range<u64>) -> bool {
+        range.is_empty()
+            || self
+                .ranges
+                .iter()
+                .any(|r| r.start <= range.start && range.end <= r.end)
+    }
+
+    /// How many bytes are in all the ranges
+    pub fn len(&self) -> u64 {
+        self.ranges.iter().map(|r| r.end - r.start).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// The ranges from 0..size that are not in self
+    pub fn missing(&self, size: u64) -> Vec<Range<u64>> {
+        let mut missing = Vec::new();
+        let mut last = 0;
+        for r in self.ranges.iter() {
+            if r.start >= size {
+                break;
+            }
+            if r.start > last {
+                missing.push(last..r.start);
+            }
+            last = r.end;
+        }
+        if last < size {
+            missing.push(last..size);
+        }
+        missing
+    }
+
+    pub fn is_complete(&self, size: u64) -> bool {
+        self.missing(size).is_empty()
+    }
+
+    /// From 0.0 to 1.0
+    pub fn progress(&self, size: u64) -> f32 {
+        if size == 0 {
+            return 1.0;
+        }
+        let written: u64 = self
+            .ranges
+            .iter()
+            .map(|r| r.end.min(size).saturating_sub(r.start))
+            .sum();
+        written as f32 / size as f32
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Range<u64>> {
+        self.ranges.iter()
+    }
+}
+
+/// A file that can be written at offsets from multiple segments at the same time
+/// Every clone shares the same file and ranges
+#[derive(Clone, Debug)]
+pub struct SegmentedFile {
+    file: Arc<File>,
+    ranges: Arc<RwLock<Ranges>>,
+}
+
+impl SegmentedFile {
+    pub fn new(file: File) -> Self {
+        Self::with_ranges(file, Ranges::default())
+    }
+
+    /// When resuming, ranges should be what was written before
+    pub fn with_ranges(file: File, ranges: Ranges) -> Self {
+        Self {
+            file: Arc::new(file),
+            ranges: Arc::new(RwLock::new(ranges)),
+        }
+    }
+
+    /// Writes all the buf at offset and marks the range as written
+    pub fn write_at(&self, offset: u64, buf: &[u8]) -> std::io::Result<()> {
+        write_all_at(&self.file, offset, buf)?;
+        self.ranges
+            .write()
+            .unwrap()
+            .insert(offset..offset + buf.len() as u64);
+        Ok(())
+    }
+
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> std::io::Result<usize> {
+        read_at(&self.file, offset, buf)
+    }
+
+    pub fn ranges(&self) -> Ranges {
+        self.ranges.read().unwrap().clone()
+    }
+
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn write_all_at(file: &File, offset: u64, buf: &[u8]) -> std::io::Result<()> {
+    std::os::unix::fs::FileExt::write_all_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+pub(crate) fn write_all_at(file: &File, mut offset: u64, mut buf: &[u8]) -> std::io::Result<()> {
+    while !buf.is_empty() {
+        let n = std::os::windows::fs::FileExt::seek_write(file, buf, offset)?;
+        if n == 0 {
+            return Err(std::io::ErrorKind::WriteZero.into());
+        }
+        buf = &buf[n..];
+        offset += n as u64;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+pub(crate) fn read_at(file: &File, offset: u64, buf: &mut [u8]) -> std::io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+pub(crate) fn read_at(file: &File, offset: u64, buf: &mut [u8]) -> std::io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
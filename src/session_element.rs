@@ -4,6 +4,12 @@ use crate::prelude::*;
 
 pub trait TSessionElement {
     fn create_element(&self, location: LocationId, name: String) -> SessionResult<ElementId>;
+    /// Creates an element named from the url with the best module for the url
+    fn create_element_from_url(
+        &self,
+        location: LocationId,
+        url: String,
+    ) -> SessionResult<ElementId>;
     fn get_element(&self, path: Vec<usize>) -> SessionResult<ElementId>;
 
     fn move_element(&self, element: ElementId, location: LocationId) -> SessionResult<()>;
@@ -54,6 +60,31 @@ pub trait TSessionElement {
         module_id: Option<ModuleId>,
     ) -> SessionResult<()>;
 
+    fn element_get_proxy(&self, element: ElementId) -> SessionResult<Option<ModuleId>>;
+    /// The element module (the front) gives the transfer to the `proxy` module (the back),
+    /// like a module that finds the media url of a page and gives it to the HTTP module
+    ///
+    /// While proxied the session polls the back module instead of the front module,
+    /// the back module has its own storage and gets the element settings that the front module does not have.
+    /// Both modules work on the same element, so the status, progress and stream set by the back module are the ones of the element
+    /// and the front module sees them, when the back module completes the element is completed.
+    ///
+    /// The proxy ends with `None`, when the transfer fails or when the front or back module is changed, reloaded or destroyed,
+    /// then the front module polls the element again, so after an error enabling the element starts again from the front module.
+    ///
+    /// Only the front module or the user can change the proxy.
+    /// To use child elements instead, the front module creates them with the back module and follows them like any element.
+    fn element_set_proxy(&self, element: ElementId, proxy: Option<ModuleId>) -> SessionResult<()>;
+
+    /// The actions of the element module, empty if the element has no module
+    fn element_get_actions(&self, element: ElementId) -> SessionResult<Vec<Action>>;
+    fn element_run_action(
+        &self,
+        element: ElementId,
+        name: String,
+        args: Vec<Atom>,
+    ) -> SessionResult<()>;
+
     fn element_wait(&self, element: ElementId) -> SessionResult<()>;
 
     fn destroy_element(&self, element: ElementId) -> SessionResult<()>;
@@ -73,6 +73,15 @@ pub trait TSessionLocation {
         module_id: Option<ModuleId>,
     ) -> SessionResult<()>;
 
+    /// The actions of the location module, empty if the location has no module
+    fn location_get_actions(&self, location: LocationId) -> SessionResult<Vec<Action>>;
+    fn location_run_action(
+        &self,
+        location: LocationId,
+        name: String,
+        args: Vec<Atom>,
+    ) -> SessionResult<()>;
+
     fn move_location(
         &self,
         location: LocationId,
@@ -1,8 +1,21 @@
+use std::path::PathBuf;
+
 use crate::prelude::*;
 
 pub trait TSessionModule {
     fn add_module(&self, source: ModuleSource) -> SessionResult<ModuleId>;
+    /// Loads all the module libraries and `.wasm` modules from `dirs`, directories that don't exist are ignored
+    /// For the modules with the same id only the one with the biggest version is added
+    fn load_modules(&self, dirs: Vec<PathBuf>) -> SessionResult<Vec<ModuleLoadReport>>;
     fn get_module(&self, path: usize) -> SessionResult<ModuleId>;
+    /// In the order that they were added
+    fn get_modules(&self) -> SessionResult<Vec<ModuleId>>;
+    fn get_modules_info(&self) -> SessionResult<Vec<ModuleInfo>>;
+    /// Finds the module with the `TModule::id`
+    fn find_module_by_id(&self, id: u64) -> SessionResult<ModuleId>;
+    fn find_module_by_name(&self, name: String) -> SessionResult<ModuleId>;
+
+    fn module_info(&self, module: ModuleId) -> SessionResult<ModuleInfo>;
 
     fn module_get_element_settings(&self, module: ModuleId) -> SessionResult<Settings>;
     fn module_set_element_settings(
@@ -26,5 +39,17 @@ pub trait TSessionModule {
     fn module_path(&self, module: ModuleId) -> SessionResult<usize>;
 
     fn module_id(&self, module: ModuleId) -> SessionResult<u64>;
+
+    fn module_get_permissions(&self, module: ModuleId) -> SessionResult<Permissions>;
+    /// Cannot be called by a module
+    fn module_set_permissions(
+        &self,
+        module: ModuleId,
+        permissions: Permissions,
+    ) -> SessionResult<()>;
+    /// Replaces the module with `source` that should have the same id
+    /// The elements that use the module are paused while is replaced
+    /// The permissions granted to the module are kept
+    fn module_reload(&self, module: ModuleId, source: ModuleSource) -> SessionResult<()>;
     fn destroy_module(&self, module: ModuleId) -> SessionResult<()>;
 }
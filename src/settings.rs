@@ -1,9 +1,8 @@
-use std::{
-    collections::HashMap,
-    ops::{RangeFull, RangeInclusive},
-};
+use std::collections::HashMap;
 
-#[derive(Clone, Debug, PartialEq, Default)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
 pub struct Settings {
     settings: HashMap<String, Setting>,
 }
@@ -34,9 +33,122 @@ impl Settings {
             setting.set_default();
         }
     }
+
+    pub fn get_mut(&mut self, name: impl Into<String>) -> Option<&mut Setting> {
+        self.settings.get_mut(&name.into())
+    }
+
+    pub fn remove(&mut self, name: impl Into<String>) -> Option<Setting> {
+        self.settings.remove(&name.into())
+    }
+
+    pub fn iter(&self) -> std::collections::hash_map::Iter<'_, String, Setting> {
+        self.settings.iter()
+    }
+
+    /// Keeps the values from `old` for the settings that exist in self and are still valid
+    pub fn migrate_values(&mut self, old: &Settings) {
+        for (name, old) in old.iter() {
+            if let Some(setting) = self.settings.get_mut(name) {
+                let value = std::mem::replace(&mut setting.value, old.value.clone());
+                if !setting.validate() {
+                    setting.value = value;
+                }
+            }
+        }
+    }
+
+    /// Replaces the module settings with `module`, the values already set are kept if still valid
+    /// The settings that `module` does not have are dropped, except the session settings
+    /// The session settings of `module` are ignored, a module cannot set them on every element
+    pub fn merge_module(&mut self, module: &Settings) {
+        let mut settings = module.clone();
+        settings
+            .settings
+            .retain(|name, _| !session_settings::is_session_setting(name));
+        settings.migrate_values(self);
+        for (name, setting) in self.settings.drain() {
+            if session_settings::is_session_setting(&name) {
+                settings.settings.insert(name, setting);
+            }
+        }
+        *self = settings;
+    }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+/// Settings handled by the session and not by the module
+/// They can be added on any element or location, an element inherits them from the parent locations
+pub mod session_settings {
+    use super::{Setting, Settings};
+
+    pub const PREFIX: &str = "Session.";
+
+    /// Atom::B, the element is written to `path.part` and renamed to `path` when completed
+    pub const PARTIAL_FILE: &str = "Session.PartialFile";
+    /// Atom::U, only for locations, max bytes that the elements in the location and sub locations can download
+    /// 0 means no quota
+    pub const QUOTA: &str = "Session.Quota";
+    /// Atom::S, one of CHECKSUM_ALGORITHMS, used to hash the element when completed
+    pub const CHECKSUM_ALGORITHM: &str = "Session.ChecksumAlgorithm";
+    /// Atom::S, the expected digest in hex, without it the digest is only stored in the element data
+    pub const CHECKSUM: &str = "Session.Checksum";
+
+    pub const CHECKSUM_ALGORITHMS: [&str; 4] = ["SHA-256", "SHA-1", "MD5", "BLAKE3"];
+    /// Atom::S, one of ON_COLLISION_POLICIES, what to do when the element path is already used
+    pub const ON_COLLISION: &str = "Session.OnCollision";
+
+    pub const ON_COLLISION_POLICIES: [&str; 4] = ["Overwrite", "Skip", "Rename", "Fail"];
+
+    /// Atom::U, the TModule::id of the module that is preferred when a module is selected from an url
+    pub const PREFERRED_MODULE: &str = "Session.PreferredModule";
+
+    /// Atom::S, when completed the file is moved in the location with this path, like "0/1", "/" is the default location
+    /// The location should exist, it is not created
+    pub const ON_COMPLETE_MOVE: &str = "Session.OnComplete.Move";
+    /// Atom::B, when completed the archive is unpacked next to it
+    pub const ON_COMPLETE_UNPACK: &str = "Session.OnComplete.Unpack";
+    /// Atom::S, when completed this command is run with the path as the last argument
+    /// The arguments are split on whitespace, a command with quotes or backslashes is rejected
+    pub const ON_COMPLETE_COMMAND: &str = "Session.OnComplete.Command";
+    /// Atom::S, when completed Event::Custom with this is emitted
+    pub const ON_COMPLETE_EVENT: &str = "Session.OnComplete.Event";
+
+    pub fn is_session_setting(name: &str) -> bool {
+        name.starts_with(PREFIX)
+    }
+
+    /// Should be added on the default location
+    pub fn defaults() -> Settings {
+        let mut settings = Settings::default();
+        settings.add(
+            PARTIAL_FILE,
+            Setting::new(
+                false,
+                vec![true, false],
+                "Write to a .part file and rename it when completed",
+            ),
+        );
+        settings.add(
+            QUOTA,
+            Setting::new(
+                0u64,
+                Vec::<u64>::new(),
+                "Max bytes that can be downloaded in this location, 0 is unlimited",
+            ),
+        );
+        settings.add(
+            ON_COLLISION,
+            Setting::new(
+                "Rename",
+                ON_COLLISION_POLICIES.to_vec(),
+                "What to do when the file already exists or is used by other element",
+            ),
+        );
+        settings
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Setting {
     pub value: Atom,
     default: Atom,
@@ -80,8 +192,9 @@ impl Setting {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub enum Atom {
+    B(bool),
     I(i64),
     U(u64),
     F(f64),
@@ -91,6 +204,7 @@ pub enum Atom {
 impl std::fmt::Display for Atom {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Atom::B(v) => v.fmt(f),
             Atom::I(v) => v.fmt(f),
             Atom::U(v) => v.fmt(f),
             Atom::F(v) => v.fmt(f),
@@ -99,6 +213,36 @@ impl std::fmt::Display for Atom {
     }
 }
 
+impl Atom {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Atom::B(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Atom::U(v) => Some(*v),
+            Atom::I(v) => u64::try_from(*v).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Atom::S(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for Atom {
+    fn from(value: bool) -> Self {
+        Self::B(value)
+    }
+}
+
 impl From<String> for Atom {
     fn from(value: String) -> Self {
         Self::S(value)
@@ -42,11 +42,11 @@ impl Storage {
         self.data.pop()
     }
 
-    pub fn iter(&self) -> std::slice::Iter<Box<dyn Any + Send + Sync>> {
+    pub fn iter(&self) -> std::slice::Iter<'_, Box<dyn Any + Send + Sync>> {
         self.data.iter()
     }
 
-    pub fn iter_mut(&mut self) -> std::slice::IterMut<Box<dyn Any + Send + Sync>> {
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Box<dyn Any + Send + Sync>> {
         self.data.iter_mut()
     }
 }
@@ -1,17 +1,27 @@
-use std::io::{BufReader, BufWriter, Read, Write};
+use std::{
+    io::{BufReader, BufWriter, Cursor, ErrorKind, Read, Seek, SeekFrom, Write},
+    process::{Child, Command, Stdio},
+};
 
-use crate::prelude::SessionError;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::{Ranges, SegmentedFile, SessionError};
 
 pub type UID = u64;
 pub type SessionResult<T> = std::result::Result<T, SessionError>;
 
-#[derive(Debug, Clone)]
+/// The default Element/Location buffer_size
+pub const DEFAULT_BUFFER_SIZE: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Event {
     /// This will be receive if on that element/location is TCommonSession::write
     NewData(Vec<u8>),
     ProgressChanged(UID),
     Completed(UID),
     Error(UID),
+    /// Emitted by Session.OnComplete.Event
+    Custom(String),
     From(UID, Box<Event>),
 }
 
@@ -22,22 +32,126 @@ pub enum Stream {
         BufWriter<std::fs::File>,
         BufReader<std::fs::File>,
     ),
+    /// Can be written at offsets from many segments, the u64 is the position for Read/Write/Seek
+    Segmented(SegmentedFile, u64),
+    /// Growable in memory buffer
+    Memory(Cursor<Vec<u8>>),
+    /// Writes to the stdin and reads from the stdout of the child process
+    Pipe(Child),
+    /// Discards everything, counts how many bytes were written
+    Null(u64),
+    /// Writes to every stream, reads from the first one
+    Tee(Vec<Stream>),
     None,
 }
 
-impl Clone for Stream {
-    fn clone(&self) -> Self {
-        match self {
-            Stream::File(_, _, _) => unimplemented!("Cannot clone Stream type File"),
+impl Stream {
+    pub fn from_file(file: std::fs::File) -> std::io::Result<Self> {
+        let writer = BufWriter::new(file.try_clone()?);
+        let reader = BufReader::new(file.try_clone()?);
+        Ok(Self::File(file, writer, reader))
+    }
+
+    /// Pipe will spawn the command with piped stdin and stdout
+    pub fn pipe(command: &mut Command) -> std::io::Result<Self> {
+        let child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        Ok(Self::Pipe(child))
+    }
+
+    /// Unflushed data of File is not in the clone
+    /// Pipe cannot be cloned
+    pub fn try_clone(&self) -> std::io::Result<Self> {
+        Ok(match self {
+            Stream::File(file, _, _) => Self::from_file(file.try_clone()?)?,
+            Stream::Segmented(file, position) => Stream::Segmented(file.clone(), *position),
+            Stream::Memory(cursor) => Stream::Memory(cursor.clone()),
+            Stream::Pipe(_) => {
+                return Err(std::io::Error::new(
+                    ErrorKind::Unsupported,
+                    "Cannot clone Stream type Pipe",
+                ))
+            }
+            Stream::Null(count) => Stream::Null(*count),
+            Stream::Tee(streams) => Stream::Tee(
+                streams
+                    .iter()
+                    .map(|stream| stream.try_clone())
+                    .collect::<std::io::Result<Vec<Stream>>>()?,
+            ),
             Stream::None => Stream::None,
+        })
+    }
+
+    /// Writes all the buf at offset without changing the position
+    pub fn write_at(&mut self, offset: u64, buf: &[u8]) -> std::io::Result<()> {
+        match self {
+            Stream::File(file, writer, _) => {
+                writer.flush()?;
+                crate::segmented::write_all_at(file, offset, buf)
+            }
+            Stream::Segmented(file, _) => file.write_at(offset, buf),
+            Stream::Memory(cursor) => {
+                let position = cursor.position();
+                cursor.set_position(offset);
+                let res = cursor.write_all(buf);
+                cursor.set_position(position);
+                res
+            }
+            Stream::Tee(streams) => {
+                for stream in streams.iter_mut() {
+                    stream.write_at(offset, buf)?;
+                }
+                Ok(())
+            }
+            Stream::Pipe(_) | Stream::Null(_) | Stream::None => Err(std::io::Error::new(
+                ErrorKind::Unsupported,
+                "Stream type cannot write at offset",
+            )),
+        }
+    }
+
+    /// A handle that can be sent to every segment of the element
+    pub fn segmented(&self) -> Option<SegmentedFile> {
+        match self {
+            Stream::Segmented(file, _) => Some(file.clone()),
+            Stream::Tee(streams) => streams.iter().find_map(|stream| stream.segmented()),
+            _ => None,
         }
     }
+
+    /// What was written with write_at, only for Segmented
+    pub fn written_ranges(&self) -> Option<Ranges> {
+        self.segmented().map(|file| file.ranges())
+    }
 }
 
 impl Write for Stream {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         match self {
             Stream::File(_, writer, _) => writer.write(buf),
+            Stream::Segmented(file, position) => {
+                file.write_at(*position, buf)?;
+                *position += buf.len() as u64;
+                Ok(buf.len())
+            }
+            Stream::Memory(cursor) => cursor.write(buf),
+            Stream::Pipe(child) => match &mut child.stdin {
+                Some(stdin) => stdin.write(buf),
+                None => Err(ErrorKind::BrokenPipe.into()),
+            },
+            Stream::Null(count) => {
+                *count += buf.len() as u64;
+                Ok(buf.len())
+            }
+            Stream::Tee(streams) => {
+                for stream in streams.iter_mut() {
+                    stream.write_all(buf)?;
+                }
+                Ok(buf.len())
+            }
             Stream::None => Ok(0),
         }
     }
@@ -45,6 +159,19 @@ impl Write for Stream {
     fn flush(&mut self) -> std::io::Result<()> {
         match self {
             Stream::File(_, writer, _) => writer.flush(),
+            Stream::Segmented(_, _) => Ok(()),
+            Stream::Memory(cursor) => cursor.flush(),
+            Stream::Pipe(child) => match &mut child.stdin {
+                Some(stdin) => stdin.flush(),
+                None => Ok(()),
+            },
+            Stream::Null(_) => Ok(()),
+            Stream::Tee(streams) => {
+                for stream in streams.iter_mut() {
+                    stream.flush()?;
+                }
+                Ok(())
+            }
             Stream::None => Ok(()),
         }
     }
@@ -54,7 +181,59 @@ impl Read for Stream {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         match self {
             Stream::File(_, _, reader) => reader.read(buf),
-            Stream::None => Ok(0),
+            Stream::Segmented(file, position) => {
+                let len = file.read_at(*position, buf)?;
+                *position += len as u64;
+                Ok(len)
+            }
+            Stream::Memory(cursor) => cursor.read(buf),
+            Stream::Pipe(child) => match &mut child.stdout {
+                Some(stdout) => stdout.read(buf),
+                None => Ok(0),
+            },
+            Stream::Tee(streams) => match streams.first_mut() {
+                Some(stream) => stream.read(buf),
+                None => Ok(0),
+            },
+            Stream::Null(_) | Stream::None => Ok(0),
+        }
+    }
+}
+
+impl Seek for Stream {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            Stream::File(_, writer, reader) => {
+                writer.flush()?;
+                // all the handles share the same cursor
+                reader.seek(pos)
+            }
+            Stream::Segmented(file, position) => {
+                let new = match pos {
+                    SeekFrom::Start(offset) => Some(offset),
+                    SeekFrom::Current(offset) => position.checked_add_signed(offset),
+                    SeekFrom::End(offset) => {
+                        file.file().metadata()?.len().checked_add_signed(offset)
+                    }
+                };
+                let Some(new) = new else {
+                    return Err(ErrorKind::InvalidInput.into());
+                };
+                *position = new;
+                Ok(new)
+            }
+            Stream::Memory(cursor) => cursor.seek(pos),
+            Stream::Tee(streams) => {
+                let mut res = Err(ErrorKind::Unsupported.into());
+                for stream in streams.iter_mut().rev() {
+                    res = Ok(stream.seek(pos)?);
+                }
+                res
+            }
+            Stream::Pipe(_) | Stream::Null(_) | Stream::None => Err(std::io::Error::new(
+                ErrorKind::Unsupported,
+                "Stream type cannot seek",
+            )),
         }
     }
 }